@@ -0,0 +1,9 @@
+create table tag (
+    id serial primary key,
+    guild_id int8 not null,
+    name text not null,
+    content text not null,
+    owner_id int8 not null,
+    uses int4 not null default 0,
+    unique(guild_id, name)
+);
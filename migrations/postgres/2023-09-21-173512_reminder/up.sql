@@ -0,0 +1,11 @@
+create table reminder (
+    id serial primary key,
+    user_id int8 not null,
+    guild_id int8,
+    channel_id int8 not null,
+    message_id int8,
+    content text not null,
+    remind_at timestamptz not null
+);
+
+create index reminder_remind_at on reminder(remind_at);
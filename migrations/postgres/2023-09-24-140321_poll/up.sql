@@ -0,0 +1,30 @@
+create table poll (
+    id serial primary key,
+    guild_id int8 not null,
+    channel_id int8 not null,
+    message_id int8,
+    author_id int8 not null,
+    question text not null,
+    multiple bool not null default false,
+    anonymous bool not null default false,
+    closes_at timestamptz,
+    closed bool not null default false
+);
+
+create table poll_option (
+    id serial primary key,
+    poll_id int4 not null references poll(id) on delete cascade,
+    position int4 not null,
+    label text not null,
+    unique(poll_id, position)
+);
+
+create table poll_vote (
+    id serial primary key,
+    poll_id int4 not null references poll(id) on delete cascade,
+    user_id int8 not null,
+    position int4 not null,
+    unique(poll_id, user_id, position)
+);
+
+create index poll_closes_at on poll(closes_at) where not closed;
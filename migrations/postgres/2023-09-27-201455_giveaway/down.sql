@@ -0,0 +1,2 @@
+drop table giveaway_entry;
+drop table giveaway;
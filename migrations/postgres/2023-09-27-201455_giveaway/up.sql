@@ -0,0 +1,23 @@
+create table giveaway (
+    id serial primary key,
+    guild_id int8 not null,
+    channel_id int8 not null,
+    message_id int8,
+    host_id int8 not null,
+    prize text not null,
+    winners int4 not null default 1,
+    required_role_id int8,
+    min_account_age int8,
+    ends_at timestamptz not null,
+    ended bool not null default false
+);
+
+create table giveaway_entry (
+    id serial primary key,
+    giveaway_id int4 not null references giveaway(id) on delete cascade,
+    user_id int8 not null,
+    won bool not null default false,
+    unique(giveaway_id, user_id)
+);
+
+create index giveaway_ends_at on giveaway(ends_at) where not ended;
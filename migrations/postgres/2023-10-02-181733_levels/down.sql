@@ -0,0 +1,3 @@
+drop table level_reward;
+drop table xp_channel;
+drop table xp_member;
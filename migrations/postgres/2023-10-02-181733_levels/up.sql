@@ -0,0 +1,26 @@
+create table xp_member (
+    id serial primary key,
+    guild_id int8 not null,
+    user_id int8 not null,
+    xp int8 not null default 0,
+    last_award timestamptz not null default now(),
+    unique(guild_id, user_id)
+);
+
+create index xp_member_leaderboard on xp_member(guild_id, xp desc);
+
+create table xp_channel (
+    id serial primary key,
+    guild_id int8 not null,
+    channel_id int8 not null,
+    multiplier float8 not null,
+    unique(guild_id, channel_id)
+);
+
+create table level_reward (
+    id serial primary key,
+    guild_id int8 not null,
+    level int4 not null,
+    role_id int8 not null,
+    unique(guild_id, role_id)
+);
@@ -0,0 +1,2 @@
+drop table mod_config;
+drop table mod_cases;
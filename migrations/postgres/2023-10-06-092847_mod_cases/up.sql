@@ -0,0 +1,22 @@
+create table mod_cases (
+    id serial primary key,
+    guild_id int8 not null,
+    case_number int4 not null,
+    action text not null,
+    user_id int8 not null,
+    moderator_id int8 not null,
+    reason text,
+    duration int8,
+    expires_at timestamptz,
+    resolved bool not null default false,
+    log_message_id int8,
+    created_at timestamptz not null default now(),
+    unique(guild_id, case_number)
+);
+
+create index mod_cases_expires_at on mod_cases(expires_at) where not resolved;
+
+create table mod_config (
+    guild_id int8 primary key,
+    log_channel_id int8
+);
@@ -0,0 +1,2 @@
+drop table message_log_ignore;
+drop table message_log_config;
@@ -0,0 +1,12 @@
+create table message_log_config (
+    guild_id int8 primary key,
+    channel_id int8 not null
+);
+
+create table message_log_ignore (
+    id serial primary key,
+    guild_id int8 not null,
+    target_id int8 not null,
+    kind text not null,
+    unique(guild_id, target_id)
+);
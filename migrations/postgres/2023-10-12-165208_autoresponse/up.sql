@@ -0,0 +1,13 @@
+create table autoresponse (
+    id serial primary key,
+    guild_id int8 not null,
+    trigger text not null,
+    kind text not null,
+    actions text not null,
+    response text,
+    timeout int8,
+    channel_id int8,
+    cooldown int4 not null default 0
+);
+
+create index autoresponse_guild_id on autoresponse(guild_id);
@@ -0,0 +1,11 @@
+create table greeting (
+    id serial primary key,
+    guild_id int8 not null,
+    kind text not null,
+    channel_id int8,
+    template text not null,
+    embed bool not null default false,
+    dm bool not null default false,
+    role_menu text,
+    unique(guild_id, kind)
+);
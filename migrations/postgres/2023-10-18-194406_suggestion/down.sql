@@ -0,0 +1,3 @@
+drop table suggestion_vote;
+drop table suggestion;
+drop table suggestion_config;
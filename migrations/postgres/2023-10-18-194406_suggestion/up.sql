@@ -0,0 +1,24 @@
+create table suggestion_config (
+    guild_id int8 primary key,
+    channel_id int8 not null
+);
+
+create table suggestion (
+    id serial primary key,
+    guild_id int8 not null,
+    channel_id int8 not null,
+    message_id int8,
+    author_id int8 not null,
+    content text not null,
+    status text not null default 'Open',
+    reason text,
+    created_at timestamptz not null default now()
+);
+
+create table suggestion_vote (
+    id serial primary key,
+    suggestion_id int4 not null references suggestion(id) on delete cascade,
+    user_id int8 not null,
+    upvote bool not null,
+    unique(suggestion_id, user_id)
+);
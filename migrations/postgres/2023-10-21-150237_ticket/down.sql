@@ -0,0 +1,2 @@
+drop table ticket;
+drop table ticket_config;
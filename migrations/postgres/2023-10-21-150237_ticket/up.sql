@@ -0,0 +1,19 @@
+create table ticket_config (
+    guild_id int8 primary key,
+    staff_role_id int8,
+    log_channel_id int8
+);
+
+create table ticket (
+    id serial primary key,
+    guild_id int8 not null,
+    channel_id int8,
+    opener_id int8 not null,
+    claimed_by int8,
+    closed bool not null default false,
+    created_at timestamptz not null default now(),
+    closed_at timestamptz
+);
+
+-- One open ticket per user, also when they click twice
+create unique index ticket_opener on ticket(guild_id, opener_id) where not closed;
@@ -0,0 +1,3 @@
+drop table bean_ledger;
+drop function bean_ledger_immutable;
+drop table bean_account;
@@ -0,0 +1,31 @@
+create table bean_account (
+    id serial primary key,
+    guild_id int8 not null,
+    user_id int8 not null,
+    balance int8 not null default 0,
+    last_daily timestamptz,
+    unique(guild_id, user_id),
+    check (balance >= 0)
+);
+
+create table bean_ledger (
+    id serial primary key,
+    guild_id int8 not null,
+    user_id int8 not null,
+    amount int8 not null,
+    kind text not null,
+    counterparty_id int8,
+    created_at timestamptz not null default now()
+);
+
+create index bean_ledger_user on bean_ledger(guild_id, user_id);
+
+-- The ledger is append only
+create function bean_ledger_immutable() returns trigger as $$
+begin
+    raise exception 'bean_ledger entries can not be changed';
+end;
+$$ language plpgsql;
+
+create trigger bean_ledger_immutable before update or delete on bean_ledger
+    for each row execute function bean_ledger_immutable();
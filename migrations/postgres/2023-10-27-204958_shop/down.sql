@@ -0,0 +1,2 @@
+drop table shop_purchase;
+drop table shop_item;
@@ -0,0 +1,22 @@
+create table shop_item (
+    id serial primary key,
+    guild_id int8 not null,
+    role_id int8 not null,
+    price int8 not null check (price > 0),
+    duration int8,
+    unique(guild_id, role_id)
+);
+
+create table shop_purchase (
+    id serial primary key,
+    guild_id int8 not null,
+    user_id int8 not null,
+    role_id int8 not null,
+    price int8 not null,
+    expires_at timestamptz,
+    active bool not null default true,
+    refunded bool not null default false,
+    created_at timestamptz not null default now()
+);
+
+create index shop_purchase_expires_at on shop_purchase(expires_at) where active;
@@ -0,0 +1,2 @@
+drop table birthday;
+drop table birthday_config;
@@ -0,0 +1,18 @@
+create table birthday_config (
+    guild_id int8 primary key,
+    channel_id int8,
+    role_id int8
+);
+
+create table birthday (
+    id serial primary key,
+    guild_id int8 not null,
+    user_id int8 not null,
+    day int4 not null check (day between 1 and 31),
+    month int4 not null check (month between 1 and 12),
+    year int4,
+    timezone text not null default 'UTC',
+    celebrated_year int4,
+    role_until timestamptz,
+    unique(guild_id, user_id)
+);
@@ -0,0 +1,16 @@
+create table quote (
+    id serial primary key,
+    guild_id int8 not null,
+    number int4 not null,
+    channel_id int8 not null,
+    message_id int8 not null,
+    author_id int8 not null,
+    author_name text not null,
+    author_avatar text,
+    content text not null,
+    image_url text,
+    saved_by int8 not null,
+    created_at timestamptz not null,
+    unique(guild_id, number),
+    unique(guild_id, message_id)
+);
@@ -0,0 +1 @@
+alter table fav_msgs drop column note;
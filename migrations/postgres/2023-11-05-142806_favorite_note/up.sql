@@ -0,0 +1 @@
+alter table fav_msgs add column note text;
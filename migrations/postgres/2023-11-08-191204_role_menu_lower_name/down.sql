@@ -0,0 +1 @@
+drop index role_menu_guild_id_lower_name_key;
@@ -0,0 +1,5 @@
+-- Menus are looked up ignoring case, so their names have to be unique that way too.
+-- Existing menus that only differ in case keep the oldest name, the others get their id appended.
+update role_menu set name = name || ' (' || id || ')'
+where id not in (select min(id) from role_menu group by guild_id, lower(name));
+create unique index role_menu_guild_id_lower_name_key on role_menu (guild_id, lower(name));
@@ -0,0 +1 @@
+drop table disabled_module;
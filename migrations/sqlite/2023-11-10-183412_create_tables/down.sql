@@ -0,0 +1,31 @@
+drop table quote;
+drop table birthday;
+drop table birthday_config;
+drop table shop_purchase;
+drop table shop_item;
+drop table bean_ledger;
+drop table bean_account;
+drop table ticket;
+drop table ticket_config;
+drop table suggestion_vote;
+drop table suggestion;
+drop table suggestion_config;
+drop table greeting;
+drop table autoresponse;
+drop table message_log_ignore;
+drop table message_log_config;
+drop table mod_config;
+drop table mod_cases;
+drop table level_reward;
+drop table xp_channel;
+drop table xp_member;
+drop table giveaway_entry;
+drop table giveaway;
+drop table poll_vote;
+drop table poll_option;
+drop table poll;
+drop table reminder;
+drop table tag;
+drop table role_option;
+drop table role_menu;
+drop table fav_msgs;
@@ -0,0 +1,332 @@
+-- SQLite starts out with the schema the Postgres migrations add up to.
+-- Timestamps are text in UTC like diesel writes them, so they compare in order.
+
+create table fav_msgs (
+    id integer primary key autoincrement,
+    user_id bigint not null,
+    guild_id bigint not null,
+    channel_id bigint not null,
+    message_id bigint not null,
+    note text,
+    unique(user_id, guild_id, channel_id, message_id)
+);
+
+create table role_menu (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    name text not null,
+    max_selectable integer,
+    unique(guild_id, name)
+);
+
+create unique index role_menu_guild_id_lower_name_key on role_menu (guild_id, lower(name));
+
+create table role_option (
+    id integer primary key autoincrement,
+    role_id bigint not null,
+    description text,
+    emoji text,
+    role_menu_id integer not null references role_menu(id) on delete cascade
+);
+
+create table tag (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    name text not null,
+    content text not null,
+    owner_id bigint not null,
+    uses integer not null default 0,
+    unique(guild_id, name)
+);
+
+create table reminder (
+    id integer primary key autoincrement,
+    user_id bigint not null,
+    guild_id bigint,
+    channel_id bigint not null,
+    message_id bigint,
+    content text not null,
+    remind_at text not null
+);
+
+create index reminder_remind_at on reminder(remind_at);
+
+create table poll (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    channel_id bigint not null,
+    message_id bigint,
+    author_id bigint not null,
+    question text not null,
+    multiple boolean not null default false,
+    anonymous boolean not null default false,
+    closes_at text,
+    closed boolean not null default false
+);
+
+create table poll_option (
+    id integer primary key autoincrement,
+    poll_id integer not null references poll(id) on delete cascade,
+    position integer not null,
+    label text not null,
+    unique(poll_id, position)
+);
+
+create table poll_vote (
+    id integer primary key autoincrement,
+    poll_id integer not null references poll(id) on delete cascade,
+    user_id bigint not null,
+    position integer not null,
+    unique(poll_id, user_id, position)
+);
+
+create index poll_closes_at on poll(closes_at) where not closed;
+
+create table giveaway (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    channel_id bigint not null,
+    message_id bigint,
+    host_id bigint not null,
+    prize text not null,
+    winners integer not null default 1,
+    required_role_id bigint,
+    min_account_age bigint,
+    ends_at text not null,
+    ended boolean not null default false
+);
+
+create table giveaway_entry (
+    id integer primary key autoincrement,
+    giveaway_id integer not null references giveaway(id) on delete cascade,
+    user_id bigint not null,
+    won boolean not null default false,
+    unique(giveaway_id, user_id)
+);
+
+create index giveaway_ends_at on giveaway(ends_at) where not ended;
+
+create table xp_member (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    user_id bigint not null,
+    xp bigint not null default 0,
+    last_award text not null default (strftime('%Y-%m-%d %H:%M:%f+00:00', 'now')),
+    unique(guild_id, user_id)
+);
+
+create index xp_member_leaderboard on xp_member(guild_id, xp desc);
+
+create table xp_channel (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    channel_id bigint not null,
+    multiplier double precision not null,
+    unique(guild_id, channel_id)
+);
+
+create table level_reward (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    level integer not null,
+    role_id bigint not null,
+    unique(guild_id, role_id)
+);
+
+create table mod_cases (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    case_number integer not null,
+    action text not null,
+    user_id bigint not null,
+    moderator_id bigint not null,
+    reason text,
+    duration bigint,
+    expires_at text,
+    resolved boolean not null default false,
+    log_message_id bigint,
+    created_at text not null default (strftime('%Y-%m-%d %H:%M:%f+00:00', 'now')),
+    unique(guild_id, case_number)
+);
+
+create index mod_cases_expires_at on mod_cases(expires_at) where not resolved;
+
+create table mod_config (
+    guild_id bigint primary key,
+    log_channel_id bigint
+);
+
+create table message_log_config (
+    guild_id bigint primary key,
+    channel_id bigint not null
+);
+
+create table message_log_ignore (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    target_id bigint not null,
+    kind text not null,
+    unique(guild_id, target_id)
+);
+
+create table autoresponse (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    trigger text not null,
+    kind text not null,
+    actions text not null,
+    response text,
+    timeout bigint,
+    channel_id bigint,
+    cooldown integer not null default 0
+);
+
+create index autoresponse_guild_id on autoresponse(guild_id);
+
+create table greeting (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    kind text not null,
+    channel_id bigint,
+    template text not null,
+    embed boolean not null default false,
+    dm boolean not null default false,
+    role_menu text,
+    unique(guild_id, kind)
+);
+
+create table suggestion_config (
+    guild_id bigint primary key,
+    channel_id bigint not null
+);
+
+create table suggestion (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    channel_id bigint not null,
+    message_id bigint,
+    author_id bigint not null,
+    content text not null,
+    status text not null default 'Open',
+    reason text,
+    created_at text not null default (strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'))
+);
+
+create table suggestion_vote (
+    id integer primary key autoincrement,
+    suggestion_id integer not null references suggestion(id) on delete cascade,
+    user_id bigint not null,
+    upvote boolean not null,
+    unique(suggestion_id, user_id)
+);
+
+create table ticket_config (
+    guild_id bigint primary key,
+    staff_role_id bigint,
+    log_channel_id bigint
+);
+
+create table ticket (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    channel_id bigint,
+    opener_id bigint not null,
+    claimed_by bigint,
+    closed boolean not null default false,
+    created_at text not null default (strftime('%Y-%m-%d %H:%M:%f+00:00', 'now')),
+    closed_at text
+);
+
+create unique index ticket_opener on ticket(guild_id, opener_id) where not closed;
+
+create table bean_account (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    user_id bigint not null,
+    balance bigint not null default 0,
+    last_daily text,
+    unique(guild_id, user_id),
+    check (balance >= 0)
+);
+
+create table bean_ledger (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    user_id bigint not null,
+    amount bigint not null,
+    kind text not null,
+    counterparty_id bigint,
+    created_at text not null default (strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'))
+);
+
+create index bean_ledger_user on bean_ledger(guild_id, user_id);
+
+-- The ledger is append only
+create trigger bean_ledger_no_update before update on bean_ledger
+begin
+    select raise(abort, 'bean_ledger entries can not be changed');
+end;
+
+create trigger bean_ledger_no_delete before delete on bean_ledger
+begin
+    select raise(abort, 'bean_ledger entries can not be changed');
+end;
+
+create table shop_item (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    role_id bigint not null,
+    price bigint not null check (price > 0),
+    duration bigint,
+    unique(guild_id, role_id)
+);
+
+create table shop_purchase (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    user_id bigint not null,
+    role_id bigint not null,
+    price bigint not null,
+    expires_at text,
+    active boolean not null default true,
+    refunded boolean not null default false,
+    created_at text not null default (strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'))
+);
+
+create index shop_purchase_expires_at on shop_purchase(expires_at) where active;
+
+create table birthday_config (
+    guild_id bigint primary key,
+    channel_id bigint,
+    role_id bigint
+);
+
+create table birthday (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    user_id bigint not null,
+    day integer not null check (day between 1 and 31),
+    month integer not null check (month between 1 and 12),
+    year integer,
+    timezone text not null default 'UTC',
+    celebrated_year integer,
+    role_until text,
+    unique(guild_id, user_id)
+);
+
+create table quote (
+    id integer primary key autoincrement,
+    guild_id bigint not null,
+    number integer not null,
+    channel_id bigint not null,
+    message_id bigint not null,
+    author_id bigint not null,
+    author_name text not null,
+    author_avatar text,
+    content text not null,
+    image_url text,
+    saved_by bigint not null,
+    created_at text not null,
+    unique(guild_id, number),
+    unique(guild_id, message_id)
+);
@@ -0,0 +1,6 @@
+-- Modules are on unless a guild turned them off
+create table disabled_module (
+    guild_id bigint not null,
+    module text not null,
+    primary key (guild_id, module)
+);
@@ -0,0 +1 @@
+drop table command_registration;
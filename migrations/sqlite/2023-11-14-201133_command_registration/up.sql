@@ -0,0 +1,5 @@
+-- Hash of the commands last pushed to each guild, 0 for the global ones
+create table command_registration (
+    scope bigint primary key,
+    hash text not null
+);
@@ -1,3 +1,112 @@
+pub mod autoresponder;
+pub mod birthdays;
+pub mod economy;
 pub mod fav_msgs;
 pub mod general;
+pub mod giveaways;
+pub mod levels;
+pub mod message_log;
+pub mod moderation;
+pub mod module_settings;
+pub mod polls;
+pub mod quotes;
+pub mod registration;
+pub mod reminders;
 pub mod roles;
+pub mod shop;
+pub mod suggestions;
+pub mod tags;
+pub mod tickets;
+pub mod welcome;
+
+use crate::{AppError, Data};
+use poise::serenity_prelude::CreateCommand;
+
+type Command = poise::Command<Data, AppError>;
+
+/// Commands that can be turned off together, for the whole bot in the config or per guild.
+/// Named after the module they live in.
+pub struct Module {
+    pub name: &'static str,
+    pub commands: Vec<Command>,
+}
+
+/// Commands that are always there, whatever modules are enabled
+pub fn core() -> Vec<Command> {
+    vec![
+        general::help(),
+        general::shutdown(),
+        general::dbstats(),
+        module_settings::modules(),
+        registration::commands(),
+    ]
+}
+
+pub fn modules() -> Vec<Module> {
+    let module = |name, commands| Module { name, commands };
+    vec![
+        module(
+            "general",
+            vec![
+                general::say(),
+                general::ask_matthias(),
+                general::eight_ball(),
+            ],
+        ),
+        module("birthdays", vec![birthdays::birthday()]),
+        module("economy", vec![economy::beans()]),
+        module("fav_msgs", vec![fav_msgs::mystery(), fav_msgs::add()]),
+        module("quotes", vec![quotes::save(), quotes::quote()]),
+        module("autoresponder", vec![autoresponder::autoresponder()]),
+        module("roles", vec![roles::rolemenu(), roles::roles()]),
+        module("shop", vec![shop::shop()]),
+        module("polls", vec![polls::poll()]),
+        module("giveaways", vec![giveaways::giveaway()]),
+        module(
+            "levels",
+            vec![levels::rank(), levels::leaderboard(), levels::levels()],
+        ),
+        module("message_log", vec![message_log::msglog()]),
+        module(
+            "moderation",
+            vec![
+                moderation::warn(),
+                moderation::timeout(),
+                moderation::kick(),
+                moderation::ban(),
+                moderation::unban(),
+                moderation::case(),
+                moderation::modlog(),
+            ],
+        ),
+        module(
+            "reminders",
+            vec![
+                reminders::remind(),
+                reminders::remind_msg(),
+                reminders::reminders(),
+            ],
+        ),
+        module(
+            "suggestions",
+            vec![suggestions::suggest(), suggestions::suggestion()],
+        ),
+        module("tags", vec![tags::tag()]),
+        module("tickets", vec![tickets::tickets()]),
+        module("welcome", vec![welcome::welcome()]),
+    ]
+}
+
+/// What to register for `commands`, like `poise::builtins::create_application_commands`
+/// but for any selection of them
+pub fn application_commands<'a>(commands: impl Iterator<Item = &'a Command>) -> Vec<CreateCommand> {
+    commands
+        .flat_map(|c| {
+            [
+                c.create_as_slash_command(),
+                c.create_as_context_menu_command(),
+            ]
+        })
+        .flatten()
+        .collect()
+}
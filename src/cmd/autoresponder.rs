@@ -0,0 +1,413 @@
+mod model;
+
+use crate::cmd::moderation::{self, model::*, MAX_TIMEOUT};
+use crate::util::parse_duration;
+use crate::{AppError, Context, Data, Db};
+use chrono::Utc;
+use log::error;
+use model::*;
+use poise::serenity_prelude as serenity;
+use regex::{Regex, RegexBuilder};
+use serenity::{builder::*, model::prelude::*};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Keeps user supplied patterns from blowing up memory
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+
+/// The rules of a guild with their triggers compiled
+pub struct Matcher {
+    rules: Vec<(Autoresponse, Regex)>,
+    last_fired: Mutex<HashMap<(i32, ChannelId), Instant>>,
+}
+
+impl Matcher {
+    fn compile(rules: Vec<Autoresponse>) -> Matcher {
+        let rules = rules
+            .into_iter()
+            .filter_map(|rule| match pattern(&rule.trigger, rule.kind()) {
+                Ok(regex) => Some((rule, regex)),
+                Err(e) => {
+                    error!("Skipping autoresponse {}: {}", rule.id, e);
+                    None
+                }
+            })
+            .collect();
+        Matcher {
+            rules,
+            last_fired: Default::default(),
+        }
+    }
+
+    /// Rules whose trigger matches and that apply to the channel
+    fn matches<'a>(
+        &'a self,
+        channel_id: ChannelId,
+        content: &'a str,
+    ) -> impl Iterator<Item = &'a Autoresponse> {
+        self.rules
+            .iter()
+            .filter(move |(rule, regex)| {
+                rule.channel_id
+                    .map_or(true, |c| c == channel_id.get() as i64)
+                    && regex.is_match(content)
+            })
+            .map(|(rule, _)| rule)
+    }
+
+    /// Matching rules that aren't on cooldown, which starts their cooldown
+    fn fire(&self, channel_id: ChannelId, content: &str) -> Vec<Autoresponse> {
+        let mut last_fired = self.last_fired.lock().unwrap();
+        let now = Instant::now();
+        self.matches(channel_id, content)
+            .filter(|rule| {
+                let cooldown = Duration::from_secs(rule.cooldown as u64);
+                match last_fired.get(&(rule.id, channel_id)) {
+                    Some(last) if now.duration_since(*last) < cooldown => false,
+                    _ => {
+                        last_fired.insert((rule.id, channel_id), now);
+                        true
+                    }
+                }
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Compiled rules of every guild, built the first time a guild needs them
+#[derive(Default)]
+pub struct MatcherCache(Mutex<HashMap<GuildId, Arc<Matcher>>>);
+
+impl MatcherCache {
+    async fn get(&self, db: &Db, guild_id: GuildId) -> Result<Arc<Matcher>, AppError> {
+        if let Some(matcher) = self.0.lock().unwrap().get(&guild_id) {
+            return Ok(matcher.clone());
+        }
+        let guild = guild_id.get() as i64;
+        let rules = db.run(move |conn| Autoresponse::list(conn, guild)).await?;
+        let matcher = Arc::new(Matcher::compile(rules));
+        self.0.lock().unwrap().insert(guild_id, matcher.clone());
+        Ok(matcher)
+    }
+
+    fn invalidate(&self, guild_id: GuildId) {
+        self.0.lock().unwrap().remove(&guild_id);
+    }
+}
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("add", "remove", "list", "test")
+)]
+pub async fn autoresponder(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Respond to messages containing a trigger
+#[poise::command(slash_command, ephemeral = true)]
+#[allow(clippy::too_many_arguments)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Text that triggers the rule"] trigger: String,
+    #[description = "Any of reply, react, delete, timeout, log"] actions: String,
+    #[description = "Message to reply with, {user} mentions the author"] response: Option<String>,
+    #[description = "Treat the trigger as a regular expression"] regex: Option<bool>,
+    #[description = "Only match the trigger as a whole word"] whole_word: Option<bool>,
+    #[description = "Only respond in this channel"] channel: Option<GuildChannel>,
+    #[description = "Time between responses per channel, e.g. '30s'"] cooldown: Option<String>,
+    #[description = "Length of the timeout action, e.g. '10m'"] timeout: Option<String>,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    let parsed = match parse_actions(&actions) {
+        Ok(parsed) if !parsed.is_empty() => parsed,
+        _ => {
+            ctx.say(format!(
+                "'{}' is not a list of actions, use any of reply, react, delete, timeout and log",
+                &actions
+            ))
+            .await?;
+            return Ok(());
+        }
+    };
+    if parsed.contains(&RuleAction::Reply) && response.is_none() {
+        ctx.say("The reply action needs a response").await?;
+        return Ok(());
+    }
+    let kind = match (regex, whole_word) {
+        (Some(true), _) => TriggerKind::Regex,
+        (_, Some(true)) => TriggerKind::Word,
+        _ => TriggerKind::Literal,
+    };
+    if let Err(e) = pattern(&trigger, kind) {
+        ctx.say(format!(
+            "The trigger is not a valid pattern:\n```\n{}\n```",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+    let cooldown = match cooldown.as_deref().map(parse_duration) {
+        Some(Ok(d)) => match i32::try_from(d.as_secs()) {
+            Ok(secs) => secs,
+            Err(_) => {
+                ctx.say("The cooldown is too long").await?;
+                return Ok(());
+            }
+        },
+        Some(Err(_)) => {
+            ctx.say("The cooldown is not a duration I understand")
+                .await?;
+            return Ok(());
+        }
+        None => 0,
+    };
+    let timeout = match timeout.as_deref().map(parse_duration) {
+        Some(Ok(d)) if d <= MAX_TIMEOUT => Some(d.as_secs() as i64),
+        Some(_) => {
+            ctx.say("The timeout must be a duration of at most 28 days")
+                .await?;
+            return Ok(());
+        }
+        None if parsed.contains(&RuleAction::Timeout) => {
+            ctx.say("The timeout action needs a timeout").await?;
+            return Ok(());
+        }
+        None => None,
+    };
+
+    let new = NewAutoresponse {
+        guild_id: guild_id.get() as i64,
+        trigger,
+        kind: kind.to_string(),
+        actions: parsed
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        response,
+        timeout,
+        channel_id: channel.map(|c| c.id.get() as i64),
+        cooldown,
+    };
+    let rule = ctx.data().db.run(move |conn| new.insert(conn)).await?;
+    ctx.data().responders.invalidate(guild_id);
+    ctx.say(format!("Added rule #{}", rule.id)).await?;
+    Ok(())
+}
+
+/// Remove an automatic response
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Number of the rule"] id: i32,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let guild = guild_id.get() as i64;
+    let deleted = ctx
+        .data()
+        .db
+        .run(move |conn| Autoresponse::delete(conn, guild, id))
+        .await?;
+    ctx.data().responders.invalidate(guild_id);
+    let msg = if deleted > 0 {
+        format!("Removed rule #{}", id)
+    } else {
+        format!("Could not find rule #{}", id)
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// List the automatic responses of this server
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn list(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let rules = ctx
+        .data()
+        .db
+        .run(move |conn| Autoresponse::list(conn, guild_id))
+        .await?;
+
+    if rules.is_empty() {
+        ctx.say("No rules configured yet.").await?;
+        return Ok(());
+    }
+
+    let lines: Vec<_> = rules.iter().map(describe).collect();
+    let mut text = String::new();
+    for line in lines {
+        if text.len() + line.len() > 4000 {
+            text += "…";
+            break;
+        }
+        text += &line;
+        text += "\n";
+    }
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Automatic responses")
+                .description(text),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Check which rules a message would trigger in this channel
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn test(
+    ctx: Context<'_>,
+    #[description = "Message to test"] text: String,
+) -> Result<(), AppError> {
+    let matcher = ctx
+        .data()
+        .responders
+        .get(&ctx.data().db, ctx.guild_id().unwrap())
+        .await?;
+    let lines: Vec<_> = matcher
+        .matches(ctx.channel_id(), &text)
+        .map(describe)
+        .collect();
+
+    if lines.is_empty() {
+        ctx.say("No rule matches this message here.").await?;
+    } else {
+        ctx.say(format!("Matching rules:\n{}", lines.join("\n")))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Runs the rules matching a message. Returns whether the message was deleted.
+/// Those who can manage messages are neither deleted nor timed out, the other actions still run.
+pub async fn on_message(
+    ctx: &serenity::Context,
+    msg: &Message,
+    data: &Data,
+) -> Result<bool, AppError> {
+    let guild_id = match msg.guild_id {
+        Some(id) if !msg.author.bot => id,
+        _ => return Ok(false),
+    };
+    let matcher = data.responders.get(&data.db, guild_id).await?;
+
+    let exempt = msg
+        .author_permissions(ctx)
+        .map_or(false, |p| p.manage_messages());
+    let mut deleted = false;
+    for rule in matcher.fire(msg.channel_id, &msg.content) {
+        for action in rule.actions() {
+            if exempt && matches!(action, RuleAction::Delete | RuleAction::Timeout) {
+                continue;
+            }
+            match run(ctx, msg, guild_id, &rule, action, data).await {
+                Ok(()) => deleted |= action == RuleAction::Delete,
+                Err(e) => error!("Could not {} for rule {}: {:?}", action, rule.id, e),
+            }
+        }
+    }
+    Ok(deleted)
+}
+
+async fn run(
+    ctx: &serenity::Context,
+    msg: &Message,
+    guild_id: GuildId,
+    rule: &Autoresponse,
+    action: RuleAction,
+    data: &Data,
+) -> Result<(), AppError> {
+    match action {
+        RuleAction::Reply => {
+            let response = rule.response.as_deref().unwrap_or_default();
+            let response = response.replace("{user}", &format!("<@{}>", msg.author.id));
+            msg.channel_id.say(ctx, response).await?;
+        }
+        RuleAction::React => {
+            msg.react(ctx, '🫘').await?;
+        }
+        RuleAction::Delete => {
+            msg.delete(ctx).await?;
+        }
+        RuleAction::Timeout => {
+            let duration = rule.timeout.unwrap_or(60);
+            let until = Utc::now() + chrono::Duration::seconds(duration);
+            let reason = format!("Triggered automatic response #{}", rule.id);
+            guild_id
+                .edit_member(
+                    ctx,
+                    msg.author.id,
+                    EditMember::new()
+                        .disable_communication_until(until.to_rfc3339())
+                        .audit_log_reason(&reason),
+                )
+                .await?;
+            let new = NewModCase {
+                guild_id: guild_id.get() as i64,
+                action: ModAction::Timeout.to_string(),
+                user_id: msg.author.id.get() as i64,
+                moderator_id: ctx.cache.current_user().id.get() as i64,
+                reason: Some(reason),
+                duration: Some(duration),
+                expires_at: Some(until),
+            };
+            moderation::record(ctx, &data.db, new).await?;
+        }
+        RuleAction::Log => {
+            let guild = guild_id.get() as i64;
+            let channel = match data.db.run(move |conn| log_channel(conn, guild)).await? {
+                Some(channel) => ChannelId::new(channel as u64),
+                None => return Ok(()),
+            };
+            let embed = CreateEmbed::default()
+                .title(format!("Automatic response #{} triggered", rule.id))
+                .description(format!(
+                    "By <@{}> in <#{}>\n\n{}",
+                    msg.author.id,
+                    msg.channel_id,
+                    msg.content.chars().take(3800).collect::<String>()
+                ))
+                .timestamp(Timestamp::now());
+            channel
+                .send_message(ctx, CreateMessage::new().embed(embed))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+fn pattern(trigger: &str, kind: TriggerKind) -> Result<Regex, regex::Error> {
+    let pattern = match kind {
+        TriggerKind::Literal => regex::escape(trigger),
+        TriggerKind::Word => format!(r"\b{}\b", regex::escape(trigger)),
+        TriggerKind::Regex => trigger.to_owned(),
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(kind != TriggerKind::Regex)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+}
+
+fn describe(rule: &Autoresponse) -> String {
+    let mut line = format!(
+        "**#{}** {} `{}` → {}",
+        rule.id,
+        rule.kind,
+        rule.trigger.replace('`', "'"),
+        rule.actions.replace(',', ", ")
+    );
+    if let Some(channel) = rule.channel_id {
+        line += &format!(" in <#{}>", channel);
+    }
+    if rule.cooldown > 0 {
+        line += &format!(", every {}s at most", rule.cooldown);
+    }
+    line
+}
@@ -0,0 +1,99 @@
+use crate::db::schema;
+use crate::Conn;
+
+use ar::autoresponse;
+use diesel::result::Error;
+use diesel::{insert_into, prelude::*};
+use schema::autoresponse::dsl as ar;
+use strum_macros::{Display, EnumString};
+
+#[derive(EnumString, Display, Debug, PartialEq, Clone, Copy)]
+pub enum TriggerKind {
+    /// Matches anywhere in a message, ignoring case
+    Literal,
+    /// Matches whole words only, ignoring case
+    Word,
+    Regex,
+}
+
+#[derive(EnumString, Display, Debug, PartialEq, Clone, Copy)]
+#[strum(ascii_case_insensitive)]
+pub enum RuleAction {
+    Reply,
+    React,
+    Delete,
+    Timeout,
+    Log,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq, Clone)]
+#[diesel(table_name = schema::autoresponse)]
+pub struct Autoresponse {
+    pub id: i32,
+    pub guild_id: i64,
+    pub trigger: String,
+    pub kind: String,
+    /// Comma separated list of [`RuleAction`]s
+    pub actions: String,
+    pub response: Option<String>,
+    /// Length of the timeout action in seconds
+    pub timeout: Option<i64>,
+    /// Only respond in this channel
+    pub channel_id: Option<i64>,
+    /// Seconds to wait before responding in the same channel again
+    pub cooldown: i32,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::autoresponse)]
+pub struct NewAutoresponse {
+    pub guild_id: i64,
+    pub trigger: String,
+    pub kind: String,
+    pub actions: String,
+    pub response: Option<String>,
+    pub timeout: Option<i64>,
+    pub channel_id: Option<i64>,
+    pub cooldown: i32,
+}
+
+impl Autoresponse {
+    pub fn list(conn: &mut Conn, guild_id: i64) -> Result<Vec<Autoresponse>, Error> {
+        autoresponse
+            .filter(ar::guild_id.eq(guild_id))
+            .order(ar::id)
+            .load(conn)
+    }
+
+    pub fn delete(conn: &mut Conn, guild_id: i64, id: i32) -> Result<usize, Error> {
+        diesel::delete(
+            autoresponse
+                .filter(ar::guild_id.eq(guild_id))
+                .filter(ar::id.eq(id)),
+        )
+        .execute(conn)
+    }
+
+    pub fn kind(&self) -> TriggerKind {
+        self.kind.parse().unwrap_or(TriggerKind::Literal)
+    }
+
+    pub fn actions(&self) -> Vec<RuleAction> {
+        parse_actions(&self.actions).unwrap_or_default()
+    }
+}
+
+impl NewAutoresponse {
+    pub fn insert(&self, conn: &mut Conn) -> Result<Autoresponse, Error> {
+        insert_into(autoresponse).values(self).get_result(conn)
+    }
+}
+
+pub fn parse_actions(actions: &str) -> Result<Vec<RuleAction>, strum::ParseError> {
+    actions
+        .split(',')
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .map(str::parse)
+        .collect()
+}
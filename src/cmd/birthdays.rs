@@ -0,0 +1,328 @@
+mod model;
+
+use crate::cmd::module_settings::Modules;
+use crate::{AppError, Context, Db};
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use log::{error, info};
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::{builder::*, model::prelude::*};
+
+/// How long the birthday role is worn in hours
+const ROLE_HOURS: i64 = 24;
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    subcommands("set", "remove", "list", "next", "setup")
+)]
+pub async fn birthday(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Tell the server when your birthday is
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn set(
+    ctx: Context<'_>,
+    #[min = 1]
+    #[max = 31]
+    #[description = "Day of the month"]
+    day: u32,
+    #[min = 1]
+    #[max = 12]
+    #[description = "Month as a number"]
+    month: u32,
+    #[description = "Year you were born in, to show your age"] year: Option<i32>,
+    #[autocomplete = "comp_timezone"]
+    #[description = "Your timezone, e.g. 'Europe/Berlin'"]
+    timezone: Option<String>,
+) -> Result<(), AppError> {
+    // 2000 was a leap year, so February 29th is fine without a year
+    let date = NaiveDate::from_ymd_opt(year.unwrap_or(2000), month, day);
+    let in_past = year.map_or(true, |y| y >= 1900 && y < Utc::now().year());
+    if date.is_none() || !in_past {
+        ctx.say("That's not a date you could have been born on")
+            .await?;
+        return Ok(());
+    }
+    let timezone = timezone.unwrap_or("UTC".to_owned());
+    if timezone.parse::<Tz>().is_err() {
+        ctx.say(format!("I don't know the timezone '{}'", timezone))
+            .await?;
+        return Ok(());
+    }
+
+    let new = NewBirthday {
+        guild_id: ctx.guild_id().unwrap().get() as i64,
+        user_id: ctx.author().id.get() as i64,
+        day: day as i32,
+        month: month as i32,
+        year,
+        timezone,
+    };
+    ctx.data().db.run(move |conn| new.upsert(conn)).await?;
+    ctx.say(format!(
+        "🎂 Your birthday is set to {}",
+        format_date(day, month)
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Forget your birthday
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn remove(ctx: Context<'_>) -> Result<(), AppError> {
+    let (guild_id, user_id) = (
+        ctx.guild_id().unwrap().get() as i64,
+        ctx.author().id.get() as i64,
+    );
+    let deleted = ctx
+        .data()
+        .db
+        .run(move |conn| Birthday::delete(conn, guild_id, user_id))
+        .await?;
+    let msg = if deleted > 0 {
+        "Your birthday was removed"
+    } else {
+        "You haven't set a birthday"
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Show everyone's birthday
+#[poise::command(slash_command)]
+pub async fn list(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let birthdays = ctx
+        .data()
+        .db
+        .run(move |conn| Birthday::list(conn, guild_id))
+        .await?;
+    if birthdays.is_empty() {
+        ctx.say("No one has set their birthday yet").await?;
+        return Ok(());
+    }
+
+    let mut lines: Vec<_> = birthdays
+        .iter()
+        .take(50)
+        .map(|b| {
+            format!(
+                "<@{}> – {}",
+                b.user_id,
+                format_date(b.day as u32, b.month as u32)
+            )
+        })
+        .collect();
+    if birthdays.len() > lines.len() {
+        lines.push(format!("…and {} more", birthdays.len() - lines.len()));
+    }
+    ctx.send(
+        poise::CreateReply::new().embed(
+            CreateEmbed::default()
+                .title("Birthdays")
+                .description(lines.join("\n")),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Show the upcoming birthdays
+#[poise::command(slash_command)]
+pub async fn next(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let mut birthdays = ctx
+        .data()
+        .db
+        .run(move |conn| Birthday::list(conn, guild_id))
+        .await?;
+    if birthdays.is_empty() {
+        ctx.say("No one has set their birthday yet").await?;
+        return Ok(());
+    }
+
+    let today = Utc::now().date_naive();
+    birthdays.sort_by_key(|b| b.next_after(today));
+    let lines: Vec<_> = birthdays
+        .iter()
+        .take(5)
+        .map(|b| {
+            let next = b.next_after(today);
+            let days = (next - today).num_days();
+            let when = match days {
+                0 => "today".to_owned(),
+                1 => "tomorrow".to_owned(),
+                _ => format!("in {} days", days),
+            };
+            let mut line = format!(
+                "<@{}> – {}, {}",
+                b.user_id,
+                format_date(b.day as u32, b.month as u32),
+                when
+            );
+            if let Some(year) = b.year {
+                line += &format!(" (turning {})", next.year() - year);
+            }
+            line
+        })
+        .collect();
+    ctx.send(
+        poise::CreateReply::new().embed(
+            CreateEmbed::default()
+                .title("Upcoming birthdays")
+                .description(lines.join("\n")),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Choose where birthdays are announced and which role birthday children get
+#[poise::command(slash_command, ephemeral = true, required_permissions = "MANAGE_GUILD")]
+pub async fn setup(
+    ctx: Context<'_>,
+    #[description = "Channel for announcements, none to stop them"] channel: Option<GuildChannel>,
+    #[description = "Role worn for the day, none to not hand one out"] role: Option<Role>,
+) -> Result<(), AppError> {
+    let config = BirthdayConfig {
+        guild_id: ctx.guild_id().unwrap().get() as i64,
+        channel_id: channel.as_ref().map(|c| c.id.get() as i64),
+        role_id: role.as_ref().map(|r| r.id.get() as i64),
+    };
+    ctx.data().db.run(move |conn| config.upsert(conn)).await?;
+
+    let channel = channel.map_or("not announced".to_owned(), |c| {
+        format!("announced in <#{}>", c.id)
+    });
+    let role = role.map_or("no role".to_owned(), |r| format!("the role '{}'", r.name));
+    ctx.say(format!("Birthdays will be {} and get {}", channel, role))
+        .await?;
+    Ok(())
+}
+
+/// Announces everyone whose birthday just started in their timezone and hands out the role
+pub async fn celebrate_due(
+    ctx: &serenity::Context,
+    db: &Db,
+    modules: &Modules,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    let today = now.date_naive();
+
+    // Local dates are at most a day off from UTC
+    let mut dates = Vec::new();
+    for date in [today.pred_opt(), Some(today), today.succ_opt()]
+        .into_iter()
+        .flatten()
+    {
+        dates.push((date.month(), date.day()));
+        if (date.month(), date.day()) == (2, 28) {
+            dates.push((2, 29));
+        }
+    }
+
+    for birthday in db.run(move |conn| Birthday::on_dates(conn, &dates)).await? {
+        let local = now.with_timezone(&birthday.tz()).date_naive();
+        if birthday.date_in(local.year()) != local || birthday.celebrated_year == Some(local.year())
+        {
+            continue;
+        }
+        let guild_id = GuildId::new(birthday.guild_id as u64);
+        if !modules.enabled(Some(guild_id), "birthdays").await? {
+            continue;
+        }
+        let guild = birthday.guild_id;
+        let config = match db
+            .run(move |conn| BirthdayConfig::find(conn, guild))
+            .await?
+        {
+            Some(config) => config,
+            None => continue,
+        };
+        info!("Celebrating birthday {}", birthday.id);
+
+        let mut role_until = None;
+        if let Some(role) = config.role_id {
+            let res = match guild_id.member(ctx, birthday.user_id as u64).await {
+                Ok(mut member) => member.add_role(ctx, RoleId::new(role as u64)).await,
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(_) => role_until = Some(now + Duration::hours(ROLE_HOURS)),
+                Err(e) => error!("Could not give role for birthday {}: {:?}", birthday.id, e),
+            }
+        }
+        let year = local.year();
+        let birthday = db
+            .run(move |conn| birthday.celebrate(conn, year, role_until).map(|_| birthday))
+            .await?;
+
+        if let Some(channel) = config.channel_id {
+            let mut msg = format!("🎂 Happy birthday <@{}>!", birthday.user_id);
+            if let Some(year) = birthday.year {
+                msg += &format!(" They turn {} today.", local.year() - year);
+            }
+            if let Err(e) = ChannelId::new(channel as u64).say(ctx, msg).await {
+                error!("Could not announce birthday {}: {:?}", birthday.id, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Takes the birthday role away once the day is over
+pub async fn take_roles(
+    ctx: &serenity::Context,
+    db: &Db,
+    modules: &Modules,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    for birthday in db
+        .run(move |conn| Birthday::role_expired(conn, now))
+        .await?
+    {
+        let guild_id = GuildId::new(birthday.guild_id as u64);
+        if !modules.enabled(Some(guild_id), "birthdays").await? {
+            continue;
+        }
+        let (birthday, config) = db
+            .run(move |conn| {
+                birthday.clear_role(conn)?;
+                let config = BirthdayConfig::find(conn, birthday.guild_id)?;
+                Ok((birthday, config))
+            })
+            .await?;
+        let role = match config.and_then(|c| c.role_id) {
+            Some(role) => RoleId::new(role as u64),
+            None => continue,
+        };
+        let res = match guild_id.member(ctx, birthday.user_id as u64).await {
+            Ok(mut member) => member.remove_role(ctx, role).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            error!("Could not take role of birthday {}: {:?}", birthday.id, e);
+        }
+    }
+    Ok(())
+}
+
+async fn comp_timezone(_ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let partial = partial.to_lowercase();
+    TZ_VARIANTS
+        .iter()
+        .map(|tz| tz.name())
+        .filter(|name| name.to_lowercase().contains(&partial))
+        .take(25)
+        .map(|name| name.to_owned())
+        .collect()
+}
+
+fn format_date(day: u32, month: u32) -> String {
+    // Any leap year works to name the month
+    let date = NaiveDate::from_ymd_opt(2000, month, day).unwrap();
+    date.format("%B %-d").to_string()
+}
@@ -0,0 +1,158 @@
+use crate::db::schema;
+use crate::Conn;
+
+use bc::birthday_config;
+use bd::birthday;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use chrono_tz::Tz;
+use diesel::result::Error;
+use diesel::{insert_into, prelude::*};
+use schema::birthday::dsl as bd;
+use schema::birthday_config::dsl as bc;
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::birthday)]
+pub struct Birthday {
+    pub id: i32,
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub day: i32,
+    pub month: i32,
+    pub year: Option<i32>,
+    pub timezone: String,
+    /// Year of the last announcement, so nobody is celebrated twice
+    pub celebrated_year: Option<i32>,
+    /// When the birthday role has to be taken away again
+    pub role_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::birthday)]
+pub struct NewBirthday {
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub day: i32,
+    pub month: i32,
+    pub year: Option<i32>,
+    pub timezone: String,
+}
+
+#[derive(Queryable, Selectable, Insertable, Debug, PartialEq)]
+#[diesel(table_name = schema::birthday_config)]
+pub struct BirthdayConfig {
+    pub guild_id: i64,
+    pub channel_id: Option<i64>,
+    pub role_id: Option<i64>,
+}
+
+impl Birthday {
+    pub fn find(conn: &mut Conn, guild_id: i64, user_id: i64) -> Result<Option<Birthday>, Error> {
+        birthday
+            .filter(bd::guild_id.eq(guild_id))
+            .filter(bd::user_id.eq(user_id))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn delete(conn: &mut Conn, guild_id: i64, user_id: i64) -> Result<usize, Error> {
+        diesel::delete(
+            birthday
+                .filter(bd::guild_id.eq(guild_id))
+                .filter(bd::user_id.eq(user_id)),
+        )
+        .execute(conn)
+    }
+
+    pub fn list(conn: &mut Conn, guild_id: i64) -> Result<Vec<Birthday>, Error> {
+        birthday
+            .filter(bd::guild_id.eq(guild_id))
+            .order((bd::month, bd::day))
+            .load(conn)
+    }
+
+    /// Birthdays on any of the given `(month, day)` dates
+    pub fn on_dates(conn: &mut Conn, dates: &[(u32, u32)]) -> Result<Vec<Birthday>, Error> {
+        let mut query = birthday.into_boxed();
+        for (month, day) in dates {
+            query = query.or_filter(bd::month.eq(*month as i32).and(bd::day.eq(*day as i32)));
+        }
+        query.load(conn)
+    }
+
+    /// Birthdays whose role has been worn long enough
+    pub fn role_expired(conn: &mut Conn, now: DateTime<Utc>) -> Result<Vec<Birthday>, Error> {
+        birthday.filter(bd::role_until.le(now)).load(conn)
+    }
+
+    pub fn celebrate(
+        &self,
+        conn: &mut Conn,
+        year: i32,
+        role_until: Option<DateTime<Utc>>,
+    ) -> Result<usize, Error> {
+        diesel::update(self)
+            .set((bd::celebrated_year.eq(year), bd::role_until.eq(role_until)))
+            .execute(conn)
+    }
+
+    pub fn clear_role(&self, conn: &mut Conn) -> Result<usize, Error> {
+        diesel::update(self)
+            .set(bd::role_until.eq(None::<DateTime<Utc>>))
+            .execute(conn)
+    }
+
+    pub fn tz(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+
+    /// The date of the birthday in the given year, February 29th falls back to the 28th
+    pub fn date_in(&self, year: i32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, self.month as u32, self.day as u32)
+            .or_else(|| NaiveDate::from_ymd_opt(year, self.month as u32, 28))
+            .unwrap()
+    }
+
+    /// The next birthday counting from `today`, which may be today
+    pub fn next_after(&self, today: NaiveDate) -> NaiveDate {
+        let date = self.date_in(today.year());
+        if date < today {
+            self.date_in(today.year() + 1)
+        } else {
+            date
+        }
+    }
+}
+
+impl NewBirthday {
+    pub fn upsert(&self, conn: &mut Conn) -> Result<usize, Error> {
+        insert_into(birthday)
+            .values(self)
+            .on_conflict((bd::guild_id, bd::user_id))
+            .do_update()
+            .set((
+                bd::day.eq(self.day),
+                bd::month.eq(self.month),
+                bd::year.eq(self.year),
+                bd::timezone.eq(&self.timezone),
+            ))
+            .execute(conn)
+    }
+}
+
+impl BirthdayConfig {
+    pub fn find(conn: &mut Conn, guild_id: i64) -> Result<Option<BirthdayConfig>, Error> {
+        birthday_config.find(guild_id).first(conn).optional()
+    }
+
+    pub fn upsert(&self, conn: &mut Conn) -> Result<usize, Error> {
+        insert_into(birthday_config)
+            .values(self)
+            .on_conflict(bc::guild_id)
+            .do_update()
+            .set((
+                bc::channel_id.eq(self.channel_id),
+                bc::role_id.eq(self.role_id),
+            ))
+            .execute(conn)
+    }
+}
@@ -0,0 +1,245 @@
+pub mod model;
+
+use crate::{AppError, Context};
+use diesel::Connection;
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::{builder::*, model::prelude::*};
+
+const DAILY_BEANS: i64 = 100;
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only = true,
+    subcommands("daily", "balance", "give", "leaderboard", "grant", "revoke")
+)]
+pub async fn beans(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Claim your daily beans
+#[poise::command(slash_command, prefix_command)]
+pub async fn daily(ctx: Context<'_>) -> Result<(), AppError> {
+    let (guild_id, user_id) = (
+        ctx.guild_id().unwrap().get() as i64,
+        ctx.author().id.get() as i64,
+    );
+    let claimed = ctx
+        .data()
+        .db
+        .run(move |conn| {
+            Account::claim_daily(
+                conn,
+                guild_id,
+                user_id,
+                DAILY_BEANS,
+                chrono::Duration::days(1),
+            )
+        })
+        .await?;
+    let msg = match claimed {
+        Ok(account) => format!(
+            "🫘 You claimed {} beans and now have {}",
+            DAILY_BEANS, account.balance
+        ),
+        Err(next) => format!(
+            "You already claimed your beans today, come back <t:{}:R>",
+            next.timestamp()
+        ),
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Show how many beans you or someone else has
+#[poise::command(slash_command, prefix_command)]
+pub async fn balance(
+    ctx: Context<'_>,
+    #[description = "Whose balance to show"] user: Option<User>,
+) -> Result<(), AppError> {
+    let user = user.as_ref().unwrap_or(ctx.author());
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let user_id = user.id.get() as i64;
+    let own = user.id == ctx.author().id;
+
+    let (balance, history) = ctx
+        .data()
+        .db
+        .run(move |conn| {
+            let balance = Account::balance(conn, guild_id, user_id)?;
+            let history = if own {
+                LedgerEntry::recent(conn, guild_id, user_id, 5)?
+            } else {
+                Vec::new()
+            };
+            Ok((balance, history))
+        })
+        .await?;
+    let mut embed = CreateEmbed::default()
+        .author(CreateEmbedAuthor::new(&user.name).icon_url(user.face()))
+        .description(format!("🫘 {} beans", balance));
+    if own {
+        let history: Vec<_> = history
+            .iter()
+            .map(|e| {
+                let mut line = format!("`{:+}` {}", e.amount, e.kind);
+                if let Some(other) = e.counterparty_id {
+                    line += &format!(" <@{}>", other);
+                }
+                line + &format!(" <t:{}:R>", e.created_at.timestamp())
+            })
+            .collect();
+        if !history.is_empty() {
+            embed = embed.field("Recent activity", history.join("\n"), false);
+        }
+    }
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Give some of your beans to someone else
+#[poise::command(slash_command, prefix_command)]
+pub async fn give(
+    ctx: Context<'_>,
+    #[description = "Who to give beans to"] user: User,
+    #[min = 1]
+    #[description = "How many beans to give"]
+    amount: i64,
+) -> Result<(), AppError> {
+    if amount < 1 {
+        ctx.say("You have to give at least one bean").await?;
+        return Ok(());
+    }
+    if user.id == ctx.author().id || user.bot {
+        ctx.say("You can't give beans to yourself or bots").await?;
+        return Ok(());
+    }
+
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let (from, to) = (ctx.author().id.get() as i64, user.id.get() as i64);
+    let sender = ctx
+        .data()
+        .db
+        .run(move |conn| Account::transfer(conn, guild_id, from, to, amount))
+        .await?;
+    let msg = match sender {
+        Some(account) => format!(
+            "🫘 Gave {} beans to <@{}>, you have {} left",
+            amount, user.id, account.balance
+        ),
+        None => "You don't have enough beans".to_owned(),
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Show the richest members of this server
+#[poise::command(slash_command, prefix_command)]
+pub async fn leaderboard(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let top = ctx
+        .data()
+        .db
+        .run(move |conn| Account::top(conn, guild_id, 10))
+        .await?;
+
+    if top.is_empty() {
+        ctx.say("No one has any beans yet!").await?;
+        return Ok(());
+    }
+
+    let lines: Vec<_> = top
+        .iter()
+        .enumerate()
+        .map(|(i, a)| format!("**{}.** <@{}> – {} beans", i + 1, a.user_id, a.balance))
+        .collect();
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Bean leaderboard")
+                .description(lines.join("\n")),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Create beans out of thin air
+#[poise::command(slash_command, ephemeral = true, check = "is_admin")]
+pub async fn grant(
+    ctx: Context<'_>,
+    #[description = "Who to grant beans to"] user: User,
+    #[min = 1]
+    #[max = 1_000_000_000]
+    #[description = "How many beans to grant"]
+    amount: i64,
+) -> Result<(), AppError> {
+    if !(1..=1_000_000_000).contains(&amount) {
+        ctx.say("You can grant between 1 and 1000000000 beans at once")
+            .await?;
+        return Ok(());
+    }
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let (user_id, author) = (user.id.get() as i64, Some(ctx.author().id.get() as i64));
+    let account = ctx
+        .data()
+        .db
+        .run(move |conn| {
+            Account::change(conn, guild_id, user_id, amount, LedgerKind::Grant, author)
+        })
+        .await?;
+    let msg = match account {
+        Some(account) => format!(
+            "Granted {} beans to <@{}>, they now have {}",
+            amount, user.id, account.balance
+        ),
+        None => format!("<@{}> can't hold that many beans", user.id),
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Take beans away from someone
+#[poise::command(slash_command, ephemeral = true, check = "is_admin")]
+pub async fn revoke(
+    ctx: Context<'_>,
+    #[description = "Who to take beans from"] user: User,
+    #[min = 1]
+    #[description = "How many beans to take, at most all of them"]
+    amount: i64,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let user_id = user.id.get() as i64;
+    let author = Some(ctx.author().id.get() as i64);
+
+    let revoked = ctx
+        .data()
+        .db
+        .run(move |conn| {
+            conn.transaction(|conn| {
+                let amount = amount.min(Account::balance(conn, guild_id, user_id)?);
+                // Nothing to take, and nothing worth a ledger entry
+                if amount > 0 {
+                    Account::change(conn, guild_id, user_id, -amount, LedgerKind::Revoke, author)?;
+                }
+                Ok(amount)
+            })
+        })
+        .await?;
+    ctx.say(format!("Took {} beans from <@{}>", revoked, user.id))
+        .await?;
+    Ok(())
+}
+
+/// Bot owners and server managers may create and destroy beans
+pub async fn is_admin(ctx: Context<'_>) -> Result<bool, AppError> {
+    if ctx.framework().options().owners.contains(&ctx.author().id) {
+        return Ok(true);
+    }
+    let member = ctx.author_member().await;
+    Ok(member
+        .and_then(|m| m.permissions)
+        .map_or(false, |p| p.manage_guild()))
+}
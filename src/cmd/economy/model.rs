@@ -0,0 +1,199 @@
+use crate::db::schema;
+use crate::Conn;
+
+use ba::bean_account;
+use bl::bean_ledger;
+use chrono::{DateTime, Duration, Utc};
+use diesel::result::Error;
+use diesel::{insert_into, prelude::*};
+use schema::bean_account::dsl as ba;
+use schema::bean_ledger::dsl as bl;
+use strum_macros::{Display, EnumString};
+
+#[derive(EnumString, Display, Debug, PartialEq, Clone, Copy)]
+pub enum LedgerKind {
+    Daily,
+    Transfer,
+    Grant,
+    Revoke,
+    Purchase,
+    Refund,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::bean_account)]
+pub struct Account {
+    pub id: i32,
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub balance: i64,
+    pub last_daily: Option<DateTime<Utc>>,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::bean_ledger)]
+pub struct LedgerEntry {
+    pub id: i32,
+    pub guild_id: i64,
+    pub user_id: i64,
+    /// Beans added to the user's balance, negative if taken
+    pub amount: i64,
+    pub kind: String,
+    /// The other side of a transfer, or whoever granted or revoked beans
+    pub counterparty_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::bean_ledger)]
+struct NewLedgerEntry {
+    guild_id: i64,
+    user_id: i64,
+    amount: i64,
+    kind: String,
+    counterparty_id: Option<i64>,
+}
+
+impl Account {
+    pub fn find(conn: &mut Conn, guild_id: i64, user_id: i64) -> Result<Option<Account>, Error> {
+        bean_account
+            .filter(ba::guild_id.eq(guild_id))
+            .filter(ba::user_id.eq(user_id))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn balance(conn: &mut Conn, guild_id: i64, user_id: i64) -> Result<i64, Error> {
+        Ok(Account::find(conn, guild_id, user_id)?.map_or(0, |a| a.balance))
+    }
+
+    pub fn top(conn: &mut Conn, guild_id: i64, limit: i64) -> Result<Vec<Account>, Error> {
+        bean_account
+            .filter(ba::guild_id.eq(guild_id))
+            .filter(ba::balance.gt(0))
+            .order(ba::balance.desc())
+            .limit(limit)
+            .load(conn)
+    }
+
+    /// Creates the account if needed and locks it for the rest of the transaction
+    fn lock(conn: &mut Conn, guild_id: i64, user_id: i64) -> Result<Account, Error> {
+        insert_into(bean_account)
+            .values((ba::guild_id.eq(guild_id), ba::user_id.eq(user_id)))
+            .on_conflict((ba::guild_id, ba::user_id))
+            .do_nothing()
+            .execute(conn)?;
+        let query = bean_account
+            .filter(ba::guild_id.eq(guild_id))
+            .filter(ba::user_id.eq(user_id));
+        // SQLite has no row locks, the insert above already locked the whole database
+        #[cfg(feature = "postgres")]
+        let query = query.for_update();
+        query.first(conn)
+    }
+
+    /// Adds `amount` beans to the balance and records it in the ledger.
+    /// Returns `None` without changing anything if the balance would become negative or overflow.
+    pub fn change(
+        conn: &mut Conn,
+        guild_id: i64,
+        user_id: i64,
+        amount: i64,
+        kind: LedgerKind,
+        counterparty_id: Option<i64>,
+    ) -> Result<Option<Account>, Error> {
+        conn.transaction(|conn| {
+            let account = Account::lock(conn, guild_id, user_id)?;
+            match account.balance.checked_add(amount) {
+                Some(balance) if balance >= 0 => {}
+                _ => return Ok(None),
+            }
+            let account = diesel::update(&account)
+                .set(ba::balance.eq(ba::balance + amount))
+                .get_result(conn)?;
+            NewLedgerEntry {
+                guild_id,
+                user_id,
+                amount,
+                kind: kind.to_string(),
+                counterparty_id,
+            }
+            .insert_into(bean_ledger)
+            .execute(conn)?;
+            Ok(Some(account))
+        })
+    }
+
+    /// Hands out the daily beans unless they were already claimed within the cooldown.
+    /// Returns the account afterwards, or when the next claim is possible.
+    pub fn claim_daily(
+        conn: &mut Conn,
+        guild_id: i64,
+        user_id: i64,
+        amount: i64,
+        cooldown: Duration,
+    ) -> Result<Result<Account, DateTime<Utc>>, Error> {
+        conn.transaction(|conn| {
+            let now = Utc::now();
+            let account = Account::lock(conn, guild_id, user_id)?;
+            if let Some(last) = account.last_daily {
+                if now - last < cooldown {
+                    return Ok(Err(last + cooldown));
+                }
+            }
+            diesel::update(&account)
+                .set(ba::last_daily.eq(now))
+                .execute(conn)?;
+            // Only a balance at the limit can't take more, then nothing is claimed
+            let account =
+                Account::change(conn, guild_id, user_id, amount, LedgerKind::Daily, None)?
+                    .ok_or(Error::RollbackTransaction)?;
+            Ok(Ok(account))
+        })
+    }
+
+    /// Moves beans between two members.
+    /// Returns the sender's account, or `None` if they can't afford it.
+    pub fn transfer(
+        conn: &mut Conn,
+        guild_id: i64,
+        from: i64,
+        to: i64,
+        amount: i64,
+    ) -> Result<Option<Account>, Error> {
+        conn.transaction(|conn| {
+            // Always lock in the same order so concurrent transfers can't deadlock
+            Account::lock(conn, guild_id, from.min(to))?;
+            Account::lock(conn, guild_id, from.max(to))?;
+
+            let sender = Account::change(
+                conn,
+                guild_id,
+                from,
+                -amount,
+                LedgerKind::Transfer,
+                Some(to),
+            )?;
+            if sender.is_some() {
+                Account::change(conn, guild_id, to, amount, LedgerKind::Transfer, Some(from))?;
+            }
+            Ok(sender)
+        })
+    }
+}
+
+impl LedgerEntry {
+    pub fn recent(
+        conn: &mut Conn,
+        guild_id: i64,
+        user_id: i64,
+        limit: i64,
+    ) -> Result<Vec<LedgerEntry>, Error> {
+        bean_ledger
+            .filter(bl::guild_id.eq(guild_id))
+            .filter(bl::user_id.eq(user_id))
+            .order(bl::id.desc())
+            .limit(limit)
+            .load(conn)
+    }
+}
@@ -1,11 +1,99 @@
 mod model;
+#[cfg(test)]
+mod tests;
 
-use crate::{AppError, ComponentAction, Context, Data};
+use crate::component::{self, Component};
+use crate::modal::{self, Form};
+use crate::{AppError, Bot, ComponentAction, Context, Data, Db, ModalAction};
 use diesel::result::DatabaseErrorKind;
 use log::{error, info};
 use model::*;
 use poise::serenity_prelude as serenity;
-use serenity::{builder::*, model::prelude::*, CacheHttp};
+use serenity::{builder::*, model::prelude::*};
+
+/// Button removing a message from the favorites of whoever clicks it
+pub struct DeleteFromFavorites {
+    pub channel_id: u64,
+    pub message_id: u64,
+}
+
+impl Component for DeleteFromFavorites {
+    const ACTION: ComponentAction = ComponentAction::DeleteFromFavorites;
+    const MODULE: &'static str = "fav_msgs";
+    const LEGACY: bool = true;
+
+    fn encode(&self) -> Vec<String> {
+        vec![self.channel_id.to_string(), self.message_id.to_string()]
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        match fields {
+            [channel_id, message_id] => Some(DeleteFromFavorites {
+                channel_id: channel_id.parse().ok()?,
+                message_id: message_id.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ComponentInteraction,
+        data: &'a Data,
+    ) -> poise::BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(delete(ctx, event, data, self))
+    }
+}
+
+inventory::submit!(component::Route::of::<DeleteFromFavorites>());
+
+/// Form saving a message to the favorites of whoever submits it
+pub struct AddFavorite {
+    pub channel_id: u64,
+    pub message_id: u64,
+}
+
+#[derive(Debug, poise::Modal)]
+#[name = "Add to Favorites"]
+pub struct FavoriteNote {
+    #[name = "Note"]
+    #[placeholder = "Why you like this message, leave empty for none"]
+    #[max_length = 200]
+    note: Option<String>,
+}
+
+impl Form for AddFavorite {
+    const ACTION: ModalAction = ModalAction::AddFavorite;
+    const MODULE: &'static str = "fav_msgs";
+    type Inputs = FavoriteNote;
+
+    fn encode(&self) -> Vec<String> {
+        vec![self.channel_id.to_string(), self.message_id.to_string()]
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        match fields {
+            [channel_id, message_id] => Some(AddFavorite {
+                channel_id: channel_id.parse().ok()?,
+                message_id: message_id.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ModalInteraction,
+        data: &'a Data,
+        inputs: FavoriteNote,
+    ) -> poise::BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(save(ctx, event, data, self, inputs))
+    }
+}
+
+inventory::submit!(modal::Route::of::<AddFavorite>());
 
 /// Add a message to your favorites
 #[poise::command(
@@ -19,24 +107,53 @@ pub async fn add(ctx: Context<'_>, msg: Message) -> Result<(), AppError> {
         guild_id: ctx.guild_id().unwrap().into(),
         channel_id: ctx.channel_id().into(),
         message_id: msg.id.into(),
+        note: None,
     };
 
-    let mut conn = ctx.data().db.get()?;
-
-    if new.find(&mut conn)?.is_some() {
+    if ctx
+        .data()
+        .db
+        .run(move |conn| conn.find_favorite(&new))
+        .await?
+        .is_some()
+    {
         ctx.say("You already favorited this message.").await?;
         return Ok(());
     }
 
-    match new.insert(&mut conn) {
-        Ok(_) => Ok(ctx.say("Saved.")),
-        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
-            Ok(ctx.say("You already favorited this message."))
-        }
-        Err(e) => Err(e),
-    }?
-    .await?;
-    Ok(())
+    let form = AddFavorite {
+        channel_id: ctx.channel_id().get(),
+        message_id: msg.id.get(),
+    };
+    modal::show(ctx, &form, None).await
+}
+
+async fn save(
+    ctx: &Bot,
+    event: &ModalInteraction,
+    data: &Data,
+    form: AddFavorite,
+    inputs: FavoriteNote,
+) -> Result<(), AppError> {
+    let new = NewFavorite {
+        user_id: event.user.id.get() as i64,
+        guild_id: event.guild_id.unwrap().get() as i64,
+        channel_id: form.channel_id as i64,
+        message_id: form.message_id as i64,
+        note: inputs.note.filter(|n| !n.trim().is_empty()),
+    };
+
+    let res = data
+        .db
+        .run(move |conn| match conn.add_favorite(&new) {
+            Ok(_) => Ok("Saved."),
+            Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                Ok("You already favorited this message.")
+            }
+            Err(e) => Err(e),
+        })
+        .await?;
+    modal::reply(ctx, event, res).await
 }
 
 /// Post a random message from your or the server's favorites
@@ -45,63 +162,89 @@ pub async fn mystery(
     ctx: Context<'_>,
     #[description = "Draw from server's global favorites if set"] global: Option<bool>,
 ) -> Result<(), AppError> {
-    let mut conn = ctx.data().db.get()?;
+    let bot = Bot::from(ctx.serenity_context());
+    let guild_id = ctx.guild_id().unwrap();
+    let reply = mystery_reply(&bot, ctx.data(), ctx.author().id, guild_id, global).await?;
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+/// The reply to `/mystery`, a favorite posted with a button to remove it
+async fn mystery_reply(
+    ctx: &Bot,
+    data: &Data,
+    author: UserId,
+    guild_id: GuildId,
+    global: Option<bool>,
+) -> Result<poise::CreateReply, AppError> {
+    let user_id = Some(author.into()).filter(|_| !global.unwrap_or(false));
+    let (rand, msg) = match draw(&ctx.http, &data.db, user_id, guild_id.into()).await? {
+        Some(drawn) => drawn,
+        None => {
+            return Ok(poise::CreateReply::default()
+                .content("No messages favorited yet!")
+                .ephemeral(true))
+        }
+    };
+
+    let author_nick = msg
+        .author
+        .nick_in(ctx, rand.guild_id as u64)
+        .await
+        .unwrap_or(msg.author.name.to_owned());
+    let mut embed = CreateEmbed::default().description(&msg.content).author(
+        CreateEmbedAuthor::new(author_nick)
+            .icon_url(msg.author.avatar_url().unwrap_or("".to_string())),
+    );
+    if let Some(attach) = msg.attachments.iter().find(|a| a.height.is_some()) {
+        embed = embed.image(&attach.url);
+    }
+    if let Some(note) = &rand.note {
+        embed = embed.footer(CreateEmbedFooter::new(note));
+    }
+    let remove = DeleteFromFavorites {
+        channel_id: rand.channel_id as u64,
+        message_id: rand.message_id as u64,
+    };
+    Ok(poise::CreateReply::default()
+        .embed(embed)
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new_link(msg.link()).label("Source"),
+            CreateButton::new(remove.custom_id_for(Some(author), None)?)
+                .style(ButtonStyle::Danger)
+                .label("Remove from Favorites"),
+        ])]))
+}
+
+/// Draws a random favorite whose message still exists, forgetting the ones that were deleted
+async fn draw(
+    http: &serenity::Http,
+    db: &Db,
+    user_id: Option<i64>,
+    guild_id: i64,
+) -> Result<Option<(FavoritedMessage, Message)>, AppError> {
     loop {
-        let fav = FavoritedMessage::rand(
-            &mut conn,
-            Some(ctx.author().id.into()).filter(|_| !global.unwrap_or(false)),
-            ctx.guild_id().unwrap().into(),
-        )?;
-
-        if let Some(rand) = fav {
-            if let Some(msg) = fetch_msg(&ctx, &rand).await? {
-                let author_nick = msg
-                    .author
-                    .nick_in(&ctx, rand.guild_id as u64)
-                    .await
-                    .unwrap_or(msg.author.name.to_owned());
-                let mut embed = CreateEmbed::default().description(&msg.content).author(
-                    CreateEmbedAuthor::new(author_nick)
-                        .icon_url(msg.author.avatar_url().unwrap_or("".to_string())),
-                );
-                if let Some(attach) = msg.attachments.iter().find(|a| a.height.is_some()) {
-                    embed = embed.image(&attach.url);
-                }
-                ctx.send(poise::CreateReply::default().embed(embed).components(vec![
-                    CreateActionRow::Buttons(vec![
-                            CreateButton::new_link(msg.link()).label("Source"),
-                            CreateButton::new(format!(
-                                        "{}/{}/{}",
-                                        ComponentAction::DeleteFromFavorites,
-                                        rand.channel_id,
-                                        rand.message_id,
-                                    ))
-                                .style(ButtonStyle::Danger)
-                                    .label("Remove from Favorites"),
-                            ]),
-                ]))
-                .await?;
-                break;
-            } else {
-                info!("Favorited message has been deleted, deleting...");
-                FavoritedMessage::delete_id(&mut conn, rand.id)?;
-            }
-        } else {
-            ctx.send(
-                poise::CreateReply::default()
-                    .content("No messages favorited yet!")
-                    .ephemeral(true),
-            )
-            .await?;
-            break;
+        let fav = match db
+            .run(move |conn| conn.random_favorite(user_id, guild_id))
+            .await?
+        {
+            Some(fav) => fav,
+            None => return Ok(None),
         };
+        if let Some(msg) = fetch_msg(http, &fav).await? {
+            return Ok(Some((fav, msg)));
+        }
+        info!("Favorited message has been deleted, deleting...");
+        let id = fav.id;
+        db.run(move |conn| conn.remove_favorite_id(id)).await?;
     }
-    Ok(())
 }
 
-async fn fetch_msg(ctx: &Context<'_>, fav: &FavoritedMessage) -> Result<Option<Message>, AppError> {
-    match ctx
-        .http()
+async fn fetch_msg(
+    http: &serenity::Http,
+    fav: &FavoritedMessage,
+) -> Result<Option<Message>, AppError> {
+    match http
         .get_message(
             ChannelId::new(fav.channel_id as u64),
             MessageId::new(fav.message_id as u64),
@@ -124,21 +267,25 @@ async fn fetch_msg(ctx: &Context<'_>, fav: &FavoritedMessage) -> Result<Option<M
 }
 
 pub async fn delete(
-    ctx: &serenity::Context,
+    ctx: &Bot,
     event: &ComponentInteraction,
     data: &Data,
-    args: &[&str],
+    button: DeleteFromFavorites,
 ) -> Result<(), AppError> {
-    let channel_id: u64 = args[0].parse()?;
-    let message_id: u64 = args[1].parse()?;
     let search = NewFavorite {
         user_id: event.user.id.get() as i64,
         guild_id: event.guild_id.unwrap().get() as i64,
-        channel_id: channel_id as i64,
-        message_id: message_id as i64,
+        channel_id: button.channel_id as i64,
+        message_id: button.message_id as i64,
+        note: None,
     };
 
-    let res = if search.delete(&mut data.db.get()?)? > 0 {
+    let res = if data
+        .db
+        .run(move |conn| conn.remove_favorite(&search))
+        .await?
+        > 0
+    {
         "Successfully removed from favorites."
     } else {
         "This message is not in your favorites."
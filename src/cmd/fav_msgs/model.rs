@@ -4,9 +4,12 @@ use crate::Conn;
 use diesel::prelude::*;
 use diesel::result::Error;
 
+#[cfg(test)]
+mod tests;
+
 sql_function!(fn random() -> Integer);
 
-#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq, Clone)]
 #[diesel(table_name = fav_msgs)]
 pub struct FavoritedMessage {
     pub id: i32,
@@ -14,6 +17,7 @@ pub struct FavoritedMessage {
     pub guild_id: i64,
     pub channel_id: i64,
     pub message_id: i64,
+    pub note: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -23,50 +27,69 @@ pub struct NewFavorite {
     pub guild_id: i64,
     pub channel_id: i64,
     pub message_id: i64,
+    pub note: Option<String>,
 }
 
-impl FavoritedMessage {
-    pub fn delete_id(conn: &mut Conn, del_id: i32) -> Result<usize, Error> {
-        diesel::delete(fav_msgs.find(del_id)).execute(conn)
-    }
+/// Where favorites are stored, a favorite is identified by its user and message
+pub trait FavoriteRepo {
+    fn find_favorite(&mut self, fav: &NewFavorite) -> Result<Option<FavoritedMessage>, Error>;
+
+    /// Fails with a unique violation if the user already favorited the message
+    fn add_favorite(&mut self, fav: &NewFavorite) -> Result<usize, Error>;
 
-    pub fn rand(
-        conn: &mut Conn,
+    fn remove_favorite(&mut self, fav: &NewFavorite) -> Result<usize, Error>;
+
+    fn remove_favorite_id(&mut self, id: i32) -> Result<usize, Error>;
+
+    /// A random favorite of the guild, only one of `user` if set
+    fn random_favorite(
+        &mut self,
         user: Option<i64>,
         guild: i64,
-    ) -> Result<Option<FavoritedMessage>, Error> {
-        let mut query = fav_msgs.filter(guild_id.eq(guild)).into_boxed();
-        if let Some(u) = user {
-            query = query.filter(user_id.eq(u))
-        }
-        // Not the most efficient but will do for now
-        query.order(random()).first(conn).optional()
-    }
+    ) -> Result<Option<FavoritedMessage>, Error>;
 }
 
-impl NewFavorite {
-    pub fn find(&self, conn: &mut Conn) -> Result<Option<FavoritedMessage>, Error> {
+impl FavoriteRepo for Conn {
+    fn find_favorite(&mut self, fav: &NewFavorite) -> Result<Option<FavoritedMessage>, Error> {
         fav_msgs
-            .filter(user_id.eq(self.user_id))
-            .filter(guild_id.eq(self.guild_id))
-            .filter(channel_id.eq(self.channel_id))
-            .filter(message_id.eq(self.message_id))
-            .first(conn)
+            .filter(user_id.eq(fav.user_id))
+            .filter(guild_id.eq(fav.guild_id))
+            .filter(channel_id.eq(fav.channel_id))
+            .filter(message_id.eq(fav.message_id))
+            .first(self)
             .optional()
     }
 
-    pub fn delete(&self, conn: &mut Conn) -> Result<usize, Error> {
+    fn add_favorite(&mut self, fav: &NewFavorite) -> Result<usize, Error> {
+        // A duplicate must not abort a surrounding transaction
+        self.transaction(|conn| fav.insert_into(fav_msgs).execute(conn))
+    }
+
+    fn remove_favorite(&mut self, fav: &NewFavorite) -> Result<usize, Error> {
         diesel::delete(
             fav_msgs
-                .filter(user_id.eq(self.user_id))
-                .filter(guild_id.eq(self.guild_id))
-                .filter(channel_id.eq(self.channel_id))
-                .filter(message_id.eq(self.message_id)),
+                .filter(user_id.eq(fav.user_id))
+                .filter(guild_id.eq(fav.guild_id))
+                .filter(channel_id.eq(fav.channel_id))
+                .filter(message_id.eq(fav.message_id)),
         )
-        .execute(conn)
+        .execute(self)
+    }
+
+    fn remove_favorite_id(&mut self, del_id: i32) -> Result<usize, Error> {
+        diesel::delete(fav_msgs.find(del_id)).execute(self)
     }
 
-    pub fn insert(&self, conn: &mut Conn) -> Result<usize, Error> {
-        self.insert_into(fav_msgs).execute(conn)
+    fn random_favorite(
+        &mut self,
+        user: Option<i64>,
+        guild: i64,
+    ) -> Result<Option<FavoritedMessage>, Error> {
+        let mut query = fav_msgs.filter(guild_id.eq(guild)).into_boxed();
+        if let Some(u) = user {
+            query = query.filter(user_id.eq(u))
+        }
+        // Not the most efficient but will do for now
+        query.order(random()).first(self).optional()
     }
 }
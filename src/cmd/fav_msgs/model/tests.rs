@@ -0,0 +1,161 @@
+use super::{FavoriteRepo, FavoritedMessage, NewFavorite};
+use diesel::result::{DatabaseErrorKind, Error};
+use rand::seq::SliceRandom;
+
+/// Keeps favorites in a `Vec`, enforcing the same unique key as the `fav_msgs` table
+#[derive(Default)]
+struct MemoryFavorites {
+    favorites: Vec<FavoritedMessage>,
+    next_id: i32,
+}
+
+fn same_message(fav: &FavoritedMessage, key: &NewFavorite) -> bool {
+    fav.user_id == key.user_id
+        && fav.guild_id == key.guild_id
+        && fav.channel_id == key.channel_id
+        && fav.message_id == key.message_id
+}
+
+impl FavoriteRepo for MemoryFavorites {
+    fn find_favorite(&mut self, fav: &NewFavorite) -> Result<Option<FavoritedMessage>, Error> {
+        Ok(self
+            .favorites
+            .iter()
+            .find(|f| same_message(f, fav))
+            .cloned())
+    }
+
+    fn add_favorite(&mut self, fav: &NewFavorite) -> Result<usize, Error> {
+        if self.favorites.iter().any(|f| same_message(f, fav)) {
+            return Err(Error::DatabaseError(
+                DatabaseErrorKind::UniqueViolation,
+                Box::new("duplicate favorite".to_owned()),
+            ));
+        }
+        self.next_id += 1;
+        self.favorites.push(FavoritedMessage {
+            id: self.next_id,
+            user_id: fav.user_id,
+            guild_id: fav.guild_id,
+            channel_id: fav.channel_id,
+            message_id: fav.message_id,
+            note: fav.note.clone(),
+        });
+        Ok(1)
+    }
+
+    fn remove_favorite(&mut self, fav: &NewFavorite) -> Result<usize, Error> {
+        let before = self.favorites.len();
+        self.favorites.retain(|f| !same_message(f, fav));
+        Ok(before - self.favorites.len())
+    }
+
+    fn remove_favorite_id(&mut self, id: i32) -> Result<usize, Error> {
+        let before = self.favorites.len();
+        self.favorites.retain(|f| f.id != id);
+        Ok(before - self.favorites.len())
+    }
+
+    fn random_favorite(
+        &mut self,
+        user: Option<i64>,
+        guild: i64,
+    ) -> Result<Option<FavoritedMessage>, Error> {
+        let matching: Vec<_> = self
+            .favorites
+            .iter()
+            .filter(|f| f.guild_id == guild && user.map_or(true, |u| f.user_id == u))
+            .collect();
+        Ok(matching
+            .choose(&mut rand::thread_rng())
+            .map(|f| (*f).clone()))
+    }
+}
+
+fn favorite(user: i64, message: i64) -> NewFavorite {
+    NewFavorite {
+        user_id: user,
+        guild_id: 1,
+        channel_id: 2,
+        message_id: message,
+        note: None,
+    }
+}
+
+fn duplicate_favorite_is_rejected(repo: &mut impl FavoriteRepo) {
+    assert_eq!(repo.add_favorite(&favorite(10, 100)).unwrap(), 1);
+
+    let mut again = favorite(10, 100);
+    again.note = Some("Different note".to_owned());
+    match repo.add_favorite(&again) {
+        Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {}
+        res => panic!("Expected a unique violation, got {:?}", res),
+    }
+    assert!(repo
+        .find_favorite(&favorite(10, 100))
+        .unwrap()
+        .unwrap()
+        .note
+        .is_none());
+}
+
+fn same_message_favorited_by_others(repo: &mut impl FavoriteRepo) {
+    repo.add_favorite(&favorite(10, 100)).unwrap();
+    repo.add_favorite(&favorite(11, 100)).unwrap();
+
+    assert_eq!(repo.remove_favorite(&favorite(10, 100)).unwrap(), 1);
+    assert!(repo.find_favorite(&favorite(10, 100)).unwrap().is_none());
+    assert!(repo.find_favorite(&favorite(11, 100)).unwrap().is_some());
+    assert_eq!(repo.remove_favorite(&favorite(10, 100)).unwrap(), 0);
+}
+
+fn random_favorite_of_user(repo: &mut impl FavoriteRepo) {
+    assert!(repo.random_favorite(None, 1).unwrap().is_none());
+    repo.add_favorite(&favorite(10, 100)).unwrap();
+    repo.add_favorite(&favorite(11, 101)).unwrap();
+
+    for _ in 0..10 {
+        let fav = repo.random_favorite(Some(11), 1).unwrap().unwrap();
+        assert_eq!(fav.message_id, 101);
+    }
+    assert!(repo.random_favorite(None, 3).unwrap().is_none());
+
+    let fav = repo.random_favorite(Some(10), 1).unwrap().unwrap();
+    assert_eq!(repo.remove_favorite_id(fav.id).unwrap(), 1);
+    assert!(repo.random_favorite(Some(10), 1).unwrap().is_none());
+}
+
+/// Runs every check against the in-memory repo, and against the test database if there is one
+macro_rules! on_both {
+    ($($check:ident),* $(,)?) => {
+        mod memory {
+            $(
+                #[test]
+                fn $check() {
+                    super::$check(&mut super::MemoryFavorites::default());
+                }
+            )*
+        }
+
+        mod database {
+            $(
+                #[tokio::test]
+                async fn $check() {
+                    let db = crate::db::Database::for_tests();
+                    db.run(|conn| {
+                        super::$check(conn);
+                        Ok(())
+                    })
+                    .await
+                    .unwrap();
+                }
+            )*
+        }
+    };
+}
+
+on_both!(
+    duplicate_favorite_is_rejected,
+    same_message_favorited_by_others,
+    random_favorite_of_user
+);
@@ -0,0 +1,220 @@
+use super::*;
+use crate::testing::{self, FakeDiscord};
+use serde_json::json;
+
+const GUILD: u64 = 1;
+const CHANNEL: u64 = 2;
+const USER: u64 = 10;
+
+fn favorite(message_id: u64) -> NewFavorite {
+    NewFavorite {
+        user_id: USER as i64,
+        guild_id: GUILD as i64,
+        channel_id: CHANNEL as i64,
+        message_id: message_id as i64,
+        note: None,
+    }
+}
+
+async fn favorited(data: &Data, message_id: u64) -> bool {
+    data.db
+        .run(move |conn| conn.find_favorite(&favorite(message_id)))
+        .await
+        .unwrap()
+        .is_some()
+}
+
+fn remove_button(message_id: u64) -> String {
+    let button = DeleteFromFavorites {
+        channel_id: CHANNEL,
+        message_id,
+    };
+    button.custom_id_for(Some(UserId::new(USER)), None).unwrap()
+}
+
+#[tokio::test]
+async fn remove_button_deletes_favorite() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    data.db
+        .run(|conn| conn.add_favorite(&favorite(100)))
+        .await
+        .unwrap();
+
+    let event = testing::button_press(&remove_button(100), GUILD, CHANNEL, USER);
+    component::dispatch(&fake.bot(), &event, &data)
+        .await
+        .unwrap();
+    assert!(!favorited(&data, 100).await);
+    component::dispatch(&fake.bot(), &event, &data)
+        .await
+        .unwrap();
+
+    let replies: Vec<_> = fake
+        .requests_to("POST")
+        .into_iter()
+        .map(|r| {
+            assert_eq!(r.path, testing::callback_path(&event));
+            assert_eq!(r.body["data"]["flags"], json!(64));
+            r.body["data"]["content"].clone()
+        })
+        .collect();
+    assert_eq!(
+        replies,
+        vec![
+            json!("Successfully removed from favorites."),
+            json!("This message is not in your favorites.")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn remove_button_of_someone_else_is_rejected() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    data.db
+        .run(|conn| conn.add_favorite(&favorite(100)))
+        .await
+        .unwrap();
+
+    let event = testing::button_press(&remove_button(100), GUILD, CHANNEL, USER + 1);
+    component::dispatch(&fake.bot(), &event, &data)
+        .await
+        .unwrap();
+
+    assert!(favorited(&data, 100).await);
+    let requests = fake.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].body["data"]["content"],
+        json!("This button isn't meant for you.")
+    );
+}
+
+#[tokio::test]
+async fn unsigned_remove_buttons_still_work() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    for (message_id, custom_id) in [
+        (100, format!("DeleteFromFavorites/{}/100", CHANNEL)),
+        (101, format!("DeleteFromFavorites/v1/{}/101", CHANNEL)),
+    ] {
+        data.db
+            .run(move |conn| conn.add_favorite(&favorite(message_id)))
+            .await
+            .unwrap();
+        let event = testing::button_press(&custom_id, GUILD, CHANNEL, USER);
+        component::dispatch(&fake.bot(), &event, &data)
+            .await
+            .unwrap();
+        assert!(!favorited(&data, message_id).await);
+    }
+}
+
+#[tokio::test]
+async fn draw_forgets_deleted_messages() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    let bot = fake.bot();
+    let (user, guild) = (Some(USER as i64), GUILD as i64);
+    fake.respond(
+        "GET",
+        &format!("/channels/{}/messages/100", CHANNEL),
+        404,
+        json!({"message": "Unknown Message", "code": 10008}),
+    );
+    fake.respond(
+        "GET",
+        &format!("/channels/{}/messages/101", CHANNEL),
+        200,
+        testing::message(GUILD, CHANNEL, 101, USER + 1),
+    );
+
+    data.db
+        .run(|conn| conn.add_favorite(&favorite(100)))
+        .await
+        .unwrap();
+    assert!(draw(&bot.http, &data.db, user, guild)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(!favorited(&data, 100).await);
+
+    data.db
+        .run(|conn| conn.add_favorite(&favorite(101)))
+        .await
+        .unwrap();
+    let (fav, msg) = draw(&bot.http, &data.db, user, guild)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(fav.message_id, 101);
+    assert_eq!(msg.id.get(), 101);
+    assert!(favorited(&data, 101).await);
+}
+
+#[tokio::test]
+async fn mystery_without_favorites_says_so() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+
+    let reply = mystery_reply(
+        &fake.bot(),
+        &data,
+        UserId::new(USER),
+        GuildId::new(GUILD),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(reply.content.as_deref(), Some("No messages favorited yet!"));
+    assert_eq!(reply.ephemeral, Some(true));
+}
+
+#[tokio::test]
+async fn mystery_posts_a_favorite_with_its_remove_button() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    fake.respond(
+        "GET",
+        &format!("/channels/{}/messages/101", CHANNEL),
+        200,
+        testing::message(GUILD, CHANNEL, 101, USER + 1),
+    );
+    data.db
+        .run(|conn| {
+            conn.add_favorite(&NewFavorite {
+                note: Some("Classic".to_owned()),
+                ..favorite(101)
+            })
+        })
+        .await
+        .unwrap();
+
+    let reply = mystery_reply(
+        &fake.bot(),
+        &data,
+        UserId::new(USER),
+        GuildId::new(GUILD),
+        None,
+    )
+    .await
+    .unwrap();
+    let embed = serde_json::to_value(&reply.embeds[0]).unwrap();
+    assert_eq!(embed["description"], "Message 101");
+    assert_eq!(embed["author"]["name"], format!("user{}", USER + 1));
+    assert_eq!(embed["footer"]["text"], "Classic");
+    let buttons = serde_json::to_value(reply.components.unwrap()).unwrap();
+    assert_eq!(buttons[0]["components"][1]["custom_id"], remove_button(101));
+
+    // Someone else has no favorites of their own, but draws from the server's
+    let other = UserId::new(USER + 2);
+    let reply = mystery_reply(&fake.bot(), &data, other, GuildId::new(GUILD), None)
+        .await
+        .unwrap();
+    assert!(reply.embeds.is_empty());
+    let reply = mystery_reply(&fake.bot(), &data, other, GuildId::new(GUILD), Some(true))
+        .await
+        .unwrap();
+    assert_eq!(reply.embeds.len(), 1);
+}
@@ -1,5 +1,64 @@
-use crate::{AppError, Context};
+use crate::modal::{self, Form};
+use crate::{AppError, Bot, Context, Data, ModalAction};
+use poise::serenity_prelude as serenity;
 use rand::seq::SliceRandom;
+use serenity::{builder::*, model::prelude::*};
+
+/// Form composing a message the bot posts in a channel
+pub struct ComposeMessage {
+    pub channel_id: u64,
+}
+
+#[derive(Debug, poise::Modal)]
+#[name = "Compose message"]
+pub struct MessageContent {
+    #[name = "Text"]
+    #[paragraph]
+    #[max_length = 2000]
+    content: Option<String>,
+    #[name = "Embed title"]
+    #[max_length = 256]
+    title: Option<String>,
+    #[name = "Embed text"]
+    #[paragraph]
+    #[max_length = 4000]
+    description: Option<String>,
+    #[name = "Embed colour"]
+    #[placeholder = "Hex code like #5865f2"]
+    #[max_length = 7]
+    colour: Option<String>,
+}
+
+impl Form for ComposeMessage {
+    const ACTION: ModalAction = ModalAction::ComposeMessage;
+    const MODULE: &'static str = "general";
+    type Inputs = MessageContent;
+
+    fn encode(&self) -> Vec<String> {
+        vec![self.channel_id.to_string()]
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        match fields {
+            [channel_id] => Some(ComposeMessage {
+                channel_id: channel_id.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ModalInteraction,
+        data: &'a Data,
+        inputs: MessageContent,
+    ) -> poise::BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(send_composed(ctx, event, data, self, inputs))
+    }
+}
+
+inventory::submit!(modal::Route::of::<ComposeMessage>());
 
 /// Show this help menu
 #[poise::command(slash_command, ephemeral = true)]
@@ -36,13 +95,40 @@ pub async fn shutdown(ctx: Context<'_>) -> Result<(), AppError> {
     Ok(())
 }
 
+/// Show how busy the database connection pool is
+#[poise::command(slash_command, owners_only, hide_in_help, ephemeral = true)]
+pub async fn dbstats(ctx: Context<'_>) -> Result<(), AppError> {
+    let stats = ctx.data().db.stats();
+    ctx.say(format!(
+        "**Connections:** {} ({} idle)\n**Queries:** {}\n**Wait for a connection:** {:?} on average, {:?} at most\n**Timeouts:** {}",
+        stats.connections,
+        stats.idle,
+        stats.checkouts,
+        stats.average_wait,
+        stats.max_wait,
+        stats.timeouts
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Post a message as the bot, leave out the text to compose one with an embed
 #[poise::command(slash_command, owners_only, hide_in_help)]
 pub async fn say(
     ctx: Context<'_>,
     #[rest]
     #[description = "Text to say"]
-    msg: String,
+    msg: Option<String>,
 ) -> Result<(), AppError> {
+    let msg = match msg {
+        Some(msg) => msg,
+        None => {
+            let form = ComposeMessage {
+                channel_id: ctx.channel_id().get(),
+            };
+            return modal::show(ctx, &form, None).await;
+        }
+    };
     ctx.channel_id().say(&ctx, msg).await?;
     ctx.send(
         poise::CreateReply::default()
@@ -108,3 +194,43 @@ pub async fn eight_ball(
     ctx.send(poise::CreateReply::default().content(msg)).await?;
     Ok(())
 }
+
+async fn send_composed(
+    ctx: &Bot,
+    event: &ModalInteraction,
+    _data: &Data,
+    form: ComposeMessage,
+    inputs: MessageContent,
+) -> Result<(), AppError> {
+    let mut msg = CreateMessage::new();
+    if let Some(content) = inputs.content.filter(|c| !c.trim().is_empty()) {
+        msg = msg.content(content);
+    } else if inputs.title.is_none() && inputs.description.is_none() {
+        return modal::reply(ctx, event, "There is nothing to send").await;
+    }
+
+    if inputs.title.is_some() || inputs.description.is_some() {
+        let mut embed = CreateEmbed::default();
+        if let Some(title) = inputs.title {
+            embed = embed.title(title);
+        }
+        if let Some(description) = inputs.description {
+            embed = embed.description(description);
+        }
+        if let Some(colour) = inputs.colour {
+            match u32::from_str_radix(colour.trim().trim_start_matches('#'), 16) {
+                Ok(colour) => embed = embed.colour(colour),
+                Err(_) => {
+                    let msg = format!("'{}' is not a hex colour", colour);
+                    return modal::reply(ctx, event, &msg).await;
+                }
+            }
+        }
+        msg = msg.embed(embed);
+    }
+
+    ChannelId::new(form.channel_id)
+        .send_message(ctx, msg)
+        .await?;
+    modal::reply(ctx, event, "Sent.").await
+}
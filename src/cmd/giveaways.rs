@@ -0,0 +1,484 @@
+mod model;
+
+use crate::cmd::module_settings::Modules;
+use crate::cmd::roles::model::RoleMenuRepo;
+use crate::component::{Component, Route, TooLong};
+use crate::util::{from_now, is_not_found, parse_duration, relative_time};
+use crate::{AppError, Bot, ComponentAction, Context, Data, Db};
+use chrono::Utc;
+use log::{error, info, warn};
+use model::*;
+use poise::serenity_prelude as serenity;
+use rand::seq::SliceRandom;
+use serenity::{builder::*, model::prelude::*};
+use std::time::Duration;
+
+/// Button entering a giveaway
+pub struct EnterGiveaway {
+    pub id: i32,
+}
+
+impl Component for EnterGiveaway {
+    const ACTION: ComponentAction = ComponentAction::EnterGiveaway;
+    const MODULE: &'static str = "giveaways";
+
+    fn encode(&self) -> Vec<String> {
+        vec![self.id.to_string()]
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        match fields {
+            [id] => Some(EnterGiveaway {
+                id: id.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ComponentInteraction,
+        data: &'a Data,
+    ) -> poise::BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(enter(ctx, event, data, self))
+    }
+}
+
+inventory::submit!(Route::of::<EnterGiveaway>());
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("start", "end", "reroll", "list")
+)]
+pub async fn giveaway(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Start a giveaway in this channel
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn start(
+    ctx: Context<'_>,
+    #[description = "What is being given away"] prize: String,
+    #[description = "How long the giveaway runs, e.g. '3d' or '12h'"] duration: String,
+    #[min = 1]
+    #[max = 20]
+    #[description = "Number of winners"]
+    winners: Option<i32>,
+    #[description = "Role required to enter, must be in a role menu"] required_role: Option<Role>,
+    #[description = "Minimum account age of entrants, e.g. '30d'"] min_account_age: Option<String>,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+
+    let ends_at = match parse_duration(&duration).map(from_now) {
+        Ok(Some(time)) => time,
+        Ok(None) => {
+            ctx.say("That is too far in the future").await?;
+            return Ok(());
+        }
+        Err(_) => {
+            ctx.say(format!("'{}' is not a duration I understand", &duration))
+                .await?;
+            return Ok(());
+        }
+    };
+    let min_account_age = match min_account_age.as_deref().map(parse_duration) {
+        Some(Ok(d)) => Some(d.as_secs() as i64),
+        Some(Err(_)) => {
+            ctx.say(format!(
+                "'{}' is not a duration I understand",
+                min_account_age.unwrap()
+            ))
+            .await?;
+            return Ok(());
+        }
+        None => None,
+    };
+    if let Some(role) = &required_role {
+        let role_id = role.id.get() as i64;
+        let in_menu = ctx
+            .data()
+            .db
+            .run(move |conn| conn.role_in_menu(guild_id, role_id))
+            .await?;
+        if !in_menu {
+            ctx.say(format!(
+                "The role '{}' is not part of any role menu",
+                &role.name
+            ))
+            .await?;
+            return Ok(());
+        }
+    }
+
+    let new = NewGiveaway {
+        guild_id,
+        channel_id: ctx.channel_id().get() as i64,
+        host_id: ctx.author().id.get() as i64,
+        prize,
+        winners: winners.unwrap_or(1),
+        required_role_id: required_role.map(|r| r.id.get() as i64),
+        min_account_age,
+        ends_at,
+    };
+    let giveaway = ctx.data().db.run(move |conn| new.insert(conn)).await?;
+    let msg = ctx
+        .channel_id()
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .embed(embed(&giveaway, 0, &[]))
+                .components(vec![enter_button(&giveaway)?]),
+        )
+        .await?;
+    let (id, message_id) = (giveaway.id, msg.id.get() as i64);
+    ctx.data()
+        .db
+        .run(move |conn| giveaway.set_message(conn, message_id))
+        .await?;
+
+    ctx.say(format!("Started giveaway #{}", id)).await?;
+    Ok(())
+}
+
+/// End a giveaway early and draw its winners
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn end(
+    ctx: Context<'_>,
+    #[description = "Number of the giveaway"] id: i32,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let giveaway = ctx
+        .data()
+        .db
+        .run(move |conn| Giveaway::find(conn, guild_id, id))
+        .await?;
+    let msg = match giveaway {
+        Some(giveaway) if !giveaway.ended => {
+            if finish(ctx.serenity_context(), &ctx.data().db, giveaway).await? {
+                format!("Ended giveaway #{}", id)
+            } else {
+                format!("Giveaway #{} has already ended", id)
+            }
+        }
+        Some(_) => format!("Giveaway #{} has already ended", id),
+        None => format!("Could not find giveaway #{}", id),
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Draw new winners for an ended giveaway
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn reroll(
+    ctx: Context<'_>,
+    #[description = "Number of the giveaway"] id: i32,
+    #[min = 1]
+    #[max = 20]
+    #[description = "How many new winners to draw"]
+    count: Option<i32>,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let giveaway = ctx
+        .data()
+        .db
+        .run(move |conn| Giveaway::find(conn, guild_id, id))
+        .await?;
+    let giveaway = match giveaway {
+        Some(giveaway) if giveaway.ended => giveaway,
+        Some(_) => {
+            ctx.say(format!("Giveaway #{} is still running", id))
+                .await?;
+            return Ok(());
+        }
+        None => {
+            ctx.say(format!("Could not find giveaway #{}", id)).await?;
+            return Ok(());
+        }
+    };
+
+    let count = count.unwrap_or(1) as usize;
+    let (giveaway, winners) = ctx
+        .data()
+        .db
+        .run(move |conn| {
+            let winners = draw(&giveaway.entries(conn)?, count);
+            giveaway.mark_winners(conn, &winners)?;
+            Ok((giveaway, winners))
+        })
+        .await?;
+    if winners.is_empty() {
+        ctx.say("There is no one left to draw").await?;
+        return Ok(());
+    }
+
+    ChannelId::new(giveaway.channel_id as u64)
+        .say(
+            ctx,
+            format!(
+                "🎉 New winner(s) for **{}**: {}!",
+                giveaway.prize,
+                mentions(&winners)
+            ),
+        )
+        .await?;
+    ctx.say("Rerolled.").await?;
+    Ok(())
+}
+
+/// List running giveaways
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn list(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let giveaways = ctx
+        .data()
+        .db
+        .run(move |conn| Giveaway::active(conn, guild_id))
+        .await?;
+
+    if giveaways.is_empty() {
+        ctx.say("There are no running giveaways.").await?;
+        return Ok(());
+    }
+
+    let lines: Vec<_> = giveaways
+        .iter()
+        .map(|g| {
+            format!(
+                "**#{}** {} in <#{}>, ends {}",
+                g.id,
+                g.prize,
+                g.channel_id,
+                relative_time(g.ends_at)
+            )
+        })
+        .collect();
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Running giveaways")
+                .description(lines.join("\n")),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn enter(
+    ctx: &Bot,
+    event: &ComponentInteraction,
+    data: &Data,
+    button: EnterGiveaway,
+) -> Result<(), AppError> {
+    let id = button.id;
+    let giveaway = data.db.run(move |conn| Giveaway::find_id(conn, id)).await?;
+    let giveaway = match giveaway {
+        Some(giveaway) if !giveaway.ended => giveaway,
+        _ => return reply(ctx, event, "This giveaway has ended.".to_owned()).await,
+    };
+
+    if let Some(role) = giveaway.required_role_id {
+        let role = RoleId::new(role as u64);
+        if !event
+            .member
+            .as_ref()
+            .map_or(false, |m| m.roles.contains(&role))
+        {
+            let msg = format!("You need the <@&{}> role to enter this giveaway.", role);
+            return reply(ctx, event, msg).await;
+        }
+    }
+    if let Some(min_age) = giveaway.min_account_age {
+        let age = Utc::now().timestamp() - event.user.id.created_at().unix_timestamp();
+        if age < min_age {
+            let msg = format!(
+                "Your account must be at least {} old to enter this giveaway.",
+                humantime::format_duration(Duration::from_secs(min_age as u64))
+            );
+            return reply(ctx, event, msg).await;
+        }
+    }
+
+    let user_id = event.user.id.get() as i64;
+    let (giveaway, entered, entries) = data
+        .db
+        .run(move |conn| {
+            let entered = giveaway.toggle_entry(conn, user_id)?;
+            let entries = giveaway.entries(conn)?;
+            Ok((giveaway, entered, entries))
+        })
+        .await?;
+    event
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new().embed(embed(
+                    &giveaway,
+                    entries.len(),
+                    &[],
+                )),
+            ),
+        )
+        .await?;
+    let res = if entered {
+        "You entered the giveaway, good luck!"
+    } else {
+        "You withdrew from the giveaway."
+    };
+    event
+        .create_followup(
+            ctx,
+            CreateInteractionResponseFollowup::new()
+                .ephemeral(true)
+                .content(res),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Ends every giveaway whose end time has passed and announces the winners
+pub async fn end_due(ctx: &serenity::Context, db: &Db, modules: &Modules) -> Result<(), AppError> {
+    let now = Utc::now();
+    for giveaway in db.run(move |conn| Giveaway::due(conn, now)).await? {
+        let guild_id = GuildId::new(giveaway.guild_id as u64);
+        if !modules.enabled(Some(guild_id), "giveaways").await? {
+            continue;
+        }
+        let id = giveaway.id;
+        if let Err(e) = finish(ctx, db, giveaway).await {
+            error!("Could not end giveaway {}: {:?}", id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Ends the giveaway and announces the winners. The giveaway is claimed first, so it is announced
+/// only once even if the scheduler and `/giveaway end` race, and a deleted message or channel
+/// doesn't keep it running. Returns whether it was still running.
+async fn finish(ctx: &serenity::Context, db: &Db, giveaway: Giveaway) -> Result<bool, AppError> {
+    info!("Ending giveaway {}", giveaway.id);
+    let id = giveaway.id;
+    let ended = db
+        .run(move |conn| giveaway.end(conn, |entries| draw(entries, giveaway.winners as usize)))
+        .await?;
+    let (giveaway, entries, winners) = match ended {
+        Some(ended) => ended,
+        None => {
+            warn!("Giveaway {} was ended twice", id);
+            return Ok(false);
+        }
+    };
+
+    let channel = ChannelId::new(giveaway.channel_id as u64);
+    if let Some(message_id) = giveaway.message_id {
+        let res = channel
+            .edit_message(
+                ctx,
+                MessageId::new(message_id as u64),
+                EditMessage::new()
+                    .embed(embed(&giveaway, entries, &winners))
+                    .components(vec![]),
+            )
+            .await;
+        match res {
+            Ok(_) => {}
+            Err(e) if is_not_found(&e) => {}
+            Err(e) => error!("Could not edit giveaway {}: {:?}", id, e),
+        }
+    }
+
+    let msg = if winners.is_empty() {
+        format!("No one entered the giveaway for **{}** 😔", giveaway.prize)
+    } else {
+        format!(
+            "🎉 Congratulations {}! You won **{}**!",
+            mentions(&winners),
+            giveaway.prize
+        )
+    };
+    match channel.say(ctx, msg).await {
+        Ok(_) => Ok(true),
+        Err(e) if is_not_found(&e) => Ok(true),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Picks up to `count` random entrants that haven't won yet
+fn draw(entries: &[GiveawayEntry], count: usize) -> Vec<i64> {
+    let candidates: Vec<i64> = entries
+        .iter()
+        .filter(|e| !e.won)
+        .map(|e| e.user_id)
+        .collect();
+    candidates
+        .choose_multiple(&mut rand::thread_rng(), count)
+        .copied()
+        .collect()
+}
+
+fn mentions(users: &[i64]) -> String {
+    users
+        .iter()
+        .map(|u| format!("<@{}>", u))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn enter_button(giveaway: &Giveaway) -> Result<CreateActionRow, TooLong> {
+    Ok(CreateActionRow::Buttons(vec![CreateButton::new(
+        EnterGiveaway { id: giveaway.id }.custom_id()?,
+    )
+    .style(ButtonStyle::Primary)
+    .emoji('🎉')
+    .label("Enter")]))
+}
+
+fn embed(giveaway: &Giveaway, entries: usize, winners: &[i64]) -> CreateEmbed {
+    let mut lines = vec![
+        format!("Hosted by <@{}>", giveaway.host_id),
+        format!("Entries: {}", entries),
+    ];
+    if giveaway.ended {
+        lines.push(format!("Ended {}", relative_time(giveaway.ends_at)));
+        if !winners.is_empty() {
+            lines.push(format!("Winner(s): {}", mentions(winners)));
+        }
+    } else {
+        lines.push(format!(
+            "Ends {}, {} winner(s)",
+            relative_time(giveaway.ends_at),
+            giveaway.winners
+        ));
+    }
+    if let Some(role) = giveaway.required_role_id {
+        lines.push(format!("Requires the <@&{}> role", role));
+    }
+    if let Some(age) = giveaway.min_account_age {
+        lines.push(format!(
+            "Accounts must be at least {} old",
+            humantime::format_duration(Duration::from_secs(age as u64))
+        ));
+    }
+
+    CreateEmbed::default()
+        .title(format!("🎁 {}", giveaway.prize))
+        .description(lines.join("\n"))
+        .footer(CreateEmbedFooter::new(format!("Giveaway #{}", giveaway.id)))
+}
+
+async fn reply(ctx: &Bot, event: &ComponentInteraction, content: String) -> Result<(), AppError> {
+    event
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::Message(
+                serenity::CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(content),
+            ),
+        )
+        .await?;
+    Ok(())
+}
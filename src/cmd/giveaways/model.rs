@@ -0,0 +1,154 @@
+use crate::db::schema;
+use crate::Conn;
+
+use chrono::{DateTime, Utc};
+use diesel::result::Error;
+use diesel::{insert_into, prelude::*};
+use g::giveaway;
+use ge::giveaway_entry;
+use schema::giveaway::dsl as g;
+use schema::giveaway_entry::dsl as ge;
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::giveaway)]
+pub struct Giveaway {
+    pub id: i32,
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub message_id: Option<i64>,
+    pub host_id: i64,
+    pub prize: String,
+    pub winners: i32,
+    pub required_role_id: Option<i64>,
+    /// Minimum age of an entrant's Discord account in seconds
+    pub min_account_age: Option<i64>,
+    pub ends_at: DateTime<Utc>,
+    pub ended: bool,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Associations, Debug, PartialEq)]
+#[diesel(belongs_to(Giveaway))]
+#[diesel(table_name = schema::giveaway_entry)]
+pub struct GiveawayEntry {
+    pub id: i32,
+    pub giveaway_id: i32,
+    pub user_id: i64,
+    pub won: bool,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::giveaway)]
+pub struct NewGiveaway {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub host_id: i64,
+    pub prize: String,
+    pub winners: i32,
+    pub required_role_id: Option<i64>,
+    pub min_account_age: Option<i64>,
+    pub ends_at: DateTime<Utc>,
+}
+
+impl Giveaway {
+    pub fn find(conn: &mut Conn, guild_id: i64, id: i32) -> Result<Option<Giveaway>, Error> {
+        giveaway
+            .find(id)
+            .filter(g::guild_id.eq(guild_id))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn find_id(conn: &mut Conn, id: i32) -> Result<Option<Giveaway>, Error> {
+        giveaway.find(id).first(conn).optional()
+    }
+
+    pub fn active(conn: &mut Conn, guild_id: i64) -> Result<Vec<Giveaway>, Error> {
+        giveaway
+            .filter(g::guild_id.eq(guild_id))
+            .filter(g::ended.eq(false))
+            .order(g::ends_at)
+            .load(conn)
+    }
+
+    /// Running giveaways whose end time has passed
+    pub fn due(conn: &mut Conn, now: DateTime<Utc>) -> Result<Vec<Giveaway>, Error> {
+        giveaway
+            .filter(g::ended.eq(false))
+            .filter(g::ends_at.le(now))
+            .load(conn)
+    }
+
+    pub fn set_message(&self, conn: &mut Conn, message_id: i64) -> Result<usize, Error> {
+        diesel::update(self)
+            .set(g::message_id.eq(message_id))
+            .execute(conn)
+    }
+
+    /// Claims the giveaway for ending and marks the winners `draw` picks from the entries.
+    /// Returns the ended giveaway, how many entered and the winners, or `None` if it already ended.
+    pub fn end(
+        &self,
+        conn: &mut Conn,
+        draw: impl FnOnce(&[GiveawayEntry]) -> Vec<i64>,
+    ) -> Result<Option<(Giveaway, usize, Vec<i64>)>, Error> {
+        conn.transaction(|conn| {
+            let ended: Option<Giveaway> = diesel::update(self)
+                .filter(g::ended.eq(false))
+                .set((
+                    g::ended.eq(true),
+                    g::ends_at.eq(Utc::now().min(self.ends_at)),
+                ))
+                .get_result(conn)
+                .optional()?;
+            let ended = match ended {
+                Some(ended) => ended,
+                None => return Ok(None),
+            };
+            let entries = ended.entries(conn)?;
+            let winners = draw(&entries);
+            ended.mark_winners(conn, &winners)?;
+            Ok(Some((ended, entries.len(), winners)))
+        })
+    }
+
+    pub fn entries(&self, conn: &mut Conn) -> Result<Vec<GiveawayEntry>, Error> {
+        GiveawayEntry::belonging_to(self).load(conn)
+    }
+
+    /// Enters the user into the giveaway, or withdraws them if they already entered.
+    /// Returns whether the user is entered afterwards.
+    pub fn toggle_entry(&self, conn: &mut Conn, user_id: i64) -> Result<bool, Error> {
+        conn.transaction(|conn| {
+            let removed = diesel::delete(
+                giveaway_entry
+                    .filter(ge::giveaway_id.eq(self.id))
+                    .filter(ge::user_id.eq(user_id)),
+            )
+            .execute(conn)?;
+            if removed > 0 {
+                return Ok(false);
+            }
+
+            insert_into(giveaway_entry)
+                .values((ge::giveaway_id.eq(self.id), ge::user_id.eq(user_id)))
+                .execute(conn)?;
+            Ok(true)
+        })
+    }
+
+    pub fn mark_winners(&self, conn: &mut Conn, users: &[i64]) -> Result<usize, Error> {
+        diesel::update(
+            giveaway_entry
+                .filter(ge::giveaway_id.eq(self.id))
+                .filter(ge::user_id.eq_any(users)),
+        )
+        .set(ge::won.eq(true))
+        .execute(conn)
+    }
+}
+
+impl NewGiveaway {
+    pub fn insert(&self, conn: &mut Conn) -> Result<Giveaway, Error> {
+        self.insert_into(giveaway).get_result(conn)
+    }
+}
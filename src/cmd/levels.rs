@@ -0,0 +1,324 @@
+pub mod model;
+
+use crate::cmd::roles::model::RoleMenuRepo;
+use crate::{AppError, Context, Data};
+use diesel::result::DatabaseErrorKind;
+use log::error;
+use model::*;
+use poise::serenity_prelude as serenity;
+use rand::Rng;
+use serenity::{builder::*, model::prelude::*};
+
+const COOLDOWN_SECS: i64 = 60;
+
+/// Show your or someone else's level
+#[poise::command(slash_command, prefix_command, guild_only = true)]
+pub async fn rank(
+    ctx: Context<'_>,
+    #[description = "Whose rank to show"] user: Option<User>,
+) -> Result<(), AppError> {
+    let user = user.as_ref().unwrap_or(ctx.author());
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let user_id = user.id.get() as i64;
+
+    let ranked = ctx
+        .data()
+        .db
+        .run(move |conn| match XpMember::find(conn, guild_id, user_id)? {
+            Some(member) => {
+                let rank = member.rank(conn)?;
+                Ok(Some((member, rank)))
+            }
+            None => Ok(None),
+        })
+        .await?;
+    let (member, rank) = match ranked {
+        Some(ranked) => ranked,
+        None => {
+            ctx.say(format!("{} hasn't earned any XP yet", user.name))
+                .await?;
+            return Ok(());
+        }
+    };
+    let (level, progress, needed) = level_of(member.xp);
+    let filled = (progress * 10 / needed) as usize;
+    let embed = CreateEmbed::default()
+        .author(CreateEmbedAuthor::new(&user.name).icon_url(user.face()))
+        .field("Rank", format!("#{}", rank), true)
+        .field("Level", level.to_string(), true)
+        .field("Total XP", member.xp.to_string(), true)
+        .description(format!(
+            "{}{} {}/{} XP",
+            "🫘".repeat(filled),
+            "▫️".repeat(10 - filled),
+            progress,
+            needed
+        ));
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Show the most active members of this server
+#[poise::command(slash_command, prefix_command, guild_only = true)]
+pub async fn leaderboard(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let top = ctx
+        .data()
+        .db
+        .run(move |conn| XpMember::top(conn, guild_id, 10))
+        .await?;
+
+    if top.is_empty() {
+        ctx.say("No one has earned any XP yet!").await?;
+        return Ok(());
+    }
+
+    let lines: Vec<_> = top
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            format!(
+                "**{}.** <@{}> – level {} ({} XP)",
+                i + 1,
+                m.user_id,
+                level_of(m.xp).0,
+                m.xp
+            )
+        })
+        .collect();
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Leaderboard")
+                .description(lines.join("\n")),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "MANAGE_GUILD",
+    required_bot_permissions = "MANAGE_ROLES",
+    subcommands("multiplier", "reward", "rewards")
+)]
+pub async fn levels(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Change how much XP is earned in a channel, 0 disables it
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn multiplier(
+    ctx: Context<'_>,
+    #[description = "Channel to configure"] channel: GuildChannel,
+    #[min = 0]
+    #[max = 10]
+    #[description = "XP multiplier"]
+    value: f64,
+) -> Result<(), AppError> {
+    let (guild_id, channel_id) = (
+        ctx.guild_id().unwrap().get() as i64,
+        channel.id.get() as i64,
+    );
+    ctx.data()
+        .db
+        .run(move |conn| XpChannel::set(conn, guild_id, channel_id, value))
+        .await?;
+    ctx.say(format!(
+        "XP in <#{}> is now multiplied by {}",
+        channel.id, value
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, subcommands("reward_add", "reward_remove"))]
+pub async fn reward(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Grant a role when members reach a level
+#[poise::command(slash_command, ephemeral = true, rename = "add")]
+pub async fn reward_add(
+    ctx: Context<'_>,
+    #[min = 1]
+    #[description = "Level at which the role is granted"]
+    level: i32,
+    #[description = "Role to grant"] role: Role,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let new = NewLevelReward {
+        guild_id,
+        level,
+        role_id: role.id.get() as i64,
+    };
+
+    let (added, in_menu) = ctx
+        .data()
+        .db
+        .run(move |conn| {
+            let added = match new.insert(conn) {
+                Ok(_) => true,
+                Err(diesel::result::Error::DatabaseError(
+                    DatabaseErrorKind::UniqueViolation,
+                    _,
+                )) => false,
+                Err(e) => return Err(e),
+            };
+            Ok((added, conn.role_in_menu(guild_id, new.role_id)?))
+        })
+        .await?;
+    let mut msg = if added {
+        format!("Members reaching level {} now get '{}'", level, &role.name)
+    } else {
+        format!("'{}' is already a level reward", &role.name)
+    };
+    if in_menu {
+        msg += ". It is also part of a role menu, which can no longer assign or remove it.";
+    }
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Stop granting a role for levelling up
+#[poise::command(slash_command, ephemeral = true, rename = "remove")]
+pub async fn reward_remove(
+    ctx: Context<'_>,
+    #[description = "Role to stop granting"] role: Role,
+) -> Result<(), AppError> {
+    let (guild_id, role_id) = (ctx.guild_id().unwrap().get() as i64, role.id.get() as i64);
+    let deleted = ctx
+        .data()
+        .db
+        .run(move |conn| LevelReward::delete(conn, guild_id, role_id))
+        .await?;
+    let msg = if deleted > 0 {
+        format!("'{}' is no longer a level reward", &role.name)
+    } else {
+        format!("'{}' is not a level reward", &role.name)
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// List the roles granted for levelling up
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn rewards(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let rewards = ctx
+        .data()
+        .db
+        .run(move |conn| LevelReward::list(conn, guild_id))
+        .await?;
+
+    if rewards.is_empty() {
+        ctx.say("No level rewards configured yet.").await?;
+        return Ok(());
+    }
+
+    let lines: Vec<_> = rewards
+        .iter()
+        .map(|r| format!("Level {}: <@&{}>", r.level, r.role_id))
+        .collect();
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Level rewards")
+                .description(lines.join("\n")),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Awards XP for a message and grants reward roles on level up
+pub async fn on_message(
+    ctx: &serenity::Context,
+    msg: &Message,
+    data: &Data,
+) -> Result<(), AppError> {
+    let guild_id = match msg.guild_id {
+        Some(id) if !msg.author.bot => id,
+        _ => return Ok(()),
+    };
+    let (guild, user_id, channel_id) = (
+        guild_id.get() as i64,
+        msg.author.id.get() as i64,
+        msg.channel_id.get() as i64,
+    );
+
+    let levelled_up = data
+        .db
+        .run(move |conn| {
+            let multiplier = XpChannel::multiplier(conn, guild, channel_id)?;
+            let amount = (rand::thread_rng().gen_range(15..=25) as f64 * multiplier).round() as i64;
+            if amount <= 0 {
+                return Ok(None);
+            }
+
+            let awarded = XpMember::award(
+                conn,
+                guild,
+                user_id,
+                amount,
+                chrono::Duration::seconds(COOLDOWN_SECS),
+            )?;
+            let (before, after) = match awarded {
+                Some(xp) => xp,
+                None => return Ok(None),
+            };
+            let level = level_of(after).0;
+            if level == level_of(before).0 {
+                return Ok(None);
+            }
+            Ok(Some((
+                level,
+                LevelReward::up_to(conn, guild, level as i32)?,
+            )))
+        })
+        .await?;
+    let (level, rewards) = match levelled_up {
+        Some(levelled_up) => levelled_up,
+        None => return Ok(()),
+    };
+    if !rewards.is_empty() {
+        let mut member = guild_id.member(ctx, msg.author.id).await?;
+        let missing: Vec<_> = rewards
+            .iter()
+            .map(|r| RoleId::new(r.role_id as u64))
+            .filter(|r| !member.roles.contains(r))
+            .collect();
+        // A reward above the bot's own role can't be given, that shouldn't hide the level up
+        if let Err(e) = member.add_roles(ctx, &missing).await {
+            error!(
+                "Could not give level {} rewards to {} in {}: {:?}",
+                level, msg.author.id, guild_id, e
+            );
+        }
+    }
+    msg.channel_id
+        .say(
+            ctx,
+            format!("🫘 <@{}> reached level {}!", msg.author.id, level),
+        )
+        .await?;
+    Ok(())
+}
+
+/// XP needed to advance from `level` to the next one
+fn xp_for_next(level: i64) -> i64 {
+    5 * level * level + 50 * level + 100
+}
+
+/// Level reached with the given total XP, along with the XP earned towards and needed for the next level
+fn level_of(mut xp: i64) -> (i64, i64, i64) {
+    let mut level = 0;
+    while xp >= xp_for_next(level) {
+        xp -= xp_for_next(level);
+        level += 1;
+    }
+    (level, xp, xp_for_next(level))
+}
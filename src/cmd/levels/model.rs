@@ -0,0 +1,181 @@
+use crate::db::schema;
+use crate::Conn;
+
+use chrono::{DateTime, Utc};
+use diesel::result::Error;
+use diesel::{insert_into, prelude::*};
+use lr::level_reward;
+use poise::serenity_prelude::RoleId;
+use schema::level_reward::dsl as lr;
+use schema::xp_channel::dsl as xc;
+use schema::xp_member::dsl as xm;
+use xc::xp_channel;
+use xm::xp_member;
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::xp_member)]
+pub struct XpMember {
+    pub id: i32,
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub xp: i64,
+    pub last_award: DateTime<Utc>,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::xp_channel)]
+pub struct XpChannel {
+    pub id: i32,
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub multiplier: f64,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::level_reward)]
+pub struct LevelReward {
+    pub id: i32,
+    pub guild_id: i64,
+    pub level: i32,
+    pub role_id: i64,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::level_reward)]
+pub struct NewLevelReward {
+    pub guild_id: i64,
+    pub level: i32,
+    pub role_id: i64,
+}
+
+impl XpMember {
+    pub fn find(conn: &mut Conn, guild_id: i64, user_id: i64) -> Result<Option<XpMember>, Error> {
+        xp_member
+            .filter(xm::guild_id.eq(guild_id))
+            .filter(xm::user_id.eq(user_id))
+            .first(conn)
+            .optional()
+    }
+
+    /// Awards XP unless the member already received some within the cooldown.
+    /// Returns the XP before and after the award.
+    pub fn award(
+        conn: &mut Conn,
+        guild_id: i64,
+        user_id: i64,
+        amount: i64,
+        cooldown: chrono::Duration,
+    ) -> Result<Option<(i64, i64)>, Error> {
+        conn.transaction(|conn| {
+            let now = Utc::now();
+            let before = match XpMember::find(conn, guild_id, user_id)? {
+                Some(m) if now - m.last_award < cooldown => return Ok(None),
+                Some(m) => m.xp,
+                None => 0,
+            };
+
+            let after: XpMember = insert_into(xp_member)
+                .values((
+                    xm::guild_id.eq(guild_id),
+                    xm::user_id.eq(user_id),
+                    xm::xp.eq(amount),
+                    xm::last_award.eq(now),
+                ))
+                .on_conflict((xm::guild_id, xm::user_id))
+                .do_update()
+                .set((xm::xp.eq(xm::xp + amount), xm::last_award.eq(now)))
+                .get_result(conn)?;
+            Ok(Some((before, after.xp)))
+        })
+    }
+
+    pub fn top(conn: &mut Conn, guild_id: i64, limit: i64) -> Result<Vec<XpMember>, Error> {
+        xp_member
+            .filter(xm::guild_id.eq(guild_id))
+            .order(xm::xp.desc())
+            .limit(limit)
+            .load(conn)
+    }
+
+    /// Position on the guild's leaderboard, starting at 1
+    pub fn rank(&self, conn: &mut Conn) -> Result<i64, Error> {
+        let ahead: i64 = xp_member
+            .filter(xm::guild_id.eq(self.guild_id))
+            .filter(xm::xp.gt(self.xp))
+            .count()
+            .get_result(conn)?;
+        Ok(ahead + 1)
+    }
+}
+
+impl XpChannel {
+    /// Multiplier applied to XP earned in a channel, 1.0 unless configured
+    pub fn multiplier(conn: &mut Conn, guild_id: i64, channel_id: i64) -> Result<f64, Error> {
+        let res = xp_channel
+            .select(xc::multiplier)
+            .filter(xc::guild_id.eq(guild_id))
+            .filter(xc::channel_id.eq(channel_id))
+            .first(conn)
+            .optional()?;
+        Ok(res.unwrap_or(1.0))
+    }
+
+    pub fn set(
+        conn: &mut Conn,
+        guild_id: i64,
+        channel_id: i64,
+        multiplier: f64,
+    ) -> Result<usize, Error> {
+        insert_into(xp_channel)
+            .values((
+                xc::guild_id.eq(guild_id),
+                xc::channel_id.eq(channel_id),
+                xc::multiplier.eq(multiplier),
+            ))
+            .on_conflict((xc::guild_id, xc::channel_id))
+            .do_update()
+            .set(xc::multiplier.eq(multiplier))
+            .execute(conn)
+    }
+}
+
+impl LevelReward {
+    pub fn list(conn: &mut Conn, guild_id: i64) -> Result<Vec<LevelReward>, Error> {
+        level_reward
+            .filter(lr::guild_id.eq(guild_id))
+            .order(lr::level)
+            .load(conn)
+    }
+
+    /// Rewards for the given level and all levels below it
+    pub fn up_to(conn: &mut Conn, guild_id: i64, level: i32) -> Result<Vec<LevelReward>, Error> {
+        level_reward
+            .filter(lr::guild_id.eq(guild_id))
+            .filter(lr::level.le(level))
+            .load(conn)
+    }
+
+    /// Roles that are only granted by levelling up
+    pub fn role_ids(conn: &mut Conn, guild_id: i64) -> Result<Vec<RoleId>, Error> {
+        let ids: Vec<i64> = level_reward
+            .select(lr::role_id)
+            .filter(lr::guild_id.eq(guild_id))
+            .load(conn)?;
+        Ok(ids.into_iter().map(|id| RoleId::new(id as u64)).collect())
+    }
+
+    pub fn delete(conn: &mut Conn, guild_id: i64, role_id: i64) -> Result<usize, Error> {
+        diesel::delete(
+            level_reward
+                .filter(lr::guild_id.eq(guild_id))
+                .filter(lr::role_id.eq(role_id)),
+        )
+        .execute(conn)
+    }
+}
+
+impl NewLevelReward {
+    pub fn insert(&self, conn: &mut Conn) -> Result<usize, Error> {
+        self.insert_into(level_reward).execute(conn)
+    }
+}
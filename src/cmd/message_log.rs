@@ -0,0 +1,352 @@
+mod model;
+
+use crate::{AppError, Context, Data};
+use log::error;
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::{builder::*, model::prelude::*};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// How many messages are remembered per guild
+const CACHE_SIZE: usize = 1000;
+
+#[derive(Clone, Debug)]
+pub struct CachedMessage {
+    id: MessageId,
+    author_id: UserId,
+    author_name: String,
+    content: String,
+    attachments: Vec<String>,
+}
+
+impl From<&Message> for CachedMessage {
+    fn from(msg: &Message) -> Self {
+        CachedMessage {
+            id: msg.id,
+            author_id: msg.author.id,
+            author_name: msg.author.name.clone(),
+            content: msg.content.clone(),
+            attachments: msg.attachments.iter().map(|a| a.url.clone()).collect(),
+        }
+    }
+}
+
+/// Recent messages of every guild, since Discord doesn't tell us what an edited or deleted message said
+#[derive(Default)]
+pub struct MessageCache(Mutex<HashMap<GuildId, VecDeque<CachedMessage>>>);
+
+impl MessageCache {
+    fn insert(&self, guild_id: GuildId, msg: CachedMessage) {
+        let mut guilds = self.0.lock().unwrap();
+        let messages = guilds.entry(guild_id).or_default();
+        if messages.len() >= CACHE_SIZE {
+            messages.pop_front();
+        }
+        messages.push_back(msg);
+    }
+
+    /// Replaces the content of a cached message, returning the message as it was before
+    fn update(&self, guild_id: GuildId, id: MessageId, content: &str) -> Option<CachedMessage> {
+        let mut guilds = self.0.lock().unwrap();
+        let msg = guilds.get_mut(&guild_id)?.iter_mut().find(|m| m.id == id)?;
+        let old = msg.clone();
+        msg.content = content.to_owned();
+        Some(old)
+    }
+
+    fn remove(&self, guild_id: GuildId, id: MessageId) -> Option<CachedMessage> {
+        let mut guilds = self.0.lock().unwrap();
+        let messages = guilds.get_mut(&guild_id)?;
+        let pos = messages.iter().position(|m| m.id == id)?;
+        messages.remove(pos)
+    }
+}
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("channel", "ignore", "ignored")
+)]
+pub async fn msglog(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Set the channel edited and deleted messages are logged to
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn channel(
+    ctx: Context<'_>,
+    #[description = "Log channel, empty to disable logging"] channel: Option<GuildChannel>,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let log = channel.as_ref().map(|c| c.id.get() as i64);
+    ctx.data()
+        .db
+        .run(move |conn| set_log_channel(conn, guild_id, log))
+        .await?;
+    let msg = match channel {
+        Some(c) => format!("Edited and deleted messages will be logged in <#{}>", c.id),
+        None => "Disabled the message log".to_owned(),
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Stop logging messages from a channel or user, or start again
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn ignore(
+    ctx: Context<'_>,
+    #[description = "Channel to ignore"] channel: Option<GuildChannel>,
+    #[description = "User to ignore"] user: Option<User>,
+) -> Result<(), AppError> {
+    let (target, kind, mention) = match (channel, user) {
+        (Some(c), None) => (c.id.get(), IgnoreKind::Channel, format!("<#{}>", c.id)),
+        (None, Some(u)) => (u.id.get(), IgnoreKind::User, format!("<@{}>", u.id)),
+        _ => {
+            ctx.say("Pick either a channel or a user").await?;
+            return Ok(());
+        }
+    };
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let ignored = ctx
+        .data()
+        .db
+        .run(move |conn| Ignored::toggle(conn, guild_id, target as i64, kind))
+        .await?;
+    let msg = if ignored {
+        format!("Messages from {} will no longer be logged", mention)
+    } else {
+        format!("Messages from {} will be logged again", mention)
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// List the channels and users whose messages aren't logged
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn ignored(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let ignored = ctx
+        .data()
+        .db
+        .run(move |conn| Ignored::list(conn, guild_id))
+        .await?;
+
+    if ignored.is_empty() {
+        ctx.say("Nothing is ignored.").await?;
+        return Ok(());
+    }
+
+    let lines: Vec<_> = ignored
+        .iter()
+        .map(|i| match i.kind.parse() {
+            Ok(IgnoreKind::Channel) => format!("<#{}>", i.target_id),
+            _ => format!("<@{}>", i.target_id),
+        })
+        .collect();
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Ignored by the message log")
+                .description(lines.join("\n")),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Remembers a message so it can be logged when it's edited or deleted
+pub fn on_message(msg: &Message, data: &Data) {
+    if let Some(guild_id) = msg.guild_id {
+        if !msg.author.bot {
+            data.msg_cache.insert(guild_id, msg.into());
+        }
+    }
+}
+
+pub async fn on_update(
+    ctx: &serenity::Context,
+    event: &MessageUpdateEvent,
+    data: &Data,
+) -> Result<(), AppError> {
+    // Updates without content are embeds being resolved and the like
+    let (guild_id, content) = match (event.guild_id, &event.content) {
+        (Some(guild_id), Some(content)) => (guild_id, content),
+        _ => return Ok(()),
+    };
+    let old = data.msg_cache.update(guild_id, event.id, content);
+    let author = match (&old, &event.author) {
+        (Some(old), _) => old.author_id,
+        (None, Some(author)) if !author.bot && event.edited_timestamp.is_some() => author.id,
+        _ => return Ok(()),
+    };
+    if old.as_ref().map_or(false, |old| &old.content == content) {
+        return Ok(());
+    }
+
+    let log = match log_target(data, guild_id, event.channel_id, &[author.get()]).await? {
+        Some(log) => log,
+        None => return Ok(()),
+    };
+    let before = old.map_or("*Not cached*".to_owned(), |old| old.content);
+    let embed = CreateEmbed::default()
+        .title("Message edited")
+        .description(format!(
+            "By <@{}> in <#{}> [Jump to message]({})",
+            author,
+            event.channel_id,
+            event.id.link(event.channel_id, Some(guild_id))
+        ))
+        .field("Before", truncate(&before, 1024), false)
+        .field("After", truncate(content, 1024), false)
+        .footer(CreateEmbedFooter::new(format!("Message {}", event.id)))
+        .timestamp(Timestamp::now());
+    post(ctx, log, CreateMessage::new().embed(embed)).await;
+    Ok(())
+}
+
+pub async fn on_delete(
+    ctx: &serenity::Context,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    data: &Data,
+) -> Result<(), AppError> {
+    let guild_id = match guild_id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    // Without a cached copy there is nothing worth logging, not even the author
+    let msg = match data.msg_cache.remove(guild_id, message_id) {
+        Some(msg) => msg,
+        None => return Ok(()),
+    };
+    let log = match log_target(data, guild_id, channel_id, &[msg.author_id.get()]).await? {
+        Some(log) => log,
+        None => return Ok(()),
+    };
+
+    let mut embed = CreateEmbed::default()
+        .title("Message deleted")
+        .description(format!(
+            "By <@{}> in <#{}>\n\n{}",
+            msg.author_id,
+            channel_id,
+            truncate(&msg.content, 3800)
+        ))
+        .footer(CreateEmbedFooter::new(format!("Message {}", message_id)))
+        .timestamp(Timestamp::now());
+    if !msg.attachments.is_empty() {
+        embed = embed.field(
+            "Attachments",
+            truncate(&msg.attachments.join("\n"), 1024),
+            false,
+        );
+    }
+    post(ctx, log, CreateMessage::new().embed(embed)).await;
+    Ok(())
+}
+
+pub async fn on_delete_bulk(
+    ctx: &serenity::Context,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    message_ids: &[MessageId],
+    data: &Data,
+) -> Result<(), AppError> {
+    let guild_id = match guild_id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    let mut messages: Vec<_> = message_ids
+        .iter()
+        .filter_map(|id| data.msg_cache.remove(guild_id, *id))
+        .collect();
+    let log = match log_target(data, guild_id, channel_id, &[]).await? {
+        Some(log) => log,
+        None => return Ok(()),
+    };
+    // Ignored users stay out of the transcript, like their single deletes stay out of the log
+    let guild = guild_id.get() as i64;
+    let authors: Vec<_> = messages.iter().map(|m| m.author_id.get() as i64).collect();
+    let ignored = data
+        .db
+        .run(move |conn| Ignored::among(conn, guild, &authors))
+        .await?;
+    messages.retain(|m| !ignored.contains(&(m.author_id.get() as i64)));
+
+    let mut transcript = String::new();
+    for msg in &messages {
+        transcript += &format!(
+            "[{}] {} ({}): {}\n",
+            msg.id.created_at(),
+            msg.author_name,
+            msg.author_id,
+            msg.content
+        );
+        for url in &msg.attachments {
+            transcript += &format!("    {}\n", url);
+        }
+    }
+
+    let embed = CreateEmbed::default()
+        .title("Messages purged")
+        .description(format!(
+            "{} messages were deleted in <#{}>, {} of them are attached",
+            message_ids.len(),
+            channel_id,
+            messages.len()
+        ))
+        .timestamp(Timestamp::now());
+    let mut msg = CreateMessage::new().embed(embed);
+    if !messages.is_empty() {
+        msg = msg.add_file(CreateAttachment::bytes(
+            transcript.into_bytes(),
+            "deleted.txt",
+        ));
+    }
+    post(ctx, log, msg).await;
+    Ok(())
+}
+
+/// The log channel, unless logging is disabled or the channel or one of the users is ignored
+async fn log_target(
+    data: &Data,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    users: &[u64],
+) -> Result<Option<ChannelId>, AppError> {
+    let guild_id = guild_id.get() as i64;
+    let mut targets = vec![channel_id.get() as i64];
+    targets.extend(users.iter().map(|u| *u as i64));
+    data.db
+        .run(move |conn| {
+            let log = match log_channel(conn, guild_id)? {
+                Some(log) if log != channel_id.get() as i64 => ChannelId::new(log as u64),
+                _ => return Ok(None),
+            };
+            if Ignored::any(conn, guild_id, &targets)? {
+                return Ok(None);
+            }
+            Ok(Some(log))
+        })
+        .await
+}
+
+async fn post(ctx: &serenity::Context, channel: ChannelId, msg: CreateMessage) {
+    if let Err(e) = channel.send_message(ctx, msg).await {
+        error!("Could not post to message log {}: {:?}", channel, e);
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.is_empty() {
+        "*Empty*".to_owned()
+    } else if text.chars().count() > max {
+        text.chars().take(max - 1).collect::<String>() + "…"
+    } else {
+        text.to_owned()
+    }
+}
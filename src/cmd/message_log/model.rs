@@ -0,0 +1,106 @@
+use crate::db::schema;
+use crate::Conn;
+
+use diesel::result::Error;
+use diesel::{insert_into, prelude::*};
+use mlc::message_log_config;
+use mli::message_log_ignore;
+use schema::message_log_config::dsl as mlc;
+use schema::message_log_ignore::dsl as mli;
+use strum_macros::{Display, EnumString};
+
+#[derive(EnumString, Display, Debug, PartialEq, Clone, Copy)]
+pub enum IgnoreKind {
+    Channel,
+    User,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::message_log_ignore)]
+pub struct Ignored {
+    pub id: i32,
+    pub guild_id: i64,
+    pub target_id: i64,
+    pub kind: String,
+}
+
+impl Ignored {
+    pub fn list(conn: &mut Conn, guild_id: i64) -> Result<Vec<Ignored>, Error> {
+        message_log_ignore
+            .filter(mli::guild_id.eq(guild_id))
+            .order(mli::kind)
+            .load(conn)
+    }
+
+    /// Whether any of the given channels or users is ignored
+    pub fn any(conn: &mut Conn, guild_id: i64, target_ids: &[i64]) -> Result<bool, Error> {
+        diesel::select(diesel::dsl::exists(
+            message_log_ignore
+                .filter(mli::guild_id.eq(guild_id))
+                .filter(mli::target_id.eq_any(target_ids)),
+        ))
+        .get_result(conn)
+    }
+
+    /// Which of the given channels or users are ignored
+    pub fn among(conn: &mut Conn, guild_id: i64, target_ids: &[i64]) -> Result<Vec<i64>, Error> {
+        message_log_ignore
+            .filter(mli::guild_id.eq(guild_id))
+            .filter(mli::target_id.eq_any(target_ids))
+            .select(mli::target_id)
+            .load(conn)
+    }
+
+    /// Ignores the target, or stops ignoring it if it already was.
+    /// Returns whether the target is ignored now.
+    pub fn toggle(
+        conn: &mut Conn,
+        guild_id: i64,
+        target_id: i64,
+        kind: IgnoreKind,
+    ) -> Result<bool, Error> {
+        conn.transaction(|conn| {
+            let deleted = diesel::delete(
+                message_log_ignore
+                    .filter(mli::guild_id.eq(guild_id))
+                    .filter(mli::target_id.eq(target_id)),
+            )
+            .execute(conn)?;
+            if deleted > 0 {
+                return Ok(false);
+            }
+            insert_into(message_log_ignore)
+                .values((
+                    mli::guild_id.eq(guild_id),
+                    mli::target_id.eq(target_id),
+                    mli::kind.eq(kind.to_string()),
+                ))
+                .execute(conn)?;
+            Ok(true)
+        })
+    }
+}
+
+pub fn log_channel(conn: &mut Conn, guild_id: i64) -> Result<Option<i64>, Error> {
+    message_log_config
+        .select(mlc::channel_id)
+        .find(guild_id)
+        .first(conn)
+        .optional()
+}
+
+pub fn set_log_channel(
+    conn: &mut Conn,
+    guild_id: i64,
+    channel_id: Option<i64>,
+) -> Result<usize, Error> {
+    match channel_id {
+        Some(channel_id) => insert_into(message_log_config)
+            .values((mlc::guild_id.eq(guild_id), mlc::channel_id.eq(channel_id)))
+            .on_conflict(mlc::guild_id)
+            .do_update()
+            .set(mlc::channel_id.eq(channel_id))
+            .execute(conn),
+        None => diesel::delete(message_log_config.find(guild_id)).execute(conn),
+    }
+}
@@ -0,0 +1,514 @@
+pub mod model;
+
+use crate::cmd::module_settings::Modules;
+use crate::util::{from_now, is_not_found, outranks, parse_duration, relative_time};
+use crate::{AppError, Context, Db};
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::{builder::*, model::prelude::*};
+use std::time::Duration;
+
+/// Discord doesn't allow timeouts longer than 28 days
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(28 * 24 * 60 * 60);
+
+/// Warn a member
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn warn(
+    ctx: Context<'_>,
+    #[description = "Member to warn"] user: User,
+    #[rest]
+    #[description = "Reason for the warning"]
+    reason: String,
+) -> Result<(), AppError> {
+    if !check_hierarchy(ctx, &user).await? {
+        return Ok(());
+    }
+    notify(&ctx, &user, ModAction::Warn, Some(&reason)).await;
+    record_and_reply(ctx, ModAction::Warn, &user, Some(reason), None).await
+}
+
+/// Time out a member
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "MODERATE_MEMBERS",
+    required_bot_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn timeout(
+    ctx: Context<'_>,
+    #[description = "Member to time out"] user: User,
+    #[description = "How long, e.g. '10m' or '1d', at most 28 days"] duration: String,
+    #[description = "Reason for the timeout"] reason: Option<String>,
+) -> Result<(), AppError> {
+    let duration = match parse_duration(&duration) {
+        Ok(d) if d <= MAX_TIMEOUT => d,
+        Ok(_) => {
+            ctx.say("Timeouts can last at most 28 days").await?;
+            return Ok(());
+        }
+        Err(_) => {
+            ctx.say(format!("'{}' is not a duration I understand", &duration))
+                .await?;
+            return Ok(());
+        }
+    };
+    let until = match from_now(duration) {
+        Some(time) => time,
+        None => {
+            ctx.say("That is too far in the future").await?;
+            return Ok(());
+        }
+    };
+    if !check_hierarchy(ctx, &user).await? {
+        return Ok(());
+    }
+
+    notify(&ctx, &user, ModAction::Timeout, reason.as_deref()).await;
+    ctx.guild_id()
+        .unwrap()
+        .edit_member(
+            ctx,
+            user.id,
+            EditMember::new()
+                .disable_communication_until(until.to_rfc3339())
+                .audit_log_reason(reason.as_deref().unwrap_or("No reason given")),
+        )
+        .await?;
+    record_and_reply(
+        ctx,
+        ModAction::Timeout,
+        &user,
+        reason,
+        Some((duration, until)),
+    )
+    .await
+}
+
+/// Kick a member
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "KICK_MEMBERS",
+    required_bot_permissions = "KICK_MEMBERS"
+)]
+pub async fn kick(
+    ctx: Context<'_>,
+    #[description = "Member to kick"] user: User,
+    #[description = "Reason for the kick"] reason: Option<String>,
+) -> Result<(), AppError> {
+    if !check_hierarchy(ctx, &user).await? {
+        return Ok(());
+    }
+    notify(&ctx, &user, ModAction::Kick, reason.as_deref()).await;
+    ctx.guild_id()
+        .unwrap()
+        .kick_with_reason(ctx, user.id, reason.as_deref().unwrap_or("No reason given"))
+        .await?;
+    record_and_reply(ctx, ModAction::Kick, &user, reason, None).await
+}
+
+/// Ban a user, optionally only for a while
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "BAN_MEMBERS",
+    required_bot_permissions = "BAN_MEMBERS"
+)]
+pub async fn ban(
+    ctx: Context<'_>,
+    #[description = "User to ban"] user: User,
+    #[description = "Lift the ban after this long, e.g. '7d'"] duration: Option<String>,
+    #[min = 0]
+    #[max = 7]
+    #[description = "Delete their messages of the last days"]
+    delete_days: Option<u8>,
+    #[description = "Reason for the ban"] reason: Option<String>,
+) -> Result<(), AppError> {
+    let duration = match duration.as_deref().map(parse_duration) {
+        Some(Ok(d)) => match from_now(d) {
+            Some(time) => Some((d, time)),
+            None => {
+                ctx.say("That is too far in the future").await?;
+                return Ok(());
+            }
+        },
+        Some(Err(_)) => {
+            ctx.say(format!(
+                "'{}' is not a duration I understand",
+                duration.unwrap()
+            ))
+            .await?;
+            return Ok(());
+        }
+        None => None,
+    };
+    if !check_hierarchy(ctx, &user).await? {
+        return Ok(());
+    }
+
+    notify(&ctx, &user, ModAction::Ban, reason.as_deref()).await;
+    ctx.guild_id()
+        .unwrap()
+        .ban_with_reason(
+            ctx,
+            user.id,
+            delete_days.unwrap_or(0),
+            reason.as_deref().unwrap_or("No reason given"),
+        )
+        .await?;
+    record_and_reply(ctx, ModAction::Ban, &user, reason, duration).await
+}
+
+/// Lift a ban
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "BAN_MEMBERS",
+    required_bot_permissions = "BAN_MEMBERS"
+)]
+pub async fn unban(
+    ctx: Context<'_>,
+    #[description = "User to unban"] user: User,
+    #[description = "Reason for the unban"] reason: Option<String>,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap();
+    guild_id.unban(ctx, user.id).await?;
+    let (guild, user_id) = (guild_id.get() as i64, user.id.get() as i64);
+    ctx.data()
+        .db
+        .run(move |conn| ModCase::resolve_for(conn, guild, user_id, ModAction::Ban))
+        .await?;
+    record_and_reply(ctx, ModAction::Unban, &user, reason, None).await
+}
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "MODERATE_MEMBERS",
+    subcommands("view", "edit", "list")
+)]
+pub async fn case(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Show a moderation case
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn view(
+    ctx: Context<'_>,
+    #[description = "Case number"] number: i32,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let case = ctx
+        .data()
+        .db
+        .run(move |conn| ModCase::find(conn, guild_id, number))
+        .await?;
+    match case {
+        Some(case) => {
+            ctx.send(poise::CreateReply::default().embed(case_embed(&case)))
+                .await?
+        }
+        None => ctx.say(format!("Could not find case #{}", number)).await?,
+    };
+    Ok(())
+}
+
+/// Change the reason of a moderation case
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn edit(
+    ctx: Context<'_>,
+    #[description = "Case number"] number: i32,
+    #[rest]
+    #[description = "New reason"]
+    reason: String,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let updated = ctx
+        .data()
+        .db
+        .run(move |conn| match ModCase::find(conn, guild_id, number)? {
+            Some(case) => {
+                let case = case.set_reason(conn, &reason)?;
+                Ok(Some((case, log_channel(conn, guild_id)?)))
+            }
+            None => Ok(None),
+        })
+        .await?;
+    let (case, log) = match updated {
+        Some(updated) => updated,
+        None => {
+            ctx.say(format!("Could not find case #{}", number)).await?;
+            return Ok(());
+        }
+    };
+
+    if let (Some(channel), Some(message)) = (log, case.log_message_id) {
+        let res = ChannelId::new(channel as u64)
+            .edit_message(
+                ctx,
+                MessageId::new(message as u64),
+                EditMessage::new().embed(case_embed(&case)),
+            )
+            .await;
+        if let Err(e) = res {
+            error!("Could not update mod log for case {}: {:?}", case.id, e);
+        }
+    }
+    ctx.say(format!("Updated case #{}", number)).await?;
+    Ok(())
+}
+
+/// List recent moderation cases
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn list(
+    ctx: Context<'_>,
+    #[description = "Only show cases of this user"] user: Option<User>,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let user_id = user.map(|u| u.id.get() as i64);
+    let cases = ctx
+        .data()
+        .db
+        .run(move |conn| ModCase::list(conn, guild_id, user_id, 20))
+        .await?;
+
+    if cases.is_empty() {
+        ctx.say("No cases found.").await?;
+        return Ok(());
+    }
+
+    let lines: Vec<_> = cases
+        .iter()
+        .map(|c| {
+            format!(
+                "**#{}** {} <@{}> – {}",
+                c.case_number,
+                c.action,
+                c.user_id,
+                c.reason.as_deref().unwrap_or("No reason given")
+            )
+        })
+        .collect();
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("Moderation cases")
+                .description(lines.join("\n")),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Set the channel moderation cases are posted to
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn modlog(
+    ctx: Context<'_>,
+    #[description = "Channel for the mod log, empty to disable"] channel: Option<GuildChannel>,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let log = channel.as_ref().map(|c| c.id.get() as i64);
+    ctx.data()
+        .db
+        .run(move |conn| set_log_channel(conn, guild_id, log))
+        .await?;
+    let msg = match channel {
+        Some(c) => format!("Moderation cases will be posted in <#{}>", c.id),
+        None => "Disabled the moderation log".to_owned(),
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Lifts temporary bans and timeouts whose time is up.
+/// A case is only resolved once Discord lifted it, so failures are retried on the next run.
+pub async fn lift_expired(
+    ctx: &serenity::Context,
+    db: &Db,
+    modules: &Modules,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    for case in db.run(move |conn| ModCase::expired(conn, now)).await? {
+        let guild_id = GuildId::new(case.guild_id as u64);
+        if !modules.enabled(Some(guild_id), "moderation").await? {
+            continue;
+        }
+        info!("Lifting case {}", case.id);
+        let user_id = UserId::new(case.user_id as u64);
+
+        let res = match case.action() {
+            Some(ModAction::Ban) => guild_id.unban(ctx, user_id).await,
+            // Discord ends timeouts by itself, this just makes sure they are gone
+            Some(ModAction::Timeout) => guild_id
+                .edit_member(ctx, user_id, EditMember::new().enable_communication())
+                .await
+                .map(|_| ()),
+            _ => Ok(()),
+        };
+        match res {
+            Ok(()) => {}
+            // The ban was already lifted or the member left
+            Err(e) if is_not_found(&e) => {}
+            Err(e) => {
+                error!("Could not lift case {}: {:?}", case.id, e);
+                continue;
+            }
+        }
+
+        let case = db.run(move |conn| case.resolve(conn).map(|_| case)).await?;
+        if case.action() == Some(ModAction::Ban) {
+            let new = NewModCase {
+                guild_id: case.guild_id,
+                action: ModAction::Unban.to_string(),
+                user_id: case.user_id,
+                moderator_id: ctx.cache.current_user().id.get() as i64,
+                reason: Some(format!("Ban from case #{} expired", case.case_number)),
+                duration: None,
+                expires_at: None,
+            };
+            record(ctx, db, new).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Makes sure both the author and the bot rank above `user`, replies and returns false if not
+async fn check_hierarchy(ctx: Context<'_>, user: &User) -> Result<bool, AppError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let bot_id = ctx.cache().current_user().id;
+    let msg = if !outranks(ctx, guild_id, ctx.author().id, user.id).await? {
+        "You can't moderate someone whose highest role is not below yours"
+    } else if !outranks(ctx, guild_id, bot_id, user.id).await? {
+        "I can't moderate someone whose highest role is not below mine"
+    } else {
+        return Ok(true);
+    };
+    ctx.say(msg).await?;
+    Ok(false)
+}
+
+async fn record_and_reply(
+    ctx: Context<'_>,
+    action: ModAction,
+    user: &User,
+    reason: Option<String>,
+    duration: Option<(Duration, DateTime<Utc>)>,
+) -> Result<(), AppError> {
+    let new = NewModCase {
+        guild_id: ctx.guild_id().unwrap().get() as i64,
+        action: action.to_string(),
+        user_id: user.id.get() as i64,
+        moderator_id: ctx.author().id.get() as i64,
+        reason,
+        duration: duration.map(|(d, _)| d.as_secs() as i64),
+        expires_at: duration.map(|(_, expires_at)| expires_at),
+    };
+    let case = record(ctx.serenity_context(), &ctx.data().db, new).await?;
+    ctx.say(format!(
+        "{} {} (case #{})",
+        action.past_tense(),
+        user.name,
+        case.case_number
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Stores a case and posts it to the guild's mod log, if one is configured
+pub async fn record(
+    ctx: &serenity::Context,
+    db: &Db,
+    new: NewModCase,
+) -> Result<ModCase, AppError> {
+    let (case, log) = db
+        .run(move |conn| {
+            let case = new.insert(conn)?;
+            let log = log_channel(conn, case.guild_id)?;
+            Ok((case, log))
+        })
+        .await?;
+    let channel = match log {
+        Some(channel) => ChannelId::new(channel as u64),
+        None => return Ok(case),
+    };
+
+    let res = channel
+        .send_message(ctx, CreateMessage::new().embed(case_embed(&case)))
+        .await;
+    match res {
+        Ok(msg) => {
+            let message_id = msg.id.get() as i64;
+            db.run(move |conn| case.set_log_message(conn, message_id).map(|_| case))
+                .await
+        }
+        Err(e) => {
+            error!("Could not post case {} to mod log: {:?}", case.id, e);
+            Ok(case)
+        }
+    }
+}
+
+/// Lets the user know about the action, if they accept DMs
+async fn notify(ctx: &Context<'_>, user: &User, action: ModAction, reason: Option<&str>) {
+    let guild = ctx
+        .guild()
+        .map(|g| g.name.clone())
+        .unwrap_or("the server".to_owned());
+    let msg = format!(
+        "You were {} in **{}**: {}",
+        action.past_tense().to_lowercase(),
+        guild,
+        reason.unwrap_or("No reason given")
+    );
+    if let Err(e) = user
+        .direct_message(ctx, CreateMessage::new().content(msg))
+        .await
+    {
+        info!("Could not notify {} about {}: {:?}", user.id, action, e);
+    }
+}
+
+fn case_embed(case: &ModCase) -> CreateEmbed {
+    let mut embed = CreateEmbed::default()
+        .title(format!("Case #{} | {}", case.case_number, case.action))
+        .field(
+            "User",
+            format!("<@{}> ({})", case.user_id, case.user_id),
+            true,
+        )
+        .field("Moderator", format!("<@{}>", case.moderator_id), true)
+        .field(
+            "Reason",
+            case.reason.as_deref().unwrap_or("No reason given"),
+            false,
+        );
+    if let Some(duration) = case.duration {
+        embed = embed.field(
+            "Duration",
+            humantime::format_duration(Duration::from_secs(duration as u64)).to_string(),
+            true,
+        );
+    }
+    if let Some(expires_at) = case.expires_at {
+        let label = if case.resolved { "Expired" } else { "Expires" };
+        embed = embed.field(label, relative_time(expires_at), true);
+    }
+    embed
+}
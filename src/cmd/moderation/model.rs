@@ -0,0 +1,191 @@
+use crate::db::schema;
+use crate::Conn;
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::max;
+use diesel::result::{DatabaseErrorKind, Error};
+use diesel::{insert_into, prelude::*};
+use mc::mod_cases;
+use mcfg::mod_config;
+use schema::mod_cases::dsl as mc;
+use schema::mod_config::dsl as mcfg;
+use strum_macros::{Display, EnumString};
+
+#[derive(EnumString, Display, Debug, PartialEq, Clone, Copy)]
+pub enum ModAction {
+    Warn,
+    Timeout,
+    Kick,
+    Ban,
+    Unban,
+}
+
+impl ModAction {
+    pub fn past_tense(&self) -> &'static str {
+        match self {
+            ModAction::Warn => "Warned",
+            ModAction::Timeout => "Timed out",
+            ModAction::Kick => "Kicked",
+            ModAction::Ban => "Banned",
+            ModAction::Unban => "Unbanned",
+        }
+    }
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::mod_cases)]
+pub struct ModCase {
+    pub id: i32,
+    pub guild_id: i64,
+    pub case_number: i32,
+    pub action: String,
+    pub user_id: i64,
+    pub moderator_id: i64,
+    pub reason: Option<String>,
+    /// Length of a timeout or temporary ban in seconds
+    pub duration: Option<i64>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub resolved: bool,
+    pub log_message_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::mod_cases)]
+pub struct NewModCase {
+    pub guild_id: i64,
+    pub action: String,
+    pub user_id: i64,
+    pub moderator_id: i64,
+    pub reason: Option<String>,
+    pub duration: Option<i64>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ModCase {
+    pub fn find(conn: &mut Conn, guild_id: i64, number: i32) -> Result<Option<ModCase>, Error> {
+        mod_cases
+            .filter(mc::guild_id.eq(guild_id))
+            .filter(mc::case_number.eq(number))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn list(
+        conn: &mut Conn,
+        guild_id: i64,
+        user_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<ModCase>, Error> {
+        let mut query = mod_cases.filter(mc::guild_id.eq(guild_id)).into_boxed();
+        if let Some(u) = user_id {
+            query = query.filter(mc::user_id.eq(u));
+        }
+        query.order(mc::case_number.desc()).limit(limit).load(conn)
+    }
+
+    /// Unresolved timeouts and temporary bans whose time is up
+    pub fn expired(conn: &mut Conn, now: DateTime<Utc>) -> Result<Vec<ModCase>, Error> {
+        mod_cases
+            .filter(mc::resolved.eq(false))
+            .filter(mc::expires_at.le(now))
+            .load(conn)
+    }
+
+    pub fn action(&self) -> Option<ModAction> {
+        self.action.parse().ok()
+    }
+
+    pub fn set_reason(&self, conn: &mut Conn, reason: &str) -> Result<ModCase, Error> {
+        diesel::update(self)
+            .set(mc::reason.eq(reason))
+            .get_result(conn)
+    }
+
+    pub fn set_log_message(&self, conn: &mut Conn, message_id: i64) -> Result<usize, Error> {
+        diesel::update(self)
+            .set(mc::log_message_id.eq(message_id))
+            .execute(conn)
+    }
+
+    pub fn resolve(&self, conn: &mut Conn) -> Result<usize, Error> {
+        diesel::update(self)
+            .set(mc::resolved.eq(true))
+            .execute(conn)
+    }
+
+    /// Resolves all pending temporary punishments of a user, e.g. after a manual unban
+    pub fn resolve_for(
+        conn: &mut Conn,
+        guild_id: i64,
+        user_id: i64,
+        action: ModAction,
+    ) -> Result<usize, Error> {
+        diesel::update(
+            mod_cases
+                .filter(mc::guild_id.eq(guild_id))
+                .filter(mc::user_id.eq(user_id))
+                .filter(mc::action.eq(action.to_string()))
+                .filter(mc::resolved.eq(false)),
+        )
+        .set(mc::resolved.eq(true))
+        .execute(conn)
+    }
+}
+
+impl NewModCase {
+    /// Inserts the case with the next free case number of the guild.
+    /// A new ban or timeout replaces the user's earlier ones, so those are resolved.
+    pub fn insert(&self, conn: &mut Conn) -> Result<ModCase, Error> {
+        let action = self.action.parse::<ModAction>().ok();
+        let mut attempts = 0;
+        loop {
+            let res = conn.transaction(|conn| {
+                if let Some(action @ (ModAction::Ban | ModAction::Timeout)) = action {
+                    ModCase::resolve_for(conn, self.guild_id, self.user_id, action)?;
+                }
+                let last: Option<i32> = mod_cases
+                    .select(max(mc::case_number))
+                    .filter(mc::guild_id.eq(self.guild_id))
+                    .first(conn)?;
+                insert_into(mod_cases)
+                    .values((self, mc::case_number.eq(last.unwrap_or(0) + 1)))
+                    .get_result(conn)
+            });
+            match res {
+                // A concurrent action took the number, try the next one
+                Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _))
+                    if attempts < 5 =>
+                {
+                    attempts += 1
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+pub fn log_channel(conn: &mut Conn, guild_id: i64) -> Result<Option<i64>, Error> {
+    let res = mod_config
+        .select(mcfg::log_channel_id)
+        .find(guild_id)
+        .first(conn)
+        .optional()?;
+    Ok(res.flatten())
+}
+
+pub fn set_log_channel(
+    conn: &mut Conn,
+    guild_id: i64,
+    channel_id: Option<i64>,
+) -> Result<usize, Error> {
+    insert_into(mod_config)
+        .values((
+            mcfg::guild_id.eq(guild_id),
+            mcfg::log_channel_id.eq(channel_id),
+        ))
+        .on_conflict(mcfg::guild_id)
+        .do_update()
+        .set(mcfg::log_channel_id.eq(channel_id))
+        .execute(conn)
+}
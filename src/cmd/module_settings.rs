@@ -0,0 +1,173 @@
+pub mod model;
+#[cfg(test)]
+mod tests;
+
+use crate::cmd::registration;
+use crate::{AppError, Context, Db};
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::GuildId;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// Which modules are on, in the config and in each guild. Clones share the cache of what the
+/// guilds turned off, so the background jobs see changes made with `/modules` too.
+#[derive(Clone)]
+pub struct Modules {
+    db: Db,
+    /// Names of the modules enabled in the config
+    config: Arc<HashSet<&'static str>>,
+    /// The modules each guild turned off
+    disabled: Arc<Mutex<HashMap<GuildId, Arc<HashSet<String>>>>>,
+}
+
+impl Modules {
+    pub fn new(db: Db, config: HashSet<&'static str>) -> Modules {
+        Modules {
+            db,
+            config: Arc::new(config),
+            disabled: Default::default(),
+        }
+    }
+
+    /// Whether `module` is on, both in the config and in the guild if there is one
+    pub async fn enabled(&self, guild_id: Option<GuildId>, module: &str) -> Result<bool, AppError> {
+        if !self.config.contains(module) {
+            return Ok(false);
+        }
+        Ok(match guild_id {
+            Some(guild_id) => !self.disabled(guild_id).await?.contains(module),
+            None => true,
+        })
+    }
+
+    pub async fn disabled(&self, guild_id: GuildId) -> Result<Arc<HashSet<String>>, AppError> {
+        if let Some(disabled) = self.disabled.lock().unwrap().get(&guild_id) {
+            return Ok(disabled.clone());
+        }
+        let guild = guild_id.get() as i64;
+        let list = self
+            .db
+            .run(move |conn| DisabledModule::list(conn, guild))
+            .await?;
+        let disabled = Arc::new(list.into_iter().collect::<HashSet<_>>());
+        self.disabled
+            .lock()
+            .unwrap()
+            .insert(guild_id, disabled.clone());
+        Ok(disabled)
+    }
+
+    fn invalidate(&self, guild_id: GuildId) {
+        self.disabled.lock().unwrap().remove(&guild_id);
+    }
+
+    /// The modules enabled in the config, sorted by name
+    fn sorted(&self) -> Vec<&'static str> {
+        let mut modules: Vec<_> = self.config.iter().copied().collect();
+        modules.sort_unstable();
+        modules
+    }
+}
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("list", "enable", "disable")
+)]
+pub async fn modules(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Show which modules are on in this server
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn list(ctx: Context<'_>) -> Result<(), AppError> {
+    let data = ctx.data();
+    let disabled = data.modules.disabled(ctx.guild_id().unwrap()).await?;
+
+    let mut lines = Vec::new();
+    for module in data.modules.sorted() {
+        let mut commands: Vec<_> = data
+            .command_modules
+            .iter()
+            .filter(|(_, m)| **m == module)
+            .map(|(c, _)| c.as_str())
+            .collect();
+        commands.sort_unstable();
+        let state = if disabled.contains(module) {
+            "❌"
+        } else {
+            "✅"
+        };
+        lines.push(format!("{} **{}**: {}", state, module, commands.join(", ")));
+    }
+    ctx.say(lines.join("\n")).await?;
+    Ok(())
+}
+
+/// Turn a module back on in this server
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn enable(
+    ctx: Context<'_>,
+    #[autocomplete = "comp_module"]
+    #[description = "Module to turn on"]
+    module: String,
+) -> Result<(), AppError> {
+    set_enabled(ctx, module, true).await
+}
+
+/// Turn a module off in this server, its commands disappear and it ignores what happens here
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn disable(
+    ctx: Context<'_>,
+    #[autocomplete = "comp_module"]
+    #[description = "Module to turn off"]
+    module: String,
+) -> Result<(), AppError> {
+    set_enabled(ctx, module, false).await
+}
+
+async fn set_enabled(ctx: Context<'_>, module: String, enabled: bool) -> Result<(), AppError> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap();
+    if !data.modules.config.contains(module.as_str()) {
+        ctx.say(format!("There is no module called '{}'", module))
+            .await?;
+        return Ok(());
+    }
+
+    let row = DisabledModule {
+        guild_id: guild_id.get() as i64,
+        module: module.clone(),
+    };
+    let changed = if enabled {
+        data.db.run(move |conn| row.delete(conn)).await?
+    } else {
+        data.db.run(move |conn| row.insert(conn)).await?
+    };
+    let state = if enabled { "on" } else { "off" };
+    if changed == 0 {
+        ctx.say(format!("The {} module is already {}", module, state))
+            .await?;
+        return Ok(());
+    }
+    data.modules.invalidate(guild_id);
+
+    ctx.defer_ephemeral().await?;
+    let commands = &ctx.framework().options().commands;
+    registration::register_in_guild(ctx, commands, data, guild_id, false).await?;
+    ctx.say(format!("Turned the {} module {}", module, state))
+        .await?;
+    Ok(())
+}
+
+async fn comp_module(ctx: Context<'_>, partial: &str) -> Vec<&'static str> {
+    ctx.data()
+        .modules
+        .sorted()
+        .into_iter()
+        .filter(|m| m.starts_with(partial))
+        .collect()
+}
@@ -0,0 +1,33 @@
+use crate::db::schema::disabled_module;
+use crate::db::schema::disabled_module::dsl::*;
+use crate::Conn;
+use diesel::prelude::*;
+use diesel::result::Error;
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = disabled_module)]
+pub struct DisabledModule {
+    pub guild_id: i64,
+    pub module: String,
+}
+
+impl DisabledModule {
+    pub fn list(conn: &mut Conn, guild: i64) -> Result<Vec<String>, Error> {
+        disabled_module
+            .filter(guild_id.eq(guild))
+            .select(module)
+            .load(conn)
+    }
+
+    /// Returns 0 if the module was already disabled
+    pub fn insert(&self, conn: &mut Conn) -> Result<usize, Error> {
+        self.insert_into(disabled_module)
+            .on_conflict_do_nothing()
+            .execute(conn)
+    }
+
+    /// Returns 0 if the module wasn't disabled
+    pub fn delete(&self, conn: &mut Conn) -> Result<usize, Error> {
+        diesel::delete(disabled_module.find((self.guild_id, &self.module))).execute(conn)
+    }
+}
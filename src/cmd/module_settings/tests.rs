@@ -0,0 +1,37 @@
+use super::*;
+use crate::testing;
+
+const GUILD: u64 = 1;
+
+fn row(module: &str) -> DisabledModule {
+    DisabledModule {
+        guild_id: GUILD as i64,
+        module: module.to_owned(),
+    }
+}
+
+#[tokio::test]
+async fn disabling_is_remembered_per_guild() {
+    let data = testing::data();
+    let guild = Some(GuildId::new(GUILD));
+    let other = Some(GuildId::new(GUILD + 1));
+    assert!(data.enabled(guild, "fav_msgs").await.unwrap());
+
+    let disabled = row("fav_msgs");
+    assert_eq!(data.db.run(move |c| disabled.insert(c)).await.unwrap(), 1);
+    let disabled = row("fav_msgs");
+    assert_eq!(data.db.run(move |c| disabled.insert(c)).await.unwrap(), 0);
+    // Still cached as enabled until invalidated
+    assert!(data.enabled(guild, "fav_msgs").await.unwrap());
+    data.modules.invalidate(GuildId::new(GUILD));
+
+    assert!(!data.enabled(guild, "fav_msgs").await.unwrap());
+    assert!(data.enabled(guild, "levels").await.unwrap());
+    assert!(data.enabled(other, "fav_msgs").await.unwrap());
+    assert!(data.enabled(None, "fav_msgs").await.unwrap());
+
+    let enabled = row("fav_msgs");
+    assert_eq!(data.db.run(move |c| enabled.delete(c)).await.unwrap(), 1);
+    data.modules.invalidate(GuildId::new(GUILD));
+    assert!(data.enabled(guild, "fav_msgs").await.unwrap());
+}
@@ -0,0 +1,408 @@
+mod model;
+
+use crate::cmd::module_settings::Modules;
+use crate::component::{Component, Route, TooLong};
+use crate::util::{from_now, is_not_found, parse_duration, relative_time};
+use crate::{AppError, Bot, ComponentAction, Context, Data, Db};
+use chrono::Utc;
+use log::{error, info};
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::{
+    builder::*, ButtonStyle, CacheHttp, ChannelId, ComponentInteraction, GuildId, MessageId,
+};
+
+/// Button voting for one option of a poll
+pub struct VoteInPoll {
+    pub poll_id: i32,
+    pub position: i32,
+}
+
+impl Component for VoteInPoll {
+    const ACTION: ComponentAction = ComponentAction::VoteInPoll;
+    const MODULE: &'static str = "polls";
+
+    fn encode(&self) -> Vec<String> {
+        vec![self.poll_id.to_string(), self.position.to_string()]
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        match fields {
+            [poll_id, position] => Some(VoteInPoll {
+                poll_id: poll_id.parse().ok()?,
+                position: position.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ComponentInteraction,
+        data: &'a Data,
+    ) -> poise::BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(vote(ctx, event, data, self))
+    }
+}
+
+inventory::submit!(Route::of::<VoteInPoll>());
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    subcommands("start", "close")
+)]
+pub async fn poll(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Start a poll with buttons to vote on
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn start(
+    ctx: Context<'_>,
+    #[description = "What to ask"] question: String,
+    #[description = "Up to 25 options, separated by ';'"] options: String,
+    #[description = "Allow voting for more than one option"] multiple: Option<bool>,
+    #[description = "Hide who voted for what"] anonymous: Option<bool>,
+    #[description = "Close the poll after this long, e.g. '2h 30m'"] closes_in: Option<String>,
+) -> Result<(), AppError> {
+    let labels: Vec<String> = options
+        .split(';')
+        .map(str::trim)
+        .filter(|o| !o.is_empty())
+        .map(str::to_owned)
+        .collect();
+    if labels.len() < 2 || labels.len() > 25 {
+        ctx.say("A poll needs between 2 and 25 options").await?;
+        return Ok(());
+    }
+
+    let closes_at = match closes_in.as_deref().map(parse_duration) {
+        Some(Ok(d)) => match from_now(d) {
+            Some(time) => Some(time),
+            None => {
+                ctx.say("That is too far in the future").await?;
+                return Ok(());
+            }
+        },
+        Some(Err(_)) => {
+            ctx.say(format!(
+                "'{}' is not a duration I understand",
+                closes_in.unwrap()
+            ))
+            .await?;
+            return Ok(());
+        }
+        None => None,
+    };
+
+    let new = NewPoll {
+        guild_id: ctx.guild_id().unwrap().get() as i64,
+        channel_id: ctx.channel_id().get() as i64,
+        author_id: ctx.author().id.get() as i64,
+        question,
+        multiple: multiple.unwrap_or(false),
+        anonymous: anonymous.unwrap_or(false),
+        closes_at,
+    };
+    let (poll, options) = ctx
+        .data()
+        .db
+        .run(move |conn| {
+            let poll = new.insert(conn, &labels)?;
+            let options = poll.options(conn)?;
+            Ok((poll, options))
+        })
+        .await?;
+
+    let msg = ctx
+        .channel_id()
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .embed(results(&poll, &options, &[]))
+                .components(vote_buttons(&poll, &options)?),
+        )
+        .await?;
+    let (poll_id, message_id) = (poll.id, msg.id.get() as i64);
+    ctx.data()
+        .db
+        .run(move |conn| poll.set_message(conn, message_id))
+        .await?;
+
+    ctx.say(format!("Poll #{} created.", poll_id)).await?;
+    Ok(())
+}
+
+/// Close a poll early, only its author and those who can manage messages can
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn close(
+    ctx: Context<'_>,
+    #[description = "Number of the poll"] number: i32,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let poll = ctx
+        .data()
+        .db
+        .run(move |conn| Poll::find(conn, number))
+        .await?;
+    let poll = match poll {
+        Some(poll) if poll.guild_id == guild_id && !poll.closed => poll,
+        Some(poll) if poll.guild_id == guild_id => {
+            ctx.say(format!("Poll #{} is already closed", number))
+                .await?;
+            return Ok(());
+        }
+        _ => {
+            ctx.say(format!("There is no poll #{}", number)).await?;
+            return Ok(());
+        }
+    };
+
+    let manager = ctx
+        .author_member()
+        .await
+        .and_then(|m| m.permissions)
+        .map_or(false, |p| p.manage_messages());
+    if poll.author_id != ctx.author().id.get() as i64 && !manager {
+        ctx.say("You can only close polls you started").await?;
+        return Ok(());
+    }
+    finish(ctx, &ctx.data().db, poll).await?;
+    ctx.say(format!("Closed poll #{}", number)).await?;
+    Ok(())
+}
+
+pub async fn vote(
+    ctx: &Bot,
+    event: &ComponentInteraction,
+    data: &Data,
+    button: VoteInPoll,
+) -> Result<(), AppError> {
+    let VoteInPoll { poll_id, position } = button;
+    let guild_id = event.guild_id.map(|g| g.get() as i64);
+    let found = data
+        .db
+        .run(move |conn| match Poll::find(conn, poll_id)? {
+            // Polls only take votes from the server they were started in
+            Some(poll) if !poll.closed && Some(poll.guild_id) == guild_id => {
+                let options = poll.options(conn)?;
+                Ok(Some((poll, options)))
+            }
+            _ => Ok(None),
+        })
+        .await?;
+    let (poll, options) = match found {
+        Some(found) => found,
+        None => {
+            event
+                .create_response(
+                    ctx,
+                    serenity::CreateInteractionResponse::Message(
+                        serenity::CreateInteractionResponseMessage::new()
+                            .ephemeral(true)
+                            .content("This poll is closed."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let label = match options.iter().find(|o| o.position == position) {
+        Some(option) => &option.label,
+        None => {
+            event
+                .create_response(
+                    ctx,
+                    serenity::CreateInteractionResponse::Message(
+                        serenity::CreateInteractionResponseMessage::new()
+                            .ephemeral(true)
+                            .content("This option is not part of the poll."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+    let user_id = event.user.id.get() as i64;
+    let (poll, added, votes) = data
+        .db
+        .run(move |conn| {
+            let added = poll.vote(conn, user_id, position)?;
+            let votes = poll.votes(conn)?;
+            Ok((poll, added, votes))
+        })
+        .await?;
+
+    event
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new()
+                    .embed(results(&poll, &options, &votes)),
+            ),
+        )
+        .await?;
+    let res = if added {
+        format!("You voted for '{}'.", label)
+    } else {
+        format!("Removed your vote for '{}'.", label)
+    };
+    event
+        .create_followup(
+            ctx,
+            CreateInteractionResponseFollowup::new()
+                .ephemeral(true)
+                .content(res),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Closes every poll whose close time has passed and posts the final results
+pub async fn close_due(
+    ctx: &serenity::Context,
+    db: &Db,
+    modules: &Modules,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    for poll in db.run(move |conn| Poll::due(conn, now)).await? {
+        let guild_id = GuildId::new(poll.guild_id as u64);
+        if !modules.enabled(Some(guild_id), "polls").await? {
+            continue;
+        }
+        info!("Closing poll {}", poll.id);
+        let id = poll.id;
+        if let Err(e) = finish(ctx, db, poll).await {
+            error!("Could not close poll {}: {:?}", id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Closes a poll and replaces its buttons with the final results.
+/// The poll is closed first, so it stays closed if its message is gone.
+async fn finish(ctx: impl CacheHttp, db: &Db, poll: Poll) -> Result<(), AppError> {
+    let (poll, options, votes) = db
+        .run(move |conn| {
+            let poll = poll.close(conn)?;
+            let options = poll.options(conn)?;
+            let votes = poll.votes(conn)?;
+            Ok((poll, options, votes))
+        })
+        .await?;
+
+    if let Some(message_id) = poll.message_id {
+        let res = ChannelId::new(poll.channel_id as u64)
+            .edit_message(
+                ctx,
+                MessageId::new(message_id as u64),
+                EditMessage::new()
+                    .embed(results(&poll, &options, &votes))
+                    .components(vec![]),
+            )
+            .await;
+        match res {
+            Ok(_) => {}
+            Err(e) if is_not_found(&e) => {}
+            Err(e) => error!("Could not finalize poll {}: {:?}", poll.id, e),
+        }
+    }
+    Ok(())
+}
+
+fn vote_buttons(poll: &Poll, options: &[PollOption]) -> Result<Vec<CreateActionRow>, TooLong> {
+    let buttons: Vec<_> = options
+        .iter()
+        .map(|o| {
+            let label: String = format!("{}. {}", o.position + 1, o.label)
+                .chars()
+                .take(80)
+                .collect();
+            let button = VoteInPoll {
+                poll_id: poll.id,
+                position: o.position,
+            };
+            Ok(CreateButton::new(button.custom_id()?)
+                .style(ButtonStyle::Secondary)
+                .label(label))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(buttons
+        .chunks(5)
+        .map(|c| CreateActionRow::Buttons(c.to_vec()))
+        .collect())
+}
+
+fn results(poll: &Poll, options: &[PollOption], votes: &[PollVote]) -> CreateEmbed {
+    let voters = votes
+        .iter()
+        .map(|v| v.user_id)
+        .collect::<std::collections::HashSet<_>>()
+        .len();
+    let max = options
+        .iter()
+        .map(|o| votes.iter().filter(|v| v.position == o.position).count())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines: Vec<String> = options
+        .iter()
+        .map(|o| {
+            let option_votes: Vec<_> = votes.iter().filter(|v| v.position == o.position).collect();
+            let share = if votes.is_empty() {
+                0
+            } else {
+                option_votes.len() * 100 / votes.len()
+            };
+            let bar = "🫘".repeat(share / 10);
+            let label = if poll.closed && max > 0 && option_votes.len() == max {
+                format!("**{}. {}** 🏆", o.position + 1, o.label)
+            } else {
+                format!("**{}.** {}", o.position + 1, o.label)
+            };
+            let mut line = format!("{}\n{} {} ({}%)", label, bar, option_votes.len(), share);
+            if !poll.anonymous && !option_votes.is_empty() {
+                let names: Vec<_> = option_votes
+                    .iter()
+                    .take(5)
+                    .map(|v| format!("<@{}>", v.user_id))
+                    .collect();
+                line += &format!("\n{}", names.join(" "));
+                if option_votes.len() > 5 {
+                    line += &format!(" and {} more", option_votes.len() - 5);
+                }
+            }
+            line
+        })
+        .collect();
+
+    let mut info = vec![format!("{} voter(s)", voters)];
+    if poll.multiple {
+        info.push("multiple choice".to_owned());
+    }
+    if poll.anonymous {
+        info.push("anonymous".to_owned());
+    }
+    match poll.closes_at {
+        _ if poll.closed => info.push("closed".to_owned()),
+        Some(at) => info.push(format!("closes {}", relative_time(at))),
+        None => {}
+    }
+    lines.push(info.join(" • "));
+
+    let title = if poll.closed {
+        format!("[Closed] {}", poll.question)
+    } else {
+        poll.question.to_owned()
+    };
+    // Embed descriptions are limited to 4096 characters
+    let description: String = lines.join("\n\n").chars().take(4096).collect();
+    CreateEmbed::default()
+        .title(title)
+        .description(description)
+        .footer(CreateEmbedFooter::new(format!("Poll #{}", poll.id)))
+}
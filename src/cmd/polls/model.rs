@@ -0,0 +1,157 @@
+use crate::db::schema;
+use crate::Conn;
+
+use chrono::{DateTime, Utc};
+use diesel::result::Error;
+use diesel::{insert_into, prelude::*};
+use p::poll;
+use po::poll_option;
+use pv::poll_vote;
+use schema::poll::dsl as p;
+use schema::poll_option::dsl as po;
+use schema::poll_vote::dsl as pv;
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::poll)]
+pub struct Poll {
+    pub id: i32,
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub message_id: Option<i64>,
+    pub author_id: i64,
+    pub question: String,
+    pub multiple: bool,
+    pub anonymous: bool,
+    pub closes_at: Option<DateTime<Utc>>,
+    pub closed: bool,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Associations, Debug, PartialEq)]
+#[diesel(belongs_to(Poll))]
+#[diesel(table_name = schema::poll_option)]
+pub struct PollOption {
+    pub id: i32,
+    pub poll_id: i32,
+    pub position: i32,
+    pub label: String,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Associations, Debug, PartialEq)]
+#[diesel(belongs_to(Poll))]
+#[diesel(table_name = schema::poll_vote)]
+pub struct PollVote {
+    pub id: i32,
+    pub poll_id: i32,
+    pub user_id: i64,
+    pub position: i32,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::poll)]
+pub struct NewPoll {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub author_id: i64,
+    pub question: String,
+    pub multiple: bool,
+    pub anonymous: bool,
+    pub closes_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::poll_option)]
+struct NewPollOption<'a> {
+    poll_id: i32,
+    position: i32,
+    label: &'a str,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::poll_vote)]
+struct NewPollVote {
+    poll_id: i32,
+    user_id: i64,
+    position: i32,
+}
+
+impl Poll {
+    pub fn find(conn: &mut Conn, id: i32) -> Result<Option<Poll>, Error> {
+        poll.find(id).first(conn).optional()
+    }
+
+    /// Open polls whose close time has passed
+    pub fn due(conn: &mut Conn, now: DateTime<Utc>) -> Result<Vec<Poll>, Error> {
+        poll.filter(p::closed.eq(false))
+            .filter(p::closes_at.le(now))
+            .load(conn)
+    }
+
+    pub fn options(&self, conn: &mut Conn) -> Result<Vec<PollOption>, Error> {
+        PollOption::belonging_to(self)
+            .order(po::position)
+            .load(conn)
+    }
+
+    pub fn votes(&self, conn: &mut Conn) -> Result<Vec<PollVote>, Error> {
+        PollVote::belonging_to(self).load(conn)
+    }
+
+    pub fn set_message(&self, conn: &mut Conn, message_id: i64) -> Result<usize, Error> {
+        diesel::update(self)
+            .set(p::message_id.eq(message_id))
+            .execute(conn)
+    }
+
+    pub fn close(&self, conn: &mut Conn) -> Result<Poll, Error> {
+        diesel::update(self)
+            .set(p::closed.eq(true))
+            .get_result(conn)
+    }
+
+    /// Toggles a user's vote for an option. Voting on a single choice poll replaces the previous vote.
+    /// Returns whether the vote was added.
+    pub fn vote(&self, conn: &mut Conn, user_id: i64, position: i32) -> Result<bool, Error> {
+        conn.transaction(|conn| {
+            let own_votes = poll_vote
+                .filter(pv::poll_id.eq(self.id))
+                .filter(pv::user_id.eq(user_id));
+            let removed = diesel::delete(own_votes.clone().filter(pv::position.eq(position)))
+                .execute(conn)?;
+            if removed > 0 {
+                return Ok(false);
+            }
+
+            if !self.multiple {
+                diesel::delete(own_votes).execute(conn)?;
+            }
+            insert_into(poll_vote)
+                .values(NewPollVote {
+                    poll_id: self.id,
+                    user_id,
+                    position,
+                })
+                .execute(conn)?;
+            Ok(true)
+        })
+    }
+}
+
+impl NewPoll {
+    pub fn insert(&self, conn: &mut Conn, labels: &[String]) -> Result<Poll, Error> {
+        conn.transaction(|conn| {
+            let created: Poll = self.insert_into(poll).get_result(conn)?;
+            let options: Vec<_> = labels
+                .iter()
+                .enumerate()
+                .map(|(i, label)| NewPollOption {
+                    poll_id: created.id,
+                    position: i as i32,
+                    label: label.as_str(),
+                })
+                .collect();
+
+            insert_into(poll_option).values(&options).execute(conn)?;
+            Ok(created)
+        })
+    }
+}
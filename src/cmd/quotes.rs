@@ -0,0 +1,258 @@
+mod model;
+
+use crate::{AppError, Context};
+use chrono::{TimeZone, Utc};
+use diesel::result::DatabaseErrorKind;
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::{builder::*, model::prelude::*};
+
+/// Save a message as one of the server's quotes
+#[poise::command(
+    context_menu_command = "Save as Quote",
+    guild_only = true,
+    ephemeral = true
+)]
+pub async fn save(ctx: Context<'_>, msg: Message) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let (guild, message) = (guild_id.get() as i64, msg.id.get() as i64);
+    let existing = ctx
+        .data()
+        .db
+        .run(move |conn| Quote::find_message(conn, guild, message))
+        .await?;
+    if let Some(quote) = existing {
+        ctx.say(format!("This message already is quote #{}", quote.number))
+            .await?;
+        return Ok(());
+    }
+    let image = msg.attachments.iter().find(|a| a.height.is_some());
+    if msg.content.is_empty() && image.is_none() {
+        ctx.say("There is nothing in this message to quote").await?;
+        return Ok(());
+    }
+
+    let author_name = msg
+        .author
+        .nick_in(&ctx, guild_id)
+        .await
+        .unwrap_or(msg.author.name.to_owned());
+    let new = NewQuote {
+        guild_id: guild_id.get() as i64,
+        channel_id: msg.channel_id.get() as i64,
+        message_id: msg.id.get() as i64,
+        author_id: msg.author.id.get() as i64,
+        author_name,
+        author_avatar: msg.author.avatar_url(),
+        content: msg.content.clone(),
+        image_url: image.map(|a| a.url.clone()),
+        saved_by: ctx.author().id.get() as i64,
+        created_at: Utc
+            .timestamp_opt(msg.timestamp.unix_timestamp(), 0)
+            .unwrap(),
+    };
+    let saved = ctx
+        .data()
+        .db
+        .run(move |conn| loop {
+            match new.insert(conn) {
+                Ok(quote) => return Ok(Ok(quote)),
+                // Saved by someone else meanwhile, or they took the number first
+                Err(diesel::result::Error::DatabaseError(
+                    DatabaseErrorKind::UniqueViolation,
+                    _,
+                )) => {
+                    if let Some(quote) = Quote::find_message(conn, guild, message)? {
+                        return Ok(Err(quote));
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        })
+        .await?;
+    let msg = match saved {
+        Ok(quote) => format!("Saved as quote #{}", quote.number),
+        Err(quote) => format!("This message already is quote #{}", quote.number),
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    subcommands("get", "random", "search", "by", "delete")
+)]
+pub async fn quote(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Post a quote by its number
+#[poise::command(slash_command)]
+pub async fn get(
+    ctx: Context<'_>,
+    #[description = "Number of the quote"] number: i32,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let quote = ctx
+        .data()
+        .db
+        .run(move |conn| Quote::find(conn, guild_id, number))
+        .await?;
+    post(ctx, quote, &format!("There is no quote #{}", number)).await
+}
+
+/// Post a random quote
+#[poise::command(slash_command)]
+pub async fn random(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let quote = ctx
+        .data()
+        .db
+        .run(move |conn| Quote::rand(conn, guild_id, None))
+        .await?;
+    post(ctx, quote, "No quotes saved yet!").await
+}
+
+/// Post a random quote of someone
+#[poise::command(slash_command)]
+pub async fn by(
+    ctx: Context<'_>,
+    #[description = "Who said it"] user: User,
+) -> Result<(), AppError> {
+    let (guild_id, author) = (ctx.guild_id().unwrap().get() as i64, user.id.get() as i64);
+    let quote = ctx
+        .data()
+        .db
+        .run(move |conn| Quote::rand(conn, guild_id, Some(author)))
+        .await?;
+    post(ctx, quote, &format!("{} has never been quoted", user.name)).await
+}
+
+/// Find quotes containing some text
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn search(
+    ctx: Context<'_>,
+    #[description = "Text to look for"] text: String,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let pattern = text.clone();
+    let quotes = ctx
+        .data()
+        .db
+        .run(move |conn| Quote::search(conn, guild_id, &pattern, 10))
+        .await?;
+    if quotes.is_empty() {
+        ctx.say("No quotes found").await?;
+        return Ok(());
+    }
+
+    let lines: Vec<_> = quotes
+        .iter()
+        .map(|q| {
+            format!(
+                "**#{}** {}: {}",
+                q.number,
+                q.author_name,
+                snippet(&q.content)
+            )
+        })
+        .collect();
+    ctx.send(
+        poise::CreateReply::new().embed(
+            CreateEmbed::default()
+                .title(format!("Quotes containing '{}'", snippet(&text)))
+                .description(lines.join("\n")),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Delete a quote you saved or that quotes you
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "Number of the quote"] number: i32,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let quote = ctx
+        .data()
+        .db
+        .run(move |conn| Quote::find(conn, guild_id, number))
+        .await?;
+    let quote = match quote {
+        Some(quote) => quote,
+        None => {
+            ctx.say(format!("There is no quote #{}", number)).await?;
+            return Ok(());
+        }
+    };
+
+    let author = ctx.author().id.get() as i64;
+    let moderator = ctx
+        .author_member()
+        .await
+        .and_then(|m| m.permissions)
+        .map_or(false, |p| p.manage_messages());
+    if quote.saved_by != author && quote.author_id != author && !moderator {
+        ctx.say("You can only delete quotes you saved or that quote you")
+            .await?;
+        return Ok(());
+    }
+    ctx.data().db.run(move |conn| quote.delete(conn)).await?;
+    ctx.say(format!("Deleted quote #{}", number)).await?;
+    Ok(())
+}
+
+async fn post(ctx: Context<'_>, quote: Option<Quote>, missing: &str) -> Result<(), AppError> {
+    let quote = match quote {
+        Some(quote) => quote,
+        None => {
+            ctx.send(poise::CreateReply::new().content(missing).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let mut author = CreateEmbedAuthor::new(&quote.author_name);
+    if let Some(avatar) = &quote.author_avatar {
+        author = author.icon_url(avatar);
+    }
+    let mut embed = CreateEmbed::default()
+        .author(author)
+        .footer(CreateEmbedFooter::new(format!("Quote #{}", quote.number)));
+    // Quotes of only an image have no text, and Discord refuses empty descriptions
+    if !quote.content.is_empty() {
+        embed = embed.description(&quote.content);
+    }
+    if let Ok(time) = Timestamp::from_unix_timestamp(quote.created_at.timestamp()) {
+        embed = embed.timestamp(time);
+    }
+    if let Some(image) = &quote.image_url {
+        embed = embed.image(image);
+    }
+    let source = MessageId::new(quote.message_id as u64).link(
+        ChannelId::new(quote.channel_id as u64),
+        Some(GuildId::new(quote.guild_id as u64)),
+    );
+    ctx.send(
+        poise::CreateReply::new()
+            .embed(embed)
+            .components(vec![CreateActionRow::Buttons(vec![
+                CreateButton::new_link(source).label("Source"),
+            ])]),
+    )
+    .await?;
+    Ok(())
+}
+
+/// The first line of a quote, shortened to fit a list
+fn snippet(content: &str) -> String {
+    let line = content.lines().next().unwrap_or("");
+    if line.chars().count() > 80 {
+        format!("{}…", line.chars().take(80).collect::<String>())
+    } else {
+        line.to_owned()
+    }
+}
@@ -0,0 +1,122 @@
+use crate::db::schema;
+use crate::Conn;
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::max;
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel::sql_types::Text;
+use qt::quote;
+use schema::quote::dsl as qt;
+
+sql_function!(fn random() -> Integer);
+sql_function!(fn lower(s: Text) -> Text);
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::quote)]
+pub struct Quote {
+    pub id: i32,
+    pub guild_id: i64,
+    /// Number of the quote within its guild
+    pub number: i32,
+    pub channel_id: i64,
+    pub message_id: i64,
+    pub author_id: i64,
+    pub author_name: String,
+    pub author_avatar: Option<String>,
+    pub content: String,
+    pub image_url: Option<String>,
+    pub saved_by: i64,
+    /// When the quoted message was sent
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::quote)]
+pub struct NewQuote {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub message_id: i64,
+    pub author_id: i64,
+    pub author_name: String,
+    pub author_avatar: Option<String>,
+    pub content: String,
+    pub image_url: Option<String>,
+    pub saved_by: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Quote {
+    pub fn find(conn: &mut Conn, guild_id: i64, number: i32) -> Result<Option<Quote>, Error> {
+        quote
+            .filter(qt::guild_id.eq(guild_id))
+            .filter(qt::number.eq(number))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn find_message(
+        conn: &mut Conn,
+        guild_id: i64,
+        message_id: i64,
+    ) -> Result<Option<Quote>, Error> {
+        quote
+            .filter(qt::guild_id.eq(guild_id))
+            .filter(qt::message_id.eq(message_id))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn rand(
+        conn: &mut Conn,
+        guild_id: i64,
+        author_id: Option<i64>,
+    ) -> Result<Option<Quote>, Error> {
+        let mut query = quote.filter(qt::guild_id.eq(guild_id)).into_boxed();
+        if let Some(author) = author_id {
+            query = query.filter(qt::author_id.eq(author));
+        }
+        query.order(random()).first(conn).optional()
+    }
+
+    /// Quotes containing `text`, ignoring case
+    pub fn search(
+        conn: &mut Conn,
+        guild_id: i64,
+        text: &str,
+        limit: i64,
+    ) -> Result<Vec<Quote>, Error> {
+        let pattern = format!(
+            "%{}%",
+            text.to_lowercase()
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        quote
+            .filter(qt::guild_id.eq(guild_id))
+            .filter(lower(qt::content).like(pattern).escape('\\'))
+            .order(qt::number)
+            .limit(limit)
+            .load(conn)
+    }
+
+    pub fn delete(&self, conn: &mut Conn) -> Result<usize, Error> {
+        diesel::delete(self).execute(conn)
+    }
+}
+
+impl NewQuote {
+    /// Saves the quote with the next free number of its guild
+    pub fn insert(&self, conn: &mut Conn) -> Result<Quote, Error> {
+        conn.transaction(|conn| {
+            let last: Option<i32> = quote
+                .select(max(qt::number))
+                .filter(qt::guild_id.eq(self.guild_id))
+                .first(conn)?;
+            diesel::insert_into(quote)
+                .values((self, qt::number.eq(last.unwrap_or(0) + 1)))
+                .get_result(conn)
+        })
+    }
+}
@@ -0,0 +1,183 @@
+mod model;
+#[cfg(test)]
+mod tests;
+
+use crate::{cmd, AppError, Context, Data, Db};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use log::info;
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::{CreateCommand, GuildId};
+use sha2::{Digest, Sha256};
+
+type Command = poise::Command<Data, AppError>;
+
+/// Scope of the global commands in `command_registration`
+const GLOBAL: i64 = 0;
+
+/// Pushes `builders` to a guild, or globally if there is none, unless they are the same as what
+/// was pushed there last time. Returns whether anything was pushed.
+async fn push(
+    http: impl AsRef<serenity::Http>,
+    db: &Db,
+    guild_id: Option<GuildId>,
+    builders: Vec<CreateCommand>,
+    force: bool,
+) -> Result<bool, AppError> {
+    let scope = guild_id.map_or(GLOBAL, |g| g.get() as i64);
+    let hash = URL_SAFE_NO_PAD.encode(Sha256::digest(serenity::json::to_vec(&builders)?));
+    if !force {
+        let pushed = db
+            .run(move |conn| CommandRegistration::hash(conn, scope))
+            .await?;
+        if pushed.as_ref() == Some(&hash) {
+            return Ok(false);
+        }
+    }
+
+    match guild_id {
+        Some(guild_id) => {
+            guild_id.set_commands(&http, builders).await?;
+        }
+        None => {
+            serenity::Command::set_global_commands(&http, builders).await?;
+        }
+    }
+    let registration = CommandRegistration { scope, hash };
+    db.run(move |conn| registration.upsert(conn)).await?;
+    info!(
+        "Registered commands {}",
+        guild_id.map_or("globally".to_owned(), |g| format!("in {}", g))
+    );
+    Ok(true)
+}
+
+/// Registers the commands that belong to no module globally, along with the module commands
+/// that also work in DMs. Those are limited to DMs, guilds get them with the rest of their
+/// module so they still disappear where it is off.
+/// In dev guild mode they are all registered in the dev guilds instead, and the global commands
+/// are removed so they don't show up twice there.
+pub async fn register_globally(
+    http: impl AsRef<serenity::Http>,
+    commands: &[Command],
+    data: &Data,
+    force: bool,
+) -> Result<bool, AppError> {
+    if !data.dev_guilds.is_empty() {
+        return push(http, &data.db, None, Vec::new(), force).await;
+    }
+    let (module, global): (Vec<_>, Vec<_>) = commands
+        .iter()
+        .partition(|c| data.command_modules.contains_key(&c.name));
+    let mut builders = cmd::application_commands(global.into_iter());
+    let dm = module.into_iter().filter(|c| !c.guild_only);
+    builders.extend(
+        cmd::application_commands(dm)
+            .into_iter()
+            .map(|b| b.contexts(vec![serenity::InteractionContext::BotDm])),
+    );
+    push(http, &data.db, None, builders, force).await
+}
+
+/// Registers the commands of the modules that are on in the guild,
+/// so the commands of the others disappear from its command list.
+/// In dev guild mode the other guilds are left alone.
+pub async fn register_in_guild(
+    http: impl AsRef<serenity::Http>,
+    commands: &[Command],
+    data: &Data,
+    guild_id: GuildId,
+    force: bool,
+) -> Result<bool, AppError> {
+    let dev = !data.dev_guilds.is_empty();
+    if dev && !data.dev_guilds.contains(&guild_id) {
+        return Ok(false);
+    }
+    let disabled = data.modules.disabled(guild_id).await?;
+    let enabled = commands
+        .iter()
+        .filter(|c| match data.command_modules.get(&c.name) {
+            Some(module) => !disabled.contains(*module),
+            None => dev,
+        });
+    let builders = cmd::application_commands(enabled);
+    push(http, &data.db, Some(guild_id), builders, force).await
+}
+
+/// Registers the commands again or removes them
+#[poise::command(
+    slash_command,
+    prefix_command,
+    owners_only,
+    hide_in_help,
+    subcommands("sync", "clear")
+)]
+pub async fn commands(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Register the commands everywhere again, even where they didn't change
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn sync(
+    ctx: Context<'_>,
+    #[description = "Only register the commands of this server"] here: Option<bool>,
+) -> Result<(), AppError> {
+    ctx.defer_ephemeral().await?;
+    let commands = &ctx.framework().options().commands;
+    let data = ctx.data();
+    if here.unwrap_or(false) {
+        let guild_id = match ctx.guild_id() {
+            Some(guild_id) => guild_id,
+            None => {
+                ctx.say("There is no server here").await?;
+                return Ok(());
+            }
+        };
+        let msg = if register_in_guild(ctx, commands, data, guild_id, true).await? {
+            "Registered the commands of this server"
+        } else {
+            "This server is not a dev guild, so it has no commands of its own"
+        };
+        ctx.say(msg).await?;
+        return Ok(());
+    }
+
+    let global = register_globally(ctx, commands, data, true).await?;
+    let mut guilds = 0;
+    for guild_id in ctx.cache().guilds() {
+        if register_in_guild(ctx, commands, data, guild_id, true).await? {
+            guilds += 1;
+        }
+    }
+    ctx.say(format!(
+        "Registered the commands {}in {} servers",
+        if global { "globally and " } else { "" },
+        guilds
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Remove the registered commands, prefix commands still work to bring them back
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn clear(
+    ctx: Context<'_>,
+    #[description = "Remove the commands of this server instead of the global ones"] here: Option<
+        bool,
+    >,
+) -> Result<(), AppError> {
+    let guild_id = match (here.unwrap_or(false), ctx.guild_id()) {
+        (true, None) => {
+            ctx.say("There is no server here").await?;
+            return Ok(());
+        }
+        (here, guild_id) => guild_id.filter(|_| here),
+    };
+    push(ctx, &ctx.data().db, guild_id, Vec::new(), true).await?;
+    ctx.say(match guild_id {
+        Some(_) => "Removed the commands of this server, the prefix command `commands sync` brings them back",
+        None => "Removed the global commands, the prefix command `commands sync` brings them back",
+    })
+    .await?;
+    Ok(())
+}
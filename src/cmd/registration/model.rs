@@ -0,0 +1,32 @@
+use crate::db::schema::command_registration;
+use crate::db::schema::command_registration::dsl::*;
+use crate::Conn;
+use diesel::prelude::*;
+use diesel::result::Error;
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = command_registration)]
+pub struct CommandRegistration {
+    /// Guild id, 0 for the global commands
+    pub scope: i64,
+    pub hash: String,
+}
+
+impl CommandRegistration {
+    /// Hash of what was last pushed to `key`
+    pub fn hash(conn: &mut Conn, key: i64) -> Result<Option<String>, Error> {
+        command_registration
+            .find(key)
+            .select(hash)
+            .first(conn)
+            .optional()
+    }
+
+    pub fn upsert(&self, conn: &mut Conn) -> Result<usize, Error> {
+        self.insert_into(command_registration)
+            .on_conflict(scope)
+            .do_update()
+            .set(hash.eq(&self.hash))
+            .execute(conn)
+    }
+}
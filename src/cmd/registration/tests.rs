@@ -0,0 +1,194 @@
+use super::*;
+use crate::cmd::module_settings::model::DisabledModule;
+use crate::testing::{self, FakeDiscord, APPLICATION_ID};
+use serde_json::json;
+
+const GUILD: u64 = 1;
+
+fn all_commands() -> Vec<Command> {
+    let mut commands = cmd::core();
+    for module in cmd::modules() {
+        commands.extend(module.commands);
+    }
+    commands
+}
+
+fn fake_with_routes(fake: &FakeDiscord, guilds: &[u64]) {
+    let global = format!("/applications/{}/commands", APPLICATION_ID);
+    fake.respond("PUT", &global, 200, json!([]));
+    for guild in guilds {
+        let path = format!("/applications/{}/guilds/{}/commands", APPLICATION_ID, guild);
+        fake.respond("PUT", &path, 200, json!([]));
+    }
+}
+
+/// Names of the commands pushed by each request, by the path they went to
+fn pushed(fake: &FakeDiscord) -> Vec<(String, Vec<String>)> {
+    fake.requests_to("PUT")
+        .into_iter()
+        .map(|r| {
+            let names = r.body.as_array().unwrap().iter();
+            let names = names.map(|c| c["name"].as_str().unwrap().to_owned());
+            (r.path, names.collect())
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn unchanged_commands_are_not_pushed_again() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    fake_with_routes(&fake, &[GUILD]);
+    let bot = fake.bot();
+    let commands = all_commands();
+    let guild_id = GuildId::new(GUILD);
+
+    assert!(register_globally(&bot, &commands, &data, false)
+        .await
+        .unwrap());
+    assert!(register_in_guild(&bot, &commands, &data, guild_id, false)
+        .await
+        .unwrap());
+    assert!(!register_globally(&bot, &commands, &data, false)
+        .await
+        .unwrap());
+    assert!(!register_in_guild(&bot, &commands, &data, guild_id, false)
+        .await
+        .unwrap());
+    assert_eq!(pushed(&fake).len(), 2);
+
+    // Forced, or once the commands change
+    assert!(register_globally(&bot, &commands, &data, true)
+        .await
+        .unwrap());
+    assert!(register_globally(&bot, &commands[1..], &data, false)
+        .await
+        .unwrap());
+    let pushed = pushed(&fake);
+    assert_eq!(pushed.len(), 4);
+    assert!(pushed[3].1.iter().all(|name| name != "help"));
+}
+
+#[tokio::test]
+async fn module_commands_that_work_in_dms_are_registered_there() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    fake_with_routes(&fake, &[]);
+    register_globally(fake.bot(), &all_commands(), &data, false)
+        .await
+        .unwrap();
+
+    let requests = fake.requests_to("PUT");
+    assert_eq!(requests.len(), 1);
+    let commands = requests[0].body.as_array().unwrap();
+    let find = |name: &str| commands.iter().find(|c| c["name"] == json!(name));
+    assert_eq!(find("help").unwrap().get("contexts"), None);
+    for name in ["remind", "reminders", "8ball", "Remind me about this"] {
+        let command = find(name).unwrap_or_else(|| panic!("{} is missing", name));
+        assert_eq!(command["contexts"], json!([1]), "{} is not DM only", name);
+    }
+    assert!(find("rolemenu").is_none());
+}
+
+#[tokio::test]
+async fn guild_commands_leave_out_disabled_modules() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    fake_with_routes(&fake, &[GUILD]);
+    let disabled = DisabledModule {
+        guild_id: GUILD as i64,
+        module: "general".to_owned(),
+    };
+    data.db.run(move |c| disabled.insert(c)).await.unwrap();
+
+    register_in_guild(
+        fake.bot(),
+        &all_commands(),
+        &data,
+        GuildId::new(GUILD),
+        false,
+    )
+    .await
+    .unwrap();
+
+    let pushed = pushed(&fake);
+    assert_eq!(pushed.len(), 1);
+    let names = &pushed[0].1;
+    assert!(names.iter().any(|n| n == "rolemenu"));
+    assert!(names.iter().any(|n| n == "Add to Favorites"));
+    // Global commands and disabled modules
+    for name in ["help", "modules", "8ball", "say"] {
+        assert!(names.iter().all(|n| n != name), "{} was registered", name);
+    }
+}
+
+#[tokio::test]
+async fn dev_guilds_get_every_command() {
+    let mut data = testing::data();
+    data.dev_guilds.insert(GuildId::new(GUILD));
+    let fake = FakeDiscord::start().await;
+    fake_with_routes(&fake, &[GUILD, GUILD + 1]);
+    let bot = fake.bot();
+    let commands = all_commands();
+
+    // Global commands left from before dev mode are removed, once
+    assert!(register_globally(&bot, &commands, &data, true)
+        .await
+        .unwrap());
+    assert!(!register_globally(&bot, &commands, &data, false)
+        .await
+        .unwrap());
+    assert!(
+        !register_in_guild(&bot, &commands, &data, GuildId::new(GUILD + 1), true)
+            .await
+            .unwrap()
+    );
+    assert!(
+        register_in_guild(&bot, &commands, &data, GuildId::new(GUILD), false)
+            .await
+            .unwrap()
+    );
+
+    let pushed = pushed(&fake);
+    assert_eq!(pushed.len(), 2);
+    assert_eq!(
+        pushed[0],
+        (format!("/applications/{}/commands", APPLICATION_ID), vec![])
+    );
+    let (path, names) = &pushed[1];
+    assert_eq!(
+        path,
+        &format!("/applications/{}/guilds/{}/commands", APPLICATION_ID, GUILD)
+    );
+    for name in [
+        "help",
+        "modules",
+        "commands",
+        "rolemenu",
+        "Add to Favorites",
+    ] {
+        assert!(names.iter().any(|n| n == name), "{} is missing", name);
+    }
+}
+
+#[tokio::test]
+async fn clearing_pushes_nothing() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    fake_with_routes(&fake, &[]);
+    let bot = fake.bot();
+    let commands = all_commands();
+
+    register_globally(&bot, &commands, &data, false)
+        .await
+        .unwrap();
+    push(&bot, &data.db, None, Vec::new(), true).await.unwrap();
+    // The stored hash is that of the empty list now, so the commands go up again
+    assert!(register_globally(&bot, &commands, &data, false)
+        .await
+        .unwrap());
+
+    let pushed = pushed(&fake);
+    assert_eq!(pushed.len(), 3);
+    assert!(pushed[1].1.is_empty());
+}
@@ -0,0 +1,308 @@
+mod model;
+
+use crate::cmd::module_settings::Modules;
+use crate::component::{Component, Route, TooLong};
+use crate::util::{from_now, parse_duration, relative_time};
+use crate::{AppError, Bot, ComponentAction, Context, Data, Db};
+use chrono::Utc;
+use log::{error, info};
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::{builder::*, model::prelude::*, ComponentInteractionDataKind};
+
+/// Button cancelling one of the clicking user's reminders
+pub struct CancelReminder {
+    pub id: i32,
+}
+
+impl Component for CancelReminder {
+    const ACTION: ComponentAction = ComponentAction::CancelReminder;
+    const MODULE: &'static str = "reminders";
+
+    fn encode(&self) -> Vec<String> {
+        vec![self.id.to_string()]
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        match fields {
+            [id] => Some(CancelReminder {
+                id: id.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ComponentInteraction,
+        data: &'a Data,
+    ) -> poise::BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(cancel(ctx, event, data, self))
+    }
+}
+
+inventory::submit!(Route::of::<CancelReminder>());
+
+const PRESETS: [(&str, u64); 5] = [
+    ("In 1 hour", 60 * 60),
+    ("In 3 hours", 3 * 60 * 60),
+    ("Tomorrow", 24 * 60 * 60),
+    ("In 3 days", 3 * 24 * 60 * 60),
+    ("Next week", 7 * 24 * 60 * 60),
+];
+
+/// Get reminded about something later
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn remind(
+    ctx: Context<'_>,
+    #[rename = "in"]
+    #[description = "When to remind you, e.g. '2h 30m' or '3 days'"]
+    duration: String,
+    #[rest]
+    #[description = "What to remind you about"]
+    about: String,
+) -> Result<(), AppError> {
+    let duration = match parse_duration(&duration) {
+        Ok(d) => d,
+        Err(_) => {
+            ctx.say(format!("'{}' is not a duration I understand", &duration))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let remind_at = match from_now(duration) {
+        Some(time) => time,
+        None => {
+            ctx.say("That is too far in the future").await?;
+            return Ok(());
+        }
+    };
+
+    let new = NewReminder {
+        user_id: ctx.author().id.get() as i64,
+        guild_id: ctx.guild_id().map(|g| g.get() as i64),
+        channel_id: ctx.channel_id().get() as i64,
+        message_id: None,
+        content: about,
+        remind_at,
+    };
+    let reminder = ctx.data().db.run(move |conn| new.insert(conn)).await?;
+    ctx.say(format!(
+        "Okay, I'll remind you {}",
+        relative_time(reminder.remind_at)
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Get reminded about a message later
+#[poise::command(context_menu_command = "Remind me about this", ephemeral = true)]
+pub async fn remind_msg(ctx: Context<'_>, msg: Message) -> Result<(), AppError> {
+    let id = ctx.id();
+    let options = PRESETS
+        .iter()
+        .map(|(label, secs)| CreateSelectMenuOption::new(*label, secs.to_string()))
+        .collect();
+    let handle = ctx
+        .send(
+            poise::CreateReply::default()
+                .content("When should I remind you?")
+                .components(vec![CreateActionRow::SelectMenu(CreateSelectMenu::new(
+                    id.to_string(),
+                    CreateSelectMenuKind::String { options },
+                ))]),
+        )
+        .await?;
+
+    let res = serenity::ComponentInteractionCollector::new(&ctx)
+        .filter(move |d| d.data.custom_id == id.to_string())
+        .timeout(std::time::Duration::from_secs(120))
+        .await;
+
+    if let Some(interaction) = res {
+        if let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind {
+            let secs: i64 = values[0].parse()?;
+            let content = if msg.content.is_empty() {
+                "this message".to_owned()
+            } else {
+                msg.content.chars().take(200).collect()
+            };
+            let new = NewReminder {
+                user_id: ctx.author().id.get() as i64,
+                guild_id: msg.guild_id.or(ctx.guild_id()).map(|g| g.get() as i64),
+                channel_id: msg.channel_id.get() as i64,
+                message_id: Some(msg.id.get() as i64),
+                content,
+                remind_at: Utc::now() + chrono::Duration::seconds(secs),
+            };
+            let reminder = ctx.data().db.run(move |conn| new.insert(conn)).await?;
+            interaction.defer(ctx).await?;
+            handle
+                .edit(
+                    ctx,
+                    poise::CreateReply::default()
+                        .content(format!(
+                            "Okay, I'll remind you {}",
+                            relative_time(reminder.remind_at)
+                        ))
+                        .components(vec![]),
+                )
+                .await?;
+        } else {
+            handle
+                .edit(
+                    ctx,
+                    poise::CreateReply::default()
+                        .content("Unknown selection")
+                        .components(vec![]),
+                )
+                .await?;
+        }
+    } else {
+        handle.delete(ctx).await?;
+    }
+    Ok(())
+}
+
+/// List your pending reminders
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn reminders(ctx: Context<'_>) -> Result<(), AppError> {
+    let user_id = ctx.author().id.get() as i64;
+    let reminders = ctx
+        .data()
+        .db
+        .run(move |conn| Reminder::list(conn, user_id))
+        .await?;
+
+    if reminders.is_empty() {
+        ctx.say("You have no pending reminders.").await?;
+        return Ok(());
+    }
+
+    let description = reminders
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            format!(
+                "**{}.** {} – {} ({})",
+                i + 1,
+                relative_time(r.remind_at),
+                r.content,
+                r.link()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let buttons: Vec<_> = reminders
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let button = CancelReminder { id: r.id };
+            Ok(
+                CreateButton::new(button.custom_id_for(Some(ctx.author().id), Some(r.remind_at))?)
+                    .style(ButtonStyle::Danger)
+                    .label(format!("Cancel #{}", i + 1)),
+            )
+        })
+        .collect::<Result<_, TooLong>>()?;
+    let rows = buttons
+        .chunks(5)
+        .map(|c| CreateActionRow::Buttons(c.to_vec()))
+        .collect();
+
+    ctx.send(
+        poise::CreateReply::default()
+            .embed(
+                CreateEmbed::default()
+                    .title("Your reminders")
+                    .description(description),
+            )
+            .components(rows),
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn cancel(
+    ctx: &Bot,
+    event: &ComponentInteraction,
+    data: &Data,
+    button: CancelReminder,
+) -> Result<(), AppError> {
+    let user_id = event.user.id.get() as i64;
+    let cancelled = data
+        .db
+        .run(move |conn| Reminder::cancel(conn, user_id, button.id))
+        .await?;
+    let res = if cancelled > 0 {
+        "Cancelled the reminder."
+    } else {
+        "This reminder was already delivered or cancelled."
+    };
+
+    event
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::Message(
+                serenity::CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(res),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Delivers every reminder that is due, including the ones missed while the bot was offline
+pub async fn deliver_due(
+    ctx: &serenity::Context,
+    db: &Db,
+    modules: &Modules,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    let due = db.run(move |conn| Reminder::due(conn, now)).await?;
+    for reminder in due {
+        let guild_id = reminder.guild_id.map(|g| GuildId::new(g as u64));
+        if !modules.enabled(guild_id, "reminders").await? {
+            continue;
+        }
+        info!("Delivering reminder {}", reminder.id);
+        if let Err(e) = deliver(ctx, &reminder).await {
+            error!("Could not deliver reminder {}: {:?}", reminder.id, e);
+        }
+        // Drop failed ones too, so an unreachable user doesn't get retried forever
+        let id = reminder.id;
+        db.run(move |conn| Reminder::delete_id(conn, id)).await?;
+    }
+    Ok(())
+}
+
+async fn deliver(ctx: &serenity::Context, reminder: &Reminder) -> Result<(), AppError> {
+    let user = UserId::new(reminder.user_id as u64);
+    let mut content = format!("⏰ Reminder: {}\n{}", reminder.content, reminder.link());
+    if Utc::now() - reminder.remind_at > chrono::Duration::minutes(5) {
+        content += &format!(
+            "\n*This was due {}, sorry for the delay!*",
+            relative_time(reminder.remind_at)
+        );
+    }
+
+    if user
+        .direct_message(ctx, CreateMessage::new().content(&content))
+        .await
+        .is_err()
+    {
+        // DMs closed, ping them where the reminder was created instead, and only them
+        ChannelId::new(reminder.channel_id as u64)
+            .send_message(
+                ctx,
+                CreateMessage::new()
+                    .content(format!("<@{}> {}", user, content))
+                    .allowed_mentions(CreateAllowedMentions::new().users([user])),
+            )
+            .await?;
+    }
+    Ok(())
+}
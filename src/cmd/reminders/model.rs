@@ -0,0 +1,71 @@
+use crate::db::schema::reminder;
+use crate::db::schema::reminder::dsl::*;
+use crate::Conn;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error;
+use poise::serenity_prelude::{ChannelId, GuildId, MessageId};
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = reminder)]
+pub struct Reminder {
+    pub id: i32,
+    pub user_id: i64,
+    pub guild_id: Option<i64>,
+    pub channel_id: i64,
+    pub message_id: Option<i64>,
+    pub content: String,
+    pub remind_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = reminder)]
+pub struct NewReminder {
+    pub user_id: i64,
+    pub guild_id: Option<i64>,
+    pub channel_id: i64,
+    pub message_id: Option<i64>,
+    pub content: String,
+    pub remind_at: DateTime<Utc>,
+}
+
+impl Reminder {
+    /// All reminders that should have been delivered by `now`, including ones missed while offline
+    pub fn due(conn: &mut Conn, now: DateTime<Utc>) -> Result<Vec<Reminder>, Error> {
+        reminder
+            .filter(remind_at.le(now))
+            .order(remind_at)
+            .load(conn)
+    }
+
+    pub fn list(conn: &mut Conn, user: i64) -> Result<Vec<Reminder>, Error> {
+        reminder
+            .filter(user_id.eq(user))
+            .order(remind_at)
+            .limit(25)
+            .load(conn)
+    }
+
+    pub fn cancel(conn: &mut Conn, user: i64, del_id: i32) -> Result<usize, Error> {
+        diesel::delete(reminder.find(del_id).filter(user_id.eq(user))).execute(conn)
+    }
+
+    pub fn delete_id(conn: &mut Conn, del_id: i32) -> Result<usize, Error> {
+        diesel::delete(reminder.find(del_id)).execute(conn)
+    }
+
+    pub fn link(&self) -> String {
+        let channel = ChannelId::new(self.channel_id as u64);
+        match self.message_id {
+            Some(msg) => MessageId::new(msg as u64)
+                .link(channel, self.guild_id.map(|g| GuildId::new(g as u64))),
+            None => format!("<#{}>", channel),
+        }
+    }
+}
+
+impl NewReminder {
+    pub fn insert(&self, conn: &mut Conn) -> Result<Reminder, Error> {
+        self.insert_into(reminder).get_result(conn)
+    }
+}
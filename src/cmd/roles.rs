@@ -1,21 +1,76 @@
-mod model;
-use std::{collections::HashSet, str::FromStr};
+pub mod model;
+#[cfg(test)]
+mod tests;
+use std::collections::HashSet;
 
-use crate::{AppError, Context};
+use crate::cmd::levels::model::LevelReward;
+use crate::cmd::shop::model::ShopItem;
+use crate::modal::{self, Form};
+use crate::{AppError, Bot, Context, Data, Db, ModalAction};
 
 use diesel::result::DatabaseErrorKind;
 use poise::serenity_prelude as serenity;
 use serenity::{ComponentInteractionDataKind, builder::*, model::id::RoleId};
+use serenity::{Member, ModalInteraction, ReactionType, Role};
 
 use model::*;
 
+/// Form editing how a role is presented in its role menu
+pub struct EditRoleOption {
+    pub id: i32,
+}
+
+#[derive(Debug, poise::Modal)]
+#[name = "Edit role option"]
+pub struct RoleOptionDetails {
+    #[name = "Description"]
+    #[placeholder = "Shown below the role in the menu"]
+    #[max_length = 100]
+    description: Option<String>,
+    #[name = "Emoji"]
+    #[placeholder = "An emoji like 🫘 or <:name:id>"]
+    #[max_length = 64]
+    emoji: Option<String>,
+}
+
+impl Form for EditRoleOption {
+    const ACTION: ModalAction = ModalAction::EditRoleOption;
+    const MODULE: &'static str = "roles";
+    type Inputs = RoleOptionDetails;
+
+    fn encode(&self) -> Vec<String> {
+        vec![self.id.to_string()]
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        match fields {
+            [id] => Some(EditRoleOption {
+                id: id.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ModalInteraction,
+        data: &'a Data,
+        inputs: RoleOptionDetails,
+    ) -> poise::BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(save_option(ctx, event, data, self, inputs))
+    }
+}
+
+inventory::submit!(modal::Route::of::<EditRoleOption>());
+
 #[poise::command(
     slash_command,
     guild_only = true,
     ephemeral = true,
     required_permissions = "MANAGE_ROLES",
     required_bot_permissions = "MANAGE_ROLES",
-    subcommands("new", "del", "rename")
+    subcommands("new", "del", "rename", "edit")
 )]
 pub async fn rolemenu(_ctx: Context<'_>) -> Result<(), AppError> {
     Ok(())
@@ -30,10 +85,12 @@ pub async fn new(
     #[description = "Maximum number of selectable roles"]
     max_selectable: Option<i32>,
 ) -> Result<(), AppError> {
-    let mut conn = ctx.data().db.get()?;
     let guild_id = ctx.guild_id().unwrap().get() as i64;
 
-    if RoleMenu::find(&mut conn, guild_id, &name)?.is_some() {
+    if find(&ctx.data().db, guild_id, name.clone())
+        .await?
+        .is_some()
+    {
         ctx.say(format!("The role menu '{}' already exists", &name))
             .await?;
         return Ok(());
@@ -60,7 +117,10 @@ pub async fn new(
                 .into_iter()
                 .map(|r| NewRoleOption::blank(r))
                 .collect();
-            new.insert(&mut conn, &mut roles)?;
+            ctx.data()
+                .db
+                .run(move |conn| conn.insert_menu(&new, &mut roles))
+                .await?;
             interaction.defer(ctx).await?;
             handle
                 .edit(
@@ -87,8 +147,13 @@ pub async fn del(
     #[description = "Name of the role menu to delete"]
     name: String,
 ) -> Result<(), AppError> {
-    let mut conn = ctx.data().db.get()?;
-    let deleted = RoleMenu::delete(&mut conn, ctx.guild_id().unwrap().get() as i64, &name)?;
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let menu = name.clone();
+    let deleted = ctx
+        .data()
+        .db
+        .run(move |conn| conn.delete_menu(guild_id, &menu))
+        .await?;
     let msg = if deleted > 0 {
         format!("Deleted role menu '{}'", &name)
     } else {
@@ -106,21 +171,97 @@ pub async fn rename(
     from: String,
     #[description = "What to rename it to"] to: String,
 ) -> Result<(), AppError> {
-    let mut conn = ctx.data().db.get()?;
     let guild_id = ctx.guild_id().unwrap().get() as i64;
 
-    let msg = match RoleMenu::rename(&mut conn, guild_id, &from, &to) {
-        Ok(0) => Ok(format!("Could not find role menu '{}'", &from)),
-        Ok(_) => Ok(format!("Renamed '{}' to '{}'", &from, &to)),
-        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
-            Ok(format!("The role menu '{}' already exists", &to))
-        }
-        Err(e) => Err(e),
-    }?;
+    let (old, new) = (from.clone(), to.clone());
+    let renamed = ctx
+        .data()
+        .db
+        .run(move |conn| match conn.rename_menu(guild_id, &old, &new) {
+            Ok(renamed) => Ok(Some(renamed)),
+            Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        })
+        .await?;
+    let msg = match renamed {
+        Some(0) => format!("Could not find role menu '{}'", &from),
+        Some(_) => format!("Renamed '{}' to '{}'", &from, &to),
+        None => format!("The role menu '{}' already exists", &to),
+    };
     ctx.say(msg).await?;
     Ok(())
 }
 
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn edit(
+    ctx: Context<'_>,
+    #[autocomplete = "comp_rolemenu"]
+    #[description = "Name of the role menu"]
+    name: String,
+    #[description = "Role to edit"] role: Role,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+
+    let menu = find(&ctx.data().db, guild_id, name.clone()).await?;
+    let option = menu.and_then(|(_, options)| {
+        options
+            .into_iter()
+            .find(|o| o.role_id == role.id.get() as i64)
+    });
+    let option = match option {
+        Some(option) => option,
+        None => {
+            ctx.say(format!(
+                "'{}' is not in the role menu '{}'",
+                &role.name, &name
+            ))
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let defaults = RoleOptionDetails {
+        description: option.description,
+        emoji: option.emoji,
+    };
+    modal::show(ctx, &EditRoleOption { id: option.id }, Some(defaults)).await
+}
+
+async fn save_option(
+    ctx: &Bot,
+    event: &ModalInteraction,
+    data: &Data,
+    form: EditRoleOption,
+    inputs: RoleOptionDetails,
+) -> Result<(), AppError> {
+    let option = match data.db.run(move |conn| conn.find_option(form.id)).await? {
+        Some(option) => option,
+        None => return modal::reply(ctx, event, "This role option no longer exists").await,
+    };
+
+    let description = inputs.description.filter(|d| !d.trim().is_empty());
+    let emoji = inputs.emoji.filter(|e| !e.trim().is_empty());
+    if let Some(emoji) = &emoji {
+        if ReactionType::try_from(emoji.trim()).is_err() {
+            let msg = format!("'{}' is not an emoji I can use", emoji);
+            return modal::reply(ctx, event, &msg).await;
+        }
+    }
+    let role_id = option.role_id;
+    data.db
+        .run(move |conn| {
+            conn.set_option_details(
+                &option,
+                description.as_deref(),
+                emoji.as_deref().map(str::trim),
+            )
+        })
+        .await?;
+    modal::reply(ctx, event, &format!("Updated <@&{}>", role_id)).await
+}
+
 #[poise::command(
     slash_command,
     ephemeral = true,
@@ -134,12 +275,21 @@ pub async fn roles(
     name: String,
 ) -> Result<(), AppError> {
     let guild_id = ctx.guild_id().unwrap().get() as i64;
-    let mut conn = ctx.data().db.get()?;
-    if let Some((menu, roles)) = RoleMenu::find(&mut conn, guild_id, &name)? {
+    if let Some((menu, roles)) = find(&ctx.data().db, guild_id, name.clone()).await? {
         let mut member = ctx.author_member().await.unwrap().into_owned();
-        let user_roles: HashSet<&RoleId> = HashSet::from_iter(member.roles.iter());
-        let handle = send_rolemenu(&ctx, &menu, &roles, &user_roles).await?;
+        let guild = ctx.partial_guild().await.unwrap();
+        let present: Vec<_> = roles
+            .iter()
+            .filter_map(|option| {
+                Some((
+                    option,
+                    guild.roles.get(&RoleId::new(option.role_id as u64))?,
+                ))
+            })
+            .collect();
         let id = ctx.id();
+        let reply = menu_reply(&id.to_string(), &menu, &present, &member.roles);
+        let handle = ctx.send(reply).await?;
 
         let res = serenity::ComponentInteractionCollector::new(&ctx)
             .filter(move |d| d.data.custom_id == id.to_string())
@@ -147,34 +297,11 @@ pub async fn roles(
             .await;
 
         if let Some(interaction) = res {
-            if let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind {
-                interaction.defer(ctx).await?;
-
-                let value_set: HashSet<RoleId> =
-                    HashSet::from_iter(values.iter().map(|str| RoleId::from_str(str).unwrap()));
-                let (mut add, mut del): (Vec<_>, Vec<_>) = roles
-                    .iter()
-                    .map(|role| RoleId::new(role.role_id as u64))
-                    .partition(|id| value_set.contains(id));
-
-                // Yes, this is necessary 🤦
-                add.retain(|r| !user_roles.contains(r));
-                del.retain(|r| user_roles.contains(r));
-
-                member.add_roles(&ctx, &add).await?;
-                member.remove_roles(&ctx, &del).await?;
-
-                handle
-                    .edit(
-                        ctx,
-                        poise::CreateReply::new()
-                            .content("Successfully assigned roles")
-                            .components(vec![]),
-                    )
-                    .await?;
-            } else {
-                unreachable!();
-            }
+            interaction.defer(ctx).await?;
+            let bot = Bot::from(ctx.serenity_context());
+            let db = &ctx.data().db;
+            let reply = select(&bot, db, &mut member, &roles, &interaction.data.kind).await?;
+            handle.edit(ctx, reply).await?;
         } else {
             handle.delete(ctx).await?;
         }
@@ -185,46 +312,114 @@ pub async fn roles(
     Ok(())
 }
 
-async fn send_rolemenu<'a>(
-    ctx: &Context<'a>,
-    menu: &RoleMenu,
-    roles: &[RoleOption],
-    user_roles: &HashSet<&RoleId>,
-) -> Result<poise::ReplyHandle<'a>, AppError> {
-    let guild = ctx.partial_guild().await.unwrap();
+/// Applies what the member picked in a role menu, the reply replaces the menu
+async fn select(
+    http: impl AsRef<serenity::Http>,
+    db: &Db,
+    member: &mut Member,
+    options: &[RoleOption],
+    selection: &ComponentInteractionDataKind,
+) -> Result<poise::CreateReply, AppError> {
+    let msg = match selection {
+        ComponentInteractionDataKind::StringSelect { values } => {
+            assign_roles(http, db, member, options, values).await?;
+            "Successfully assigned roles"
+        }
+        _ => "Unknown selection",
+    };
+    Ok(poise::CreateReply::default()
+        .content(msg)
+        .components(vec![]))
+}
 
-    let options: Vec<_> = roles
+/// Gives the member the roles of the menu they selected and takes away the others,
+/// returning the roles added and removed
+pub(crate) async fn assign_roles(
+    http: impl AsRef<serenity::Http>,
+    db: &Db,
+    member: &mut Member,
+    options: &[RoleOption],
+    selected: &[String],
+) -> Result<(Vec<RoleId>, Vec<RoleId>), AppError> {
+    let guild_id = member.guild_id.get() as i64;
+    let selected: HashSet<RoleId> = selected.iter().filter_map(|r| r.parse().ok()).collect();
+    let (mut add, mut del): (Vec<_>, Vec<_>) = options
         .iter()
-        .map(|role| guild.roles.get(&RoleId::new(role.id as u64)))
-        .flatten()
-        .map(|role| {
-            CreateSelectMenuOption::new(&role.name, role.id.get().to_string())
-                .default_selection(user_roles.contains(&role.id))
+        .map(|role| RoleId::new(role.role_id as u64))
+        .partition(|id| selected.contains(id));
+
+    // Yes, this is necessary 🤦
+    add.retain(|r| !member.roles.contains(r));
+    del.retain(|r| member.roles.contains(r));
+
+    // Level rewards have to be earned and shop roles bought,
+    // neither can be dropped through a menu either
+    let exclusive = db
+        .run(move |conn| {
+            let mut exclusive = LevelReward::role_ids(conn, guild_id)?;
+            exclusive.extend(ShopItem::role_ids(conn, guild_id)?);
+            Ok(exclusive)
         })
-        .collect();
-    let max_values = std::cmp::min(roles.len(), menu.max_selectable.unwrap_or(25) as usize);
-    let select = CreateSelectMenu::new(
-        ctx.id().to_string(),
-        CreateSelectMenuKind::String { options },
-    )
-    .min_values(1)
-    .max_values(max_values as u64);
+        .await?;
+    add.retain(|r| !exclusive.contains(r));
+    del.retain(|r| !exclusive.contains(r));
+
+    member.add_roles(&http, &add).await?;
+    member.remove_roles(&http, &del).await?;
+    Ok((add, del))
+}
 
-    let res =
-        ctx.send(poise::CreateReply::new().components(vec![CreateActionRow::SelectMenu(select)]));
-    Ok(res.await?)
+/// The select menu of a role menu, with the roles the member has already selected.
+/// `options` are the menu's roles that still exist, along with them.
+fn menu_reply(
+    id: &str,
+    menu: &RoleMenu,
+    options: &[(&RoleOption, &Role)],
+    member_roles: &[RoleId],
+) -> poise::CreateReply {
+    let max_values = std::cmp::min(options.len(), menu.max_selectable.unwrap_or(25) as usize);
+    let options = options
+        .iter()
+        .map(|(option, role)| {
+            let mut select = CreateSelectMenuOption::new(&role.name, role.id.get().to_string())
+                .default_selection(member_roles.contains(&role.id));
+            if let Some(description) = &option.description {
+                select = select.description(description);
+            }
+            if let Some(emoji) = option
+                .emoji
+                .as_deref()
+                .and_then(|e| ReactionType::try_from(e).ok())
+            {
+                select = select.emoji(emoji);
+            }
+            select
+        })
+        .collect();
+    let select = CreateSelectMenu::new(id, CreateSelectMenuKind::String { options })
+        .min_values(1)
+        .max_values(max_values as u64);
+    poise::CreateReply::default().components(vec![CreateActionRow::SelectMenu(select)])
 }
 
 async fn comp_rolemenu(ctx: Context<'_>, partial: &str) -> Vec<String> {
     let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let partial = partial.to_owned();
     ctx.data()
         .db
-        .get()
-        .ok()
-        .and_then(|mut conn| RoleMenu::comp_rolemenu(&mut conn, guild_id, partial).ok())
+        .run(move |conn| conn.menu_names(guild_id, &partial))
+        .await
         .unwrap_or(Vec::new())
 }
 
+async fn find(
+    db: &Db,
+    guild_id: i64,
+    name: String,
+) -> Result<Option<(RoleMenu, Vec<RoleOption>)>, AppError> {
+    db.run(move |conn| conn.find_menu(guild_id, &name)).await
+}
+
 fn role_select(id: &str, name: &str) -> poise::CreateReply {
     poise::CreateReply::default()
         .content(format!(
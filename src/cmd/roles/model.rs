@@ -10,9 +10,12 @@ use ro::role_option;
 use schema::role_menu::dsl as rm;
 use schema::role_option::dsl as ro;
 
+#[cfg(test)]
+mod tests;
+
 sql_function!(fn lower(s: Text) -> Text);
 
-#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq, Clone)]
 #[diesel(table_name = schema::role_menu)]
 pub struct RoleMenu {
     pub id: i32,
@@ -21,7 +24,7 @@ pub struct RoleMenu {
     pub max_selectable: Option<i32>,
 }
 
-#[derive(Queryable, Identifiable, Selectable, Associations, Debug, PartialEq)]
+#[derive(Queryable, Identifiable, Selectable, Associations, Debug, PartialEq, Clone)]
 #[diesel(belongs_to(RoleMenu))]
 #[diesel(table_name = schema::role_option)]
 pub struct RoleOption {
@@ -49,20 +52,56 @@ pub struct NewRoleOption {
     pub role_menu_id: Option<i32>,
 }
 
-impl RoleMenu {
-    pub fn find(
-        conn: &mut Conn,
+/// Where role menus and their options are stored.
+/// Menu names are unique within a guild and compared ignoring case.
+pub trait RoleMenuRepo {
+    fn find_menu(
+        &mut self,
+        guild_id: i64,
+        name: &str,
+    ) -> Result<Option<(RoleMenu, Vec<RoleOption>)>, Error>;
+
+    /// Creates the menu together with its options, whose `role_menu_id` is filled in.
+    /// Fails with a unique violation if the name is taken.
+    fn insert_menu(&mut self, menu: &NewRoleMenu, roles: &mut [NewRoleOption])
+        -> Result<(), Error>;
+
+    /// Deletes the menu, its options go with it
+    fn delete_menu(&mut self, guild_id: i64, name: &str) -> Result<usize, Error>;
+
+    /// Fails with a unique violation if another menu is already called `to`
+    fn rename_menu(&mut self, guild_id: i64, from: &str, to: &str) -> Result<usize, Error>;
+
+    /// Names of the guild's menus starting with `partial`
+    fn menu_names(&mut self, guild_id: i64, partial: &str) -> Result<Vec<String>, Error>;
+
+    fn find_option(&mut self, id: i32) -> Result<Option<RoleOption>, Error>;
+
+    fn set_option_details(
+        &mut self,
+        option: &RoleOption,
+        description: Option<&str>,
+        emoji: Option<&str>,
+    ) -> Result<usize, Error>;
+
+    /// Whether the role is offered by any role menu of the guild
+    fn role_in_menu(&mut self, guild_id: i64, role_id: i64) -> Result<bool, Error>;
+}
+
+impl RoleMenuRepo for Conn {
+    fn find_menu(
+        &mut self,
         guild_id: i64,
         name: &str,
     ) -> Result<Option<(RoleMenu, Vec<RoleOption>)>, Error> {
         let menu: Option<RoleMenu> = role_menu
             .filter(rm::guild_id.eq(guild_id))
             .filter(lower(rm::name).eq(name.to_lowercase()))
-            .first(conn)
+            .first(self)
             .optional()?;
 
         let res = if let Some(m) = menu {
-            let roles = RoleOption::belonging_to(&m).load(conn)?;
+            let roles = RoleOption::belonging_to(&m).load(self)?;
             Some((m, roles))
         } else {
             None
@@ -71,50 +110,76 @@ impl RoleMenu {
         Ok(res)
     }
 
-    pub fn delete(conn: &mut Conn, guild_id: i64, name: &str) -> Result<usize, Error> {
+    fn insert_menu(
+        &mut self,
+        menu: &NewRoleMenu,
+        roles: &mut [NewRoleOption],
+    ) -> Result<(), Error> {
+        self.transaction(|conn| {
+            let menu: RoleMenu = menu.insert_into(role_menu).get_result(conn)?;
+            roles
+                .iter_mut()
+                .for_each(|u| u.role_menu_id = Some(menu.id));
+
+            insert_into(role_option).values(&*roles).execute(conn)?;
+            Ok(())
+        })
+    }
+
+    fn delete_menu(&mut self, guild_id: i64, name: &str) -> Result<usize, Error> {
         diesel::delete(
             role_menu
                 .filter(rm::guild_id.eq(guild_id))
                 .filter(lower(rm::name).eq(name.to_lowercase())),
         )
-        .execute(conn)
+        .execute(self)
     }
 
-    pub fn comp_rolemenu(
-        conn: &mut Conn,
-        guild_id: i64,
-        partial: &str,
-    ) -> Result<Vec<String>, Error> {
+    fn rename_menu(&mut self, guild_id: i64, from: &str, to: &str) -> Result<usize, Error> {
+        // A taken name must not abort a surrounding transaction
+        self.transaction(|conn| {
+            diesel::update(
+                role_menu
+                    .filter(rm::guild_id.eq(guild_id))
+                    .filter(lower(rm::name).eq(from.to_lowercase())),
+            )
+            .set(rm::name.eq(to))
+            .execute(conn)
+        })
+    }
+
+    fn menu_names(&mut self, guild_id: i64, partial: &str) -> Result<Vec<String>, Error> {
         let pattern = format!("{}%", partial.to_lowercase());
         role_menu
             .select(rm::name)
             .filter(rm::guild_id.eq(guild_id))
             .filter(lower(rm::name).like(pattern))
-            .get_results(conn)
+            .get_results(self)
     }
 
-    pub fn rename(conn: &mut Conn, guild_id: i64, from: &str, to: &str) -> Result<usize, Error> {
-        diesel::update(
-            role_menu
-                .filter(rm::guild_id.eq(guild_id))
-                .filter(lower(rm::name).eq(from.to_lowercase())),
-        )
-        .set(rm::name.eq(to))
-        .execute(conn)
+    fn find_option(&mut self, id: i32) -> Result<Option<RoleOption>, Error> {
+        role_option.find(id).first(self).optional()
     }
-}
 
-impl NewRoleMenu {
-    pub fn insert(&self, conn: &mut Conn, roles: &mut [NewRoleOption]) -> Result<(), Error> {
-        conn.transaction(|conn| {
-            let menu: RoleMenu = self.insert_into(role_menu).get_result(conn)?;
-            roles
-                .iter_mut()
-                .for_each(|u| u.role_menu_id = Some(menu.id));
+    fn set_option_details(
+        &mut self,
+        option: &RoleOption,
+        description: Option<&str>,
+        emoji: Option<&str>,
+    ) -> Result<usize, Error> {
+        diesel::update(option)
+            .set((ro::description.eq(description), ro::emoji.eq(emoji)))
+            .execute(self)
+    }
 
-            insert_into(role_option).values(&*roles).execute(conn)?;
-            Ok(())
-        })
+    fn role_in_menu(&mut self, guild_id: i64, role_id: i64) -> Result<bool, Error> {
+        let count: i64 = role_option
+            .inner_join(role_menu)
+            .filter(rm::guild_id.eq(guild_id))
+            .filter(ro::role_id.eq(role_id))
+            .count()
+            .get_result(self)?;
+        Ok(count > 0)
     }
 }
 
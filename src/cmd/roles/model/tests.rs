@@ -0,0 +1,264 @@
+use super::{NewRoleMenu, NewRoleOption, RoleMenu, RoleMenuRepo, RoleOption};
+use diesel::result::{DatabaseErrorKind, Error};
+use poise::serenity_prelude::RoleId;
+
+/// Keeps menus and options in `Vec`s, behaving like the `role_menu` and `role_option` tables:
+/// names are unique ignoring case and deleting a menu cascades to its options
+#[derive(Default)]
+struct MemoryRoleMenus {
+    menus: Vec<RoleMenu>,
+    options: Vec<RoleOption>,
+    next_id: i32,
+}
+
+impl MemoryRoleMenus {
+    fn next_id(&mut self) -> i32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn position(&self, guild_id: i64, name: &str) -> Option<usize> {
+        self.menus
+            .iter()
+            .position(|m| m.guild_id == guild_id && m.name.to_lowercase() == name.to_lowercase())
+    }
+}
+
+fn unique_violation() -> Error {
+    Error::DatabaseError(
+        DatabaseErrorKind::UniqueViolation,
+        Box::new("duplicate role menu name".to_owned()),
+    )
+}
+
+impl RoleMenuRepo for MemoryRoleMenus {
+    fn find_menu(
+        &mut self,
+        guild_id: i64,
+        name: &str,
+    ) -> Result<Option<(RoleMenu, Vec<RoleOption>)>, Error> {
+        Ok(self.position(guild_id, name).map(|i| {
+            let menu = self.menus[i].clone();
+            let options = self
+                .options
+                .iter()
+                .filter(|o| o.role_menu_id == menu.id)
+                .cloned()
+                .collect();
+            (menu, options)
+        }))
+    }
+
+    fn insert_menu(
+        &mut self,
+        menu: &NewRoleMenu,
+        roles: &mut [NewRoleOption],
+    ) -> Result<(), Error> {
+        if self.position(menu.guild_id, &menu.name).is_some() {
+            return Err(unique_violation());
+        }
+        let id = self.next_id();
+        self.menus.push(RoleMenu {
+            id,
+            guild_id: menu.guild_id,
+            name: menu.name.clone(),
+            max_selectable: menu.max_selectable,
+        });
+        for role in roles {
+            role.role_menu_id = Some(id);
+            let option = RoleOption {
+                id: self.next_id(),
+                role_id: role.role_id,
+                description: role.description.clone(),
+                emoji: role.emoji.clone(),
+                role_menu_id: id,
+            };
+            self.options.push(option);
+        }
+        Ok(())
+    }
+
+    fn delete_menu(&mut self, guild_id: i64, name: &str) -> Result<usize, Error> {
+        Ok(match self.position(guild_id, name) {
+            Some(i) => {
+                let menu = self.menus.remove(i);
+                self.options.retain(|o| o.role_menu_id != menu.id);
+                1
+            }
+            None => 0,
+        })
+    }
+
+    fn rename_menu(&mut self, guild_id: i64, from: &str, to: &str) -> Result<usize, Error> {
+        let i = match self.position(guild_id, from) {
+            Some(i) => i,
+            None => return Ok(0),
+        };
+        if self
+            .position(guild_id, to)
+            .map_or(false, |other| other != i)
+        {
+            return Err(unique_violation());
+        }
+        self.menus[i].name = to.to_owned();
+        Ok(1)
+    }
+
+    fn menu_names(&mut self, guild_id: i64, partial: &str) -> Result<Vec<String>, Error> {
+        let partial = partial.to_lowercase();
+        Ok(self
+            .menus
+            .iter()
+            .filter(|m| m.guild_id == guild_id && m.name.to_lowercase().starts_with(&partial))
+            .map(|m| m.name.clone())
+            .collect())
+    }
+
+    fn find_option(&mut self, id: i32) -> Result<Option<RoleOption>, Error> {
+        Ok(self.options.iter().find(|o| o.id == id).cloned())
+    }
+
+    fn set_option_details(
+        &mut self,
+        option: &RoleOption,
+        description: Option<&str>,
+        emoji: Option<&str>,
+    ) -> Result<usize, Error> {
+        Ok(match self.options.iter_mut().find(|o| o.id == option.id) {
+            Some(o) => {
+                o.description = description.map(str::to_owned);
+                o.emoji = emoji.map(str::to_owned);
+                1
+            }
+            None => 0,
+        })
+    }
+
+    fn role_in_menu(&mut self, guild_id: i64, role_id: i64) -> Result<bool, Error> {
+        Ok(self.options.iter().any(|o| {
+            o.role_id == role_id
+                && self
+                    .menus
+                    .iter()
+                    .any(|m| m.id == o.role_menu_id && m.guild_id == guild_id)
+        }))
+    }
+}
+
+const GUILD: i64 = 1;
+
+fn add_menu(repo: &mut impl RoleMenuRepo, name: &str, roles: &[u64]) -> Result<(), Error> {
+    let menu = NewRoleMenu {
+        guild_id: GUILD,
+        name: name.to_owned(),
+        max_selectable: None,
+    };
+    let mut roles: Vec<_> = roles
+        .iter()
+        .map(|r| NewRoleOption::blank(&RoleId::new(*r)))
+        .collect();
+    repo.insert_menu(&menu, &mut roles)
+}
+
+fn is_unique_violation(res: Result<impl std::fmt::Debug, Error>) -> bool {
+    matches!(
+        res,
+        Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _))
+    )
+}
+
+fn menu_lookup_ignores_case(repo: &mut impl RoleMenuRepo) {
+    add_menu(repo, "Colours", &[10, 11]).unwrap();
+
+    let (menu, options) = repo.find_menu(GUILD, "cOLOURS").unwrap().unwrap();
+    assert_eq!(menu.name, "Colours");
+    assert_eq!(options.len(), 2);
+    assert!(repo.find_menu(GUILD + 1, "Colours").unwrap().is_none());
+    assert_eq!(repo.menu_names(GUILD, "col").unwrap(), vec!["Colours"]);
+    assert!(repo.menu_names(GUILD, "pro").unwrap().is_empty());
+}
+
+fn menu_names_are_unique_ignoring_case(repo: &mut impl RoleMenuRepo) {
+    add_menu(repo, "Colours", &[10]).unwrap();
+    assert!(is_unique_violation(add_menu(repo, "colours", &[11])));
+    assert!(repo.role_in_menu(GUILD, 10).unwrap());
+    assert!(!repo.role_in_menu(GUILD, 11).unwrap());
+}
+
+fn rename_conflicts(repo: &mut impl RoleMenuRepo) {
+    add_menu(repo, "Colours", &[10]).unwrap();
+    add_menu(repo, "Pronouns", &[20]).unwrap();
+
+    assert!(is_unique_violation(
+        repo.rename_menu(GUILD, "Pronouns", "COLOURS")
+    ));
+    assert_eq!(repo.rename_menu(GUILD, "Games", "Hobbies").unwrap(), 0);
+
+    // Changing only the case of its own name is fine
+    assert_eq!(repo.rename_menu(GUILD, "colours", "COLOURS").unwrap(), 1);
+    let (menu, options) = repo.find_menu(GUILD, "colours").unwrap().unwrap();
+    assert_eq!(menu.name, "COLOURS");
+    assert_eq!(options[0].role_id, 10);
+}
+
+fn deleting_menu_deletes_options(repo: &mut impl RoleMenuRepo) {
+    add_menu(repo, "Colours", &[10, 11]).unwrap();
+    add_menu(repo, "Pronouns", &[20]).unwrap();
+    let (_, options) = repo.find_menu(GUILD, "Colours").unwrap().unwrap();
+
+    assert_eq!(repo.delete_menu(GUILD, "colours").unwrap(), 1);
+    assert_eq!(repo.delete_menu(GUILD, "colours").unwrap(), 0);
+    for option in options {
+        assert!(repo.find_option(option.id).unwrap().is_none());
+        assert!(!repo.role_in_menu(GUILD, option.role_id).unwrap());
+    }
+    assert!(repo.role_in_menu(GUILD, 20).unwrap());
+}
+
+fn option_details(repo: &mut impl RoleMenuRepo) {
+    add_menu(repo, "Colours", &[10]).unwrap();
+    let (_, options) = repo.find_menu(GUILD, "Colours").unwrap().unwrap();
+
+    repo.set_option_details(&options[0], Some("Red"), Some("🟥"))
+        .unwrap();
+    let option = repo.find_option(options[0].id).unwrap().unwrap();
+    assert_eq!(option.description.as_deref(), Some("Red"));
+    assert_eq!(option.emoji.as_deref(), Some("🟥"));
+}
+
+/// Runs every check against the in-memory repo, and against the test database if there is one
+macro_rules! on_both {
+    ($($check:ident),* $(,)?) => {
+        mod memory {
+            $(
+                #[test]
+                fn $check() {
+                    super::$check(&mut super::MemoryRoleMenus::default());
+                }
+            )*
+        }
+
+        mod database {
+            $(
+                #[tokio::test]
+                async fn $check() {
+                    let db = crate::db::Database::for_tests();
+                    db.run(|conn| {
+                        super::$check(conn);
+                        Ok(())
+                    })
+                    .await
+                    .unwrap();
+                }
+            )*
+        }
+    };
+}
+
+on_both!(
+    menu_lookup_ignores_case,
+    menu_names_are_unique_ignoring_case,
+    rename_conflicts,
+    deleting_menu_deletes_options,
+    option_details
+);
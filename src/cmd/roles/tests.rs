@@ -0,0 +1,200 @@
+use super::*;
+use crate::cmd::levels::model::NewLevelReward;
+use crate::cmd::shop::model::NewShopItem;
+use crate::testing::{self, FakeDiscord};
+use serde_json::json;
+
+const GUILD: u64 = 1;
+const USER: u64 = 10;
+
+fn options(roles: &[u64]) -> Vec<RoleOption> {
+    roles
+        .iter()
+        .enumerate()
+        .map(|(i, role)| RoleOption {
+            id: i as i32 + 1,
+            role_id: *role as i64,
+            description: None,
+            emoji: None,
+            role_menu_id: 1,
+        })
+        .collect()
+}
+
+fn role(id: u64) -> Role {
+    serde_json::from_value(json!({
+        "id": id.to_string(),
+        "name": format!("Role {}", id),
+        "color": 0,
+        "colors": {"primary_color": 0, "secondary_color": null, "tertiary_color": null},
+        "hoist": false,
+        "icon": null,
+        "unicode_emoji": null,
+        "position": 1,
+        "permissions": "0",
+        "managed": false,
+        "mentionable": false,
+        "flags": 0,
+    }))
+    .unwrap()
+}
+
+fn member(roles: &[u64]) -> Member {
+    serde_json::from_value(testing::member(GUILD, USER, roles)).unwrap()
+}
+
+/// The roles changed through the API, as (method, role)
+fn role_changes(fake: &FakeDiscord) -> Vec<(String, u64)> {
+    let prefix = format!("/guilds/{}/members/{}/roles/", GUILD, USER);
+    fake.requests()
+        .into_iter()
+        .map(|r| {
+            let role = r.path.strip_prefix(&prefix).expect("Not a role change");
+            (r.method, role.parse().unwrap())
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn assigning_changes_only_the_difference() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    let mut member = member(&[20, 21, 99]);
+
+    let selected = vec!["21".to_owned(), "22".to_owned()];
+    let (add, del) = assign_roles(
+        fake.bot(),
+        &data.db,
+        &mut member,
+        &options(&[20, 21, 22, 23]),
+        &selected,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(add, vec![RoleId::new(22)]);
+    assert_eq!(del, vec![RoleId::new(20)]);
+    assert_eq!(
+        role_changes(&fake),
+        vec![("PUT".to_owned(), 22), ("DELETE".to_owned(), 20)]
+    );
+}
+
+#[tokio::test]
+async fn assigning_leaves_rewards_and_shop_roles_alone() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    data.db
+        .run(|conn| {
+            NewLevelReward {
+                guild_id: GUILD as i64,
+                level: 5,
+                role_id: 30,
+            }
+            .insert(conn)?;
+            NewShopItem {
+                guild_id: GUILD as i64,
+                role_id: 31,
+                price: 100,
+                duration: None,
+            }
+            .insert(conn)
+        })
+        .await
+        .unwrap();
+    let mut member = member(&[30, 40]);
+
+    // Neither can the reward be dropped nor the shop role be picked for free
+    let selected = vec!["31".to_owned(), "41".to_owned()];
+    let (add, del) = assign_roles(
+        fake.bot(),
+        &data.db,
+        &mut member,
+        &options(&[30, 31, 40, 41]),
+        &selected,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(add, vec![RoleId::new(41)]);
+    assert_eq!(del, vec![RoleId::new(40)]);
+    assert_eq!(
+        role_changes(&fake),
+        vec![("PUT".to_owned(), 41), ("DELETE".to_owned(), 40)]
+    );
+}
+
+#[tokio::test]
+async fn assigning_nothing_new_makes_no_requests() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    let mut member = member(&[20]);
+
+    let selected = vec!["20".to_owned()];
+    let (add, del) = assign_roles(
+        fake.bot(),
+        &data.db,
+        &mut member,
+        &options(&[20, 21]),
+        &selected,
+    )
+    .await
+    .unwrap();
+
+    assert!(add.is_empty() && del.is_empty());
+    assert!(fake.requests().is_empty());
+}
+
+#[test]
+fn menu_preselects_the_roles_of_the_member() {
+    let menu = RoleMenu {
+        id: 1,
+        guild_id: GUILD as i64,
+        name: "Colors".to_owned(),
+        max_selectable: Some(1),
+    };
+    let mut options = options(&[20, 21]);
+    options[1].description = Some("The best one".to_owned());
+    let roles = [role(20), role(21)];
+    let present: Vec<_> = options.iter().zip(&roles).collect();
+
+    let reply = menu_reply("menu", &menu, &present, &[RoleId::new(21)]);
+    let menu = serde_json::to_value(reply.components.unwrap()).unwrap();
+    let select = &menu[0]["components"][0];
+    assert_eq!(select["custom_id"], "menu");
+    assert_eq!(select["max_values"], 1);
+    assert_eq!(select["options"][0]["label"], "Role 20");
+    assert_eq!(select["options"][0]["default"], false);
+    assert_eq!(select["options"][1]["default"], true);
+    assert_eq!(select["options"][1]["description"], "The best one");
+}
+
+#[tokio::test]
+async fn selecting_assigns_and_replaces_the_menu() {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    let mut member = member(&[20]);
+
+    let selection = ComponentInteractionDataKind::StringSelect {
+        values: vec!["21".to_owned()],
+    };
+    let reply = select(
+        fake.bot(),
+        &data.db,
+        &mut member,
+        &options(&[20, 21]),
+        &selection,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        reply.content.as_deref(),
+        Some("Successfully assigned roles")
+    );
+    assert_eq!(reply.components.map(|c| c.len()), Some(0));
+    assert_eq!(
+        role_changes(&fake),
+        vec![("PUT".to_owned(), 21), ("DELETE".to_owned(), 20)]
+    );
+}
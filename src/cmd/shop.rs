@@ -0,0 +1,399 @@
+pub mod model;
+
+use crate::cmd::economy::{is_admin, model::Account};
+use crate::cmd::levels::model::LevelReward;
+use crate::cmd::module_settings::Modules;
+use crate::cmd::roles::model::RoleMenuRepo;
+use crate::util::{from_now, is_not_found, parse_duration, relative_time, top_role_position};
+use crate::{AppError, Context, Db};
+use chrono::Utc;
+use diesel::result::DatabaseErrorKind;
+use log::{error, info};
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::{builder::*, model::prelude::*, ComponentInteractionDataKind};
+use std::time::Duration;
+
+/// Roles with these permissions would let anyone with enough beans moderate or run the server
+const PRIVILEGED: Permissions = Permissions::ADMINISTRATOR
+    .union(Permissions::MANAGE_GUILD)
+    .union(Permissions::MANAGE_ROLES)
+    .union(Permissions::MANAGE_CHANNELS)
+    .union(Permissions::MANAGE_MESSAGES)
+    .union(Permissions::MANAGE_THREADS)
+    .union(Permissions::MANAGE_WEBHOOKS)
+    .union(Permissions::MANAGE_NICKNAMES)
+    .union(Permissions::MANAGE_GUILD_EXPRESSIONS)
+    .union(Permissions::MANAGE_EVENTS)
+    .union(Permissions::MODERATE_MEMBERS)
+    .union(Permissions::KICK_MEMBERS)
+    .union(Permissions::BAN_MEMBERS);
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_bot_permissions = "MANAGE_ROLES",
+    subcommands("buy", "add", "remove", "refund")
+)]
+pub async fn shop(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Spend your beans on roles
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn buy(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let user_id = ctx.author().id.get() as i64;
+    let (items, balance) = ctx
+        .data()
+        .db
+        .run(move |conn| {
+            let items = ShopItem::list(conn, guild_id)?;
+            Ok((items, Account::balance(conn, guild_id, user_id)?))
+        })
+        .await?;
+    let guild = ctx
+        .partial_guild()
+        .await
+        .ok_or("Could not load the server")?;
+
+    let options: Vec<_> = items
+        .iter()
+        .filter_map(|item| {
+            let role = guild.roles.get(&RoleId::new(item.role_id as u64))?;
+            let mut label = format!("{} – {} beans", role.name, item.price);
+            if let Some(duration) = item.duration {
+                label += &format!(" for {}", format_duration(duration));
+            }
+            Some(CreateSelectMenuOption::new(label, item.id.to_string()).emoji('🫘'))
+        })
+        .take(25)
+        .collect();
+    if options.is_empty() {
+        ctx.say("There is nothing for sale yet.").await?;
+        return Ok(());
+    }
+
+    let id = ctx.id();
+    let select = CreateSelectMenu::new(id.to_string(), CreateSelectMenuKind::String { options })
+        .placeholder("Pick a role to buy");
+    let handle = ctx
+        .send(
+            poise::CreateReply::new()
+                .content(format!("You have {} beans to spend", balance))
+                .components(vec![CreateActionRow::SelectMenu(select)]),
+        )
+        .await?;
+
+    let res = serenity::ComponentInteractionCollector::new(&ctx)
+        .filter(move |d| d.data.custom_id == id.to_string())
+        .timeout(std::time::Duration::from_secs(120))
+        .await;
+
+    let interaction = match res {
+        Some(interaction) => interaction,
+        None => {
+            handle.delete(ctx).await?;
+            return Ok(());
+        }
+    };
+    let item_id: i32 = match &interaction.data.kind {
+        ComponentInteractionDataKind::StringSelect { values } if values.len() == 1 => {
+            values[0].parse()?
+        }
+        _ => {
+            handle
+                .edit(
+                    ctx,
+                    poise::CreateReply::new()
+                        .content("Unknown selection")
+                        .components(vec![]),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+    interaction.defer(ctx).await?;
+
+    let item = ctx
+        .data()
+        .db
+        .run(move |conn| ShopItem::find(conn, guild_id, item_id))
+        .await?;
+    let msg = match item {
+        Some(item) => purchase(ctx, item).await?,
+        None => "This role is no longer for sale".to_owned(),
+    };
+    handle
+        .edit(
+            ctx,
+            poise::CreateReply::new().content(msg).components(vec![]),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Put a role up for sale
+#[poise::command(
+    slash_command,
+    ephemeral = true,
+    check = "is_admin",
+    required_permissions = "MANAGE_ROLES"
+)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Role to sell"] role: Role,
+    #[min = 1]
+    #[description = "Price in beans"]
+    price: i64,
+    #[description = "How long buyers keep it, e.g. '30d', empty for forever"] duration: Option<
+        String,
+    >,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+
+    let duration = match duration.as_deref().map(parse_duration) {
+        Some(Ok(d)) if from_now(d).is_none() => {
+            ctx.say("That is too far in the future").await?;
+            return Ok(());
+        }
+        Some(Ok(d)) => Some(d.as_secs() as i64),
+        Some(Err(_)) => {
+            ctx.say("The duration is not a duration I understand")
+                .await?;
+            return Ok(());
+        }
+        None => None,
+    };
+    if price < 1 || role.managed || role.id.get() == guild_id as u64 {
+        ctx.say(format!("'{}' can't be sold for that", &role.name))
+            .await?;
+        return Ok(());
+    }
+    if role.permissions.intersects(PRIVILEGED) {
+        ctx.say(format!(
+            "'{}' can't be sold, it has moderation or management permissions",
+            &role.name
+        ))
+        .await?;
+        return Ok(());
+    }
+    if let Some(msg) = above_hierarchy(ctx, &role).await? {
+        ctx.say(msg).await?;
+        return Ok(());
+    }
+    let role_id = role.id;
+    let taken = ctx
+        .data()
+        .db
+        .run(move |conn| {
+            Ok(conn.role_in_menu(guild_id, role_id.get() as i64)?
+                || LevelReward::role_ids(conn, guild_id)?.contains(&role_id))
+        })
+        .await?;
+    if taken {
+        ctx.say(format!(
+            "'{}' is already handed out by a role menu or as a level reward",
+            &role.name
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let new = NewShopItem {
+        guild_id,
+        role_id: role.id.get() as i64,
+        price,
+        duration,
+    };
+    let added = ctx
+        .data()
+        .db
+        .run(move |conn| match new.insert(conn) {
+            Ok(_) => Ok(true),
+            Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        })
+        .await?;
+    let msg = if added {
+        format!("'{}' is now for sale", &role.name)
+    } else {
+        format!("'{}' is already for sale", &role.name)
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Stop selling a role, buyers keep it
+#[poise::command(slash_command, ephemeral = true, check = "is_admin")]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Role to stop selling"] role: Role,
+) -> Result<(), AppError> {
+    let (guild_id, role_id) = (ctx.guild_id().unwrap().get() as i64, role.id.get() as i64);
+    let deleted = ctx
+        .data()
+        .db
+        .run(move |conn| ShopItem::delete(conn, guild_id, role_id))
+        .await?;
+    let msg = if deleted > 0 {
+        format!("'{}' is no longer for sale", &role.name)
+    } else {
+        format!("'{}' is not for sale", &role.name)
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Take a bought role away and give the buyer their beans back
+#[poise::command(slash_command, ephemeral = true, check = "is_admin")]
+pub async fn refund(
+    ctx: Context<'_>,
+    #[description = "Who bought the role"] user: User,
+    #[description = "Role to refund"] role: Role,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let (guild, user_id, role_id) = (
+        guild_id.get() as i64,
+        user.id.get() as i64,
+        role.id.get() as i64,
+    );
+    let purchase = ctx
+        .data()
+        .db
+        .run(move |conn| Purchase::active_for(conn, guild, user_id, role_id))
+        .await?;
+    let purchase = match purchase {
+        Some(purchase) => purchase,
+        None => {
+            ctx.say(format!("{} didn't buy '{}'", user.name, &role.name))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let price = purchase.price;
+    match guild_id.member(ctx, user.id).await {
+        Ok(mut member) => member.remove_roles(ctx, &[role.id]).await?,
+        // They left and took the role with them
+        Err(e) if is_not_found(&e) => {}
+        Err(e) => return Err(e.into()),
+    }
+    if !ctx.data().db.run(move |conn| purchase.refund(conn)).await? {
+        ctx.say(format!(
+            "The purchase of '{}' by {} already ended",
+            &role.name, user.name
+        ))
+        .await?;
+        return Ok(());
+    }
+    ctx.say(format!(
+        "Refunded {} beans to {} for '{}'",
+        price, user.name, &role.name
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Takes away bought roles whose time is up.
+/// A purchase only ends once the role is gone, so failures are retried on the next run.
+pub async fn expire_due(
+    ctx: &serenity::Context,
+    db: &Db,
+    modules: &Modules,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    for purchase in db.run(move |conn| Purchase::expired(conn, now)).await? {
+        let guild_id = GuildId::new(purchase.guild_id as u64);
+        if !modules.enabled(Some(guild_id), "shop").await? {
+            continue;
+        }
+        info!("Purchase {} expired", purchase.id);
+        let res = match guild_id.member(ctx, purchase.user_id as u64).await {
+            Ok(mut member) => {
+                member
+                    .remove_roles(ctx, &[RoleId::new(purchase.role_id as u64)])
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match res {
+            Ok(()) => {}
+            // The buyer left or the role was deleted
+            Err(e) if is_not_found(&e) => {}
+            Err(e) => {
+                error!("Could not remove role of purchase {}: {:?}", purchase.id, e);
+                continue;
+            }
+        }
+        db.run(move |conn| purchase.deactivate(conn)).await?;
+    }
+    Ok(())
+}
+
+/// Why the role is too high to be sold, if it ranks at or above the author or the bot.
+/// Discord wouldn't let the bot hand it out, and the author could sell roles they can't assign.
+async fn above_hierarchy(ctx: Context<'_>, role: &Role) -> Result<Option<&'static str>, AppError> {
+    let guild = ctx
+        .partial_guild()
+        .await
+        .ok_or("Could not load the server")?;
+    let bot_id = ctx.cache().current_user().id;
+    let bot = guild.id.member(ctx, bot_id).await?;
+    if role.position >= top_role_position(&guild, &bot) {
+        return Ok(Some("I can only hand out roles below my highest role"));
+    }
+    if ctx.author().id == guild.owner_id {
+        return Ok(None);
+    }
+    let author = ctx
+        .author_member()
+        .await
+        .ok_or("Could not load your roles")?;
+    if role.position >= top_role_position(&guild, &author) {
+        return Ok(Some("You can only sell roles below your highest role"));
+    }
+    Ok(None)
+}
+
+async fn purchase(ctx: Context<'_>, item: ShopItem) -> Result<String, AppError> {
+    let role = RoleId::new(item.role_id as u64);
+    let mut member = ctx
+        .author_member()
+        .await
+        .ok_or("Could not load your roles")?
+        .into_owned();
+    if member.roles.contains(&role) {
+        return Ok(format!("You already have <@&{}>", role));
+    }
+
+    let price = item.price;
+    let user_id = ctx.author().id.get() as i64;
+    let purchase = match ctx
+        .data()
+        .db
+        .run(move |conn| item.buy(conn, user_id))
+        .await?
+    {
+        Some(purchase) => purchase,
+        None => return Ok("You don't have enough beans".to_owned()),
+    };
+    if let Err(e) = member.add_roles(ctx, &[role]).await {
+        error!("Could not grant role of purchase {}: {:?}", purchase.id, e);
+        ctx.data().db.run(move |conn| purchase.refund(conn)).await?;
+        return Ok("I couldn't give you the role, your beans were refunded".to_owned());
+    }
+
+    let mut msg = format!("🫘 You bought <@&{}> for {} beans", role, price);
+    if let Some(expires_at) = purchase.expires_at {
+        msg += &format!(", it expires {}", relative_time(expires_at));
+    }
+    Ok(msg)
+}
+
+fn format_duration(secs: i64) -> String {
+    humantime::format_duration(Duration::from_secs(secs as u64)).to_string()
+}
@@ -0,0 +1,185 @@
+use crate::cmd::economy::model::{Account, LedgerKind};
+use crate::db::schema;
+use crate::Conn;
+
+use chrono::{DateTime, Duration, Utc};
+use diesel::result::Error;
+use diesel::prelude::*;
+use poise::serenity_prelude::RoleId;
+use schema::shop_item::dsl as si;
+use schema::shop_purchase::dsl as sp;
+use si::shop_item;
+use sp::shop_purchase;
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::shop_item)]
+pub struct ShopItem {
+    pub id: i32,
+    pub guild_id: i64,
+    pub role_id: i64,
+    pub price: i64,
+    /// How long the role is kept in seconds, forever if unset
+    pub duration: Option<i64>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::shop_item)]
+pub struct NewShopItem {
+    pub guild_id: i64,
+    pub role_id: i64,
+    pub price: i64,
+    pub duration: Option<i64>,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::shop_purchase)]
+pub struct Purchase {
+    pub id: i32,
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub role_id: i64,
+    pub price: i64,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Whether the buyer still has the role
+    pub active: bool,
+    pub refunded: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::shop_purchase)]
+struct NewPurchase {
+    guild_id: i64,
+    user_id: i64,
+    role_id: i64,
+    price: i64,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl ShopItem {
+    pub fn list(conn: &mut Conn, guild_id: i64) -> Result<Vec<ShopItem>, Error> {
+        shop_item
+            .filter(si::guild_id.eq(guild_id))
+            .order(si::price)
+            .load(conn)
+    }
+
+    pub fn find(conn: &mut Conn, guild_id: i64, id: i32) -> Result<Option<ShopItem>, Error> {
+        shop_item
+            .find(id)
+            .filter(si::guild_id.eq(guild_id))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn delete(conn: &mut Conn, guild_id: i64, role_id: i64) -> Result<usize, Error> {
+        diesel::delete(
+            shop_item
+                .filter(si::guild_id.eq(guild_id))
+                .filter(si::role_id.eq(role_id)),
+        )
+        .execute(conn)
+    }
+
+    /// Roles that are only available by buying them
+    pub fn role_ids(conn: &mut Conn, guild_id: i64) -> Result<Vec<RoleId>, Error> {
+        let ids: Vec<i64> = shop_item
+            .select(si::role_id)
+            .filter(si::guild_id.eq(guild_id))
+            .load(conn)?;
+        Ok(ids.into_iter().map(|id| RoleId::new(id as u64)).collect())
+    }
+
+    /// Charges the user and records the purchase.
+    /// Returns `None` if the user can't afford it.
+    pub fn buy(&self, conn: &mut Conn, user_id: i64) -> Result<Option<Purchase>, Error> {
+        let expires_at = match self.duration {
+            Some(d) => Some(Utc::now().checked_add_signed(Duration::seconds(d)).ok_or_else(
+                || Error::QueryBuilderError(format!("Shop item {} lasts too long", self.id).into()),
+            )?),
+            None => None,
+        };
+        conn.transaction(|conn| {
+            let paid = Account::change(
+                conn,
+                self.guild_id,
+                user_id,
+                -self.price,
+                LedgerKind::Purchase,
+                None,
+            )?;
+            if paid.is_none() {
+                return Ok(None);
+            }
+            NewPurchase {
+                guild_id: self.guild_id,
+                user_id,
+                role_id: self.role_id,
+                price: self.price,
+                expires_at,
+            }
+            .insert_into(shop_purchase)
+            .get_result(conn)
+            .map(Some)
+        })
+    }
+}
+
+impl NewShopItem {
+    pub fn insert(&self, conn: &mut Conn) -> Result<usize, Error> {
+        self.insert_into(shop_item).execute(conn)
+    }
+}
+
+impl Purchase {
+    /// The purchase through which the user currently has the role
+    pub fn active_for(
+        conn: &mut Conn,
+        guild_id: i64,
+        user_id: i64,
+        role_id: i64,
+    ) -> Result<Option<Purchase>, Error> {
+        shop_purchase
+            .filter(sp::guild_id.eq(guild_id))
+            .filter(sp::user_id.eq(user_id))
+            .filter(sp::role_id.eq(role_id))
+            .filter(sp::active.eq(true))
+            .first(conn)
+            .optional()
+    }
+
+    /// Active purchases whose time is up
+    pub fn expired(conn: &mut Conn, now: DateTime<Utc>) -> Result<Vec<Purchase>, Error> {
+        shop_purchase
+            .filter(sp::active.eq(true))
+            .filter(sp::expires_at.le(now))
+            .load(conn)
+    }
+
+    pub fn deactivate(&self, conn: &mut Conn) -> Result<usize, Error> {
+        diesel::update(self).set(sp::active.eq(false)).execute(conn)
+    }
+
+    /// Gives the buyer their beans back and ends the purchase
+    /// Returns whether it was refunded, it is only once and only while active
+    pub fn refund(&self, conn: &mut Conn) -> Result<bool, Error> {
+        conn.transaction(|conn| {
+            let refunded = diesel::update(self)
+                .filter(sp::active.eq(true))
+                .set((sp::active.eq(false), sp::refunded.eq(true)))
+                .execute(conn)?;
+            if refunded != 1 {
+                return Ok(false);
+            }
+            Account::change(
+                conn,
+                self.guild_id,
+                self.user_id,
+                self.price,
+                LedgerKind::Refund,
+                None,
+            )?;
+            Ok(true)
+        })
+    }
+}
@@ -0,0 +1,360 @@
+mod model;
+
+use crate::component::{Component, Route, TooLong};
+use crate::{AppError, Bot, ComponentAction, Context, Data};
+use log::{error, info};
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::{builder::*, model::prelude::*};
+
+/// Button voting a suggestion up or down
+pub struct VoteOnSuggestion {
+    pub id: i32,
+    pub upvote: bool,
+}
+
+impl Component for VoteOnSuggestion {
+    const ACTION: ComponentAction = ComponentAction::VoteOnSuggestion;
+    const MODULE: &'static str = "suggestions";
+
+    fn encode(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            if self.upvote { "up" } else { "down" }.to_owned(),
+        ]
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        match fields {
+            [id, upvote] => Some(VoteOnSuggestion {
+                id: id.parse().ok()?,
+                upvote: match *upvote {
+                    "up" => true,
+                    "down" => false,
+                    _ => return None,
+                },
+            }),
+            _ => None,
+        }
+    }
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ComponentInteraction,
+        data: &'a Data,
+    ) -> poise::BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(vote(ctx, event, data, self))
+    }
+}
+
+inventory::submit!(Route::of::<VoteOnSuggestion>());
+
+/// Leaves room for the author line in the 4096 character embed description
+const MAX_CONTENT: usize = 4000;
+/// Leaves room for the status in the 1024 character embed field
+const MAX_REASON: usize = 1000;
+
+/// Suggest something to the server's staff
+#[poise::command(slash_command, prefix_command, guild_only = true, ephemeral = true)]
+pub async fn suggest(
+    ctx: Context<'_>,
+    #[rest]
+    #[description = "Your suggestion"]
+    text: String,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let channel = ctx
+        .data()
+        .db
+        .run(move |conn| suggestion_channel(conn, guild_id))
+        .await?;
+    let channel = match channel {
+        Some(channel) => ChannelId::new(channel as u64),
+        None => {
+            ctx.say("This server doesn't take suggestions").await?;
+            return Ok(());
+        }
+    };
+    if text.chars().count() > MAX_CONTENT {
+        ctx.say(format!(
+            "Suggestions can be at most {} characters long",
+            MAX_CONTENT
+        ))
+        .await?;
+        return Ok(());
+    }
+    let new = NewSuggestion {
+        guild_id,
+        channel_id: channel.get() as i64,
+        author_id: ctx.author().id.get() as i64,
+        content: text,
+    };
+    let suggestion = ctx.data().db.run(move |conn| new.insert(conn)).await?;
+    let msg = channel
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .embed(embed(&suggestion, (0, 0)))
+                .components(vec![vote_buttons(&suggestion)?]),
+        )
+        .await?;
+    let message_id = msg.id.get() as i64;
+    ctx.data()
+        .db
+        .run(move |conn| suggestion.set_message(conn, message_id))
+        .await?;
+
+    ctx.say(format!("Posted your suggestion in <#{}>", channel))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "MANAGE_MESSAGES",
+    subcommands("channel", "approve", "deny", "implement")
+)]
+pub async fn suggestion(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Set the channel suggestions are posted in
+#[poise::command(slash_command, ephemeral = true, required_permissions = "MANAGE_GUILD")]
+pub async fn channel(
+    ctx: Context<'_>,
+    #[description = "Channel for suggestions"] channel: GuildChannel,
+) -> Result<(), AppError> {
+    let (guild_id, channel_id) = (
+        ctx.guild_id().unwrap().get() as i64,
+        channel.id.get() as i64,
+    );
+    ctx.data()
+        .db
+        .run(move |conn| set_suggestion_channel(conn, guild_id, channel_id))
+        .await?;
+    ctx.say(format!("Suggestions will be posted in <#{}>", channel.id))
+        .await?;
+    Ok(())
+}
+
+/// Approve a suggestion
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn approve(
+    ctx: Context<'_>,
+    #[description = "Number of the suggestion"] id: i32,
+    #[description = "Why it was approved"] reason: Option<String>,
+) -> Result<(), AppError> {
+    update_status(ctx, id, Status::Approved, reason).await
+}
+
+/// Deny a suggestion
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn deny(
+    ctx: Context<'_>,
+    #[description = "Number of the suggestion"] id: i32,
+    #[description = "Why it was denied"] reason: Option<String>,
+) -> Result<(), AppError> {
+    update_status(ctx, id, Status::Denied, reason).await
+}
+
+/// Mark a suggestion as implemented
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn implement(
+    ctx: Context<'_>,
+    #[description = "Number of the suggestion"] id: i32,
+    #[description = "Notes on the implementation"] reason: Option<String>,
+) -> Result<(), AppError> {
+    update_status(ctx, id, Status::Implemented, reason).await
+}
+
+pub async fn vote(
+    ctx: &Bot,
+    event: &ComponentInteraction,
+    data: &Data,
+    button: VoteOnSuggestion,
+) -> Result<(), AppError> {
+    let VoteOnSuggestion { id, upvote } = button;
+    let suggestion = data
+        .db
+        .run(move |conn| Suggestion::find_id(conn, id))
+        .await?;
+    let suggestion = match suggestion {
+        Some(suggestion) if suggestion.status() == Status::Open => suggestion,
+        _ => {
+            event
+                .create_response(
+                    ctx,
+                    serenity::CreateInteractionResponse::Message(
+                        serenity::CreateInteractionResponseMessage::new()
+                            .ephemeral(true)
+                            .content("Voting on this suggestion has closed."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let user_id = event.user.id.get() as i64;
+    let (suggestion, vote, votes) = data
+        .db
+        .run(move |conn| {
+            let vote = suggestion.vote(conn, user_id, upvote)?;
+            let votes = suggestion.votes(conn)?;
+            Ok((suggestion, vote, votes))
+        })
+        .await?;
+    event
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new().embed(embed(&suggestion, votes)),
+            ),
+        )
+        .await?;
+    let res = match vote {
+        Some(true) => "You upvoted this suggestion.",
+        Some(false) => "You downvoted this suggestion.",
+        None => "You took back your vote.",
+    };
+    event
+        .create_followup(
+            ctx,
+            CreateInteractionResponseFollowup::new()
+                .ephemeral(true)
+                .content(res),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn update_status(
+    ctx: Context<'_>,
+    id: i32,
+    status: Status,
+    reason: Option<String>,
+) -> Result<(), AppError> {
+    if reason
+        .as_ref()
+        .map_or(false, |r| r.chars().count() > MAX_REASON)
+    {
+        ctx.say(format!(
+            "The reason can be at most {} characters long",
+            MAX_REASON
+        ))
+        .await?;
+        return Ok(());
+    }
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let updated = ctx
+        .data()
+        .db
+        .run(move |conn| match Suggestion::find(conn, guild_id, id)? {
+            Some(suggestion) => {
+                let suggestion = suggestion.set_status(conn, status, reason.as_deref())?;
+                let votes = suggestion.votes(conn)?;
+                Ok(Some((suggestion, votes)))
+            }
+            None => Ok(None),
+        })
+        .await?;
+    let (suggestion, votes) = match updated {
+        Some(updated) => updated,
+        None => {
+            ctx.say(format!("Could not find suggestion #{}", id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // The status is saved, so the author hears about it even if the post is gone
+    if let Some(message_id) = suggestion.message_id {
+        if let Err(e) = ChannelId::new(suggestion.channel_id as u64)
+            .edit_message(
+                ctx,
+                MessageId::new(message_id as u64),
+                EditMessage::new()
+                    .embed(embed(&suggestion, votes))
+                    .components(vec![]),
+            )
+            .await
+        {
+            error!("Could not update suggestion {}: {:?}", suggestion.id, e);
+        }
+    }
+
+    let guild = ctx
+        .guild()
+        .map(|g| g.name.clone())
+        .unwrap_or("the server".to_owned());
+    let mut msg = format!(
+        "Your suggestion #{} in **{}** was {}",
+        suggestion.id,
+        guild,
+        status.to_string().to_lowercase()
+    );
+    if let Some(reason) = &suggestion.reason {
+        msg += &format!(": {}", reason);
+    }
+    let author = UserId::new(suggestion.author_id as u64);
+    if let Err(e) = author
+        .direct_message(ctx, CreateMessage::new().content(msg))
+        .await
+    {
+        info!(
+            "Could not notify {} about their suggestion: {:?}",
+            author, e
+        );
+    }
+
+    ctx.say(format!(
+        "Marked suggestion #{} as {}",
+        id,
+        status.to_string().to_lowercase()
+    ))
+    .await?;
+    Ok(())
+}
+
+fn vote_buttons(suggestion: &Suggestion) -> Result<CreateActionRow, TooLong> {
+    let button = |upvote: bool, emoji: char| {
+        let button = VoteOnSuggestion {
+            id: suggestion.id,
+            upvote,
+        };
+        Ok(CreateButton::new(button.custom_id()?)
+            .style(ButtonStyle::Secondary)
+            .emoji(emoji))
+    };
+    Ok(CreateActionRow::Buttons(vec![
+        button(true, '👍')?,
+        button(false, '👎')?,
+    ]))
+}
+
+fn embed(suggestion: &Suggestion, (up, down): (usize, usize)) -> CreateEmbed {
+    let status = suggestion.status();
+    let colour = match status {
+        Status::Open => Colour::LIGHT_GREY,
+        Status::Approved => Colour::DARK_GREEN,
+        Status::Denied => Colour::RED,
+        Status::Implemented => Colour::BLUE,
+    };
+    let mut status = status.to_string();
+    if let Some(reason) = &suggestion.reason {
+        status += &format!(": {}", reason);
+    }
+
+    CreateEmbed::default()
+        .title(format!("Suggestion #{}", suggestion.id))
+        .description(format!(
+            "{}\n\nSuggested by <@{}>",
+            suggestion.content, suggestion.author_id
+        ))
+        .field("Status", status, false)
+        .field("Votes", format!("👍 {} · 👎 {}", up, down), false)
+        .colour(colour)
+}
@@ -0,0 +1,153 @@
+use crate::db::schema;
+use crate::Conn;
+
+use chrono::{DateTime, Utc};
+use diesel::result::Error;
+use diesel::{insert_into, prelude::*};
+use s::suggestion;
+use sc::suggestion_config;
+use schema::suggestion::dsl as s;
+use schema::suggestion_config::dsl as sc;
+use schema::suggestion_vote::dsl as sv;
+use strum_macros::{Display, EnumString};
+use sv::suggestion_vote;
+
+#[derive(EnumString, Display, Debug, PartialEq, Clone, Copy)]
+pub enum Status {
+    Open,
+    Approved,
+    Denied,
+    Implemented,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::suggestion)]
+pub struct Suggestion {
+    pub id: i32,
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub message_id: Option<i64>,
+    pub author_id: i64,
+    pub content: String,
+    pub status: String,
+    /// Staff's explanation of the status
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Associations, Debug, PartialEq)]
+#[diesel(belongs_to(Suggestion))]
+#[diesel(table_name = schema::suggestion_vote)]
+pub struct SuggestionVote {
+    pub id: i32,
+    pub suggestion_id: i32,
+    pub user_id: i64,
+    pub upvote: bool,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::suggestion)]
+pub struct NewSuggestion {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub author_id: i64,
+    pub content: String,
+}
+
+impl Suggestion {
+    pub fn find(conn: &mut Conn, guild_id: i64, id: i32) -> Result<Option<Suggestion>, Error> {
+        suggestion
+            .find(id)
+            .filter(s::guild_id.eq(guild_id))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn find_id(conn: &mut Conn, id: i32) -> Result<Option<Suggestion>, Error> {
+        suggestion.find(id).first(conn).optional()
+    }
+
+    pub fn status(&self) -> Status {
+        self.status.parse().unwrap_or(Status::Open)
+    }
+
+    pub fn set_message(&self, conn: &mut Conn, message_id: i64) -> Result<usize, Error> {
+        diesel::update(self)
+            .set(s::message_id.eq(message_id))
+            .execute(conn)
+    }
+
+    pub fn set_status(
+        &self,
+        conn: &mut Conn,
+        status: Status,
+        reason: Option<&str>,
+    ) -> Result<Suggestion, Error> {
+        diesel::update(self)
+            .set((s::status.eq(status.to_string()), s::reason.eq(reason)))
+            .get_result(conn)
+    }
+
+    /// Number of upvotes and downvotes
+    pub fn votes(&self, conn: &mut Conn) -> Result<(usize, usize), Error> {
+        let votes = SuggestionVote::belonging_to(self).load::<SuggestionVote>(conn)?;
+        let up = votes.iter().filter(|v| v.upvote).count();
+        Ok((up, votes.len() - up))
+    }
+
+    /// Casts a vote, or takes it back if the user already voted the same way.
+    /// Returns the user's vote afterwards.
+    pub fn vote(&self, conn: &mut Conn, user_id: i64, upvote: bool) -> Result<Option<bool>, Error> {
+        conn.transaction(|conn| {
+            let removed = diesel::delete(
+                suggestion_vote
+                    .filter(sv::suggestion_id.eq(self.id))
+                    .filter(sv::user_id.eq(user_id))
+                    .filter(sv::upvote.eq(upvote)),
+            )
+            .execute(conn)?;
+            if removed > 0 {
+                return Ok(None);
+            }
+
+            insert_into(suggestion_vote)
+                .values((
+                    sv::suggestion_id.eq(self.id),
+                    sv::user_id.eq(user_id),
+                    sv::upvote.eq(upvote),
+                ))
+                .on_conflict((sv::suggestion_id, sv::user_id))
+                .do_update()
+                .set(sv::upvote.eq(upvote))
+                .execute(conn)?;
+            Ok(Some(upvote))
+        })
+    }
+}
+
+impl NewSuggestion {
+    pub fn insert(&self, conn: &mut Conn) -> Result<Suggestion, Error> {
+        self.insert_into(suggestion).get_result(conn)
+    }
+}
+
+pub fn suggestion_channel(conn: &mut Conn, guild_id: i64) -> Result<Option<i64>, Error> {
+    suggestion_config
+        .select(sc::channel_id)
+        .find(guild_id)
+        .first(conn)
+        .optional()
+}
+
+pub fn set_suggestion_channel(
+    conn: &mut Conn,
+    guild_id: i64,
+    channel_id: i64,
+) -> Result<usize, Error> {
+    insert_into(suggestion_config)
+        .values((sc::guild_id.eq(guild_id), sc::channel_id.eq(channel_id)))
+        .on_conflict(sc::guild_id)
+        .do_update()
+        .set(sc::channel_id.eq(channel_id))
+        .execute(conn)
+}
@@ -0,0 +1,308 @@
+mod model;
+
+use crate::{AppError, Context, Data, Db};
+use diesel::result::DatabaseErrorKind;
+use model::*;
+use poise::serenity_prelude as serenity;
+use poise::Prefix;
+use serenity::{builder::*, model::prelude::*};
+
+/// Post and manage the server's tags
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only = true,
+    subcommands("show", "create", "edit", "delete", "info", "list")
+)]
+pub async fn tag(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Post a tag
+#[poise::command(slash_command, prefix_command)]
+pub async fn show(
+    ctx: Context<'_>,
+    #[autocomplete = "comp_tag"]
+    #[description = "Name of the tag"]
+    name: String,
+    #[rest]
+    #[description = "Text to fill in for {args}"]
+    args: Option<String>,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+
+    if let Some(tag) = find_and_use(&ctx.data().db, guild_id, name.clone()).await? {
+        let content = render(
+            &tag.content,
+            ctx.author().id,
+            ctx.channel_id(),
+            args.as_deref().unwrap_or(""),
+        );
+        ctx.send(
+            poise::CreateReply::default()
+                .content(content)
+                .allowed_mentions(allowed_mentions()),
+        )
+        .await?;
+    } else {
+        ctx.send(
+            poise::CreateReply::default()
+                .content(format!("The tag '{}' does not exist", &name))
+                .ephemeral(true),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Create a new tag
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn create(
+    ctx: Context<'_>,
+    #[description = "Name of the tag"] name: String,
+    #[rest]
+    #[description = "Content of the tag, may contain {user}, {channel} and {args}"]
+    content: String,
+) -> Result<(), AppError> {
+    if !valid_name(&name) {
+        ctx.say("Tag names must be a single word of at most 32 characters")
+            .await?;
+        return Ok(());
+    }
+
+    let new = NewTag {
+        guild_id: ctx.guild_id().unwrap().get() as i64,
+        name,
+        content,
+        owner_id: ctx.author().id.get() as i64,
+    };
+    let name = new.name.clone();
+    let created = ctx
+        .data()
+        .db
+        .run(move |conn| {
+            if Tag::find(conn, new.guild_id, &new.name)?.is_some() {
+                return Ok(false);
+            }
+            match new.insert(conn) {
+                Ok(_) => Ok(true),
+                Err(diesel::result::Error::DatabaseError(
+                    DatabaseErrorKind::UniqueViolation,
+                    _,
+                )) => Ok(false),
+                Err(e) => Err(e),
+            }
+        })
+        .await?;
+    let msg = if created {
+        format!("Created tag '{}'", &name)
+    } else {
+        format!("The tag '{}' already exists", &name)
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Change the content of a tag you own
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn edit(
+    ctx: Context<'_>,
+    #[autocomplete = "comp_tag"]
+    #[description = "Name of the tag to edit"]
+    name: String,
+    #[rest]
+    #[description = "New content of the tag"]
+    content: String,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+
+    let msg = match find(&ctx.data().db, guild_id, name.clone()).await? {
+        Some(tag) if can_manage(&ctx, &tag).await => {
+            let msg = format!("Updated tag '{}'", &tag.name);
+            ctx.data()
+                .db
+                .run(move |conn| tag.edit(conn, &content))
+                .await?;
+            msg
+        }
+        Some(tag) => format!("You do not own the tag '{}'", &tag.name),
+        None => format!("The tag '{}' does not exist", &name),
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Delete a tag you own
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[autocomplete = "comp_tag"]
+    #[description = "Name of the tag to delete"]
+    name: String,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+
+    let msg = match find(&ctx.data().db, guild_id, name.clone()).await? {
+        Some(tag) if can_manage(&ctx, &tag).await => {
+            let msg = format!("Deleted tag '{}'", &tag.name);
+            ctx.data().db.run(move |conn| tag.delete(conn)).await?;
+            msg
+        }
+        Some(tag) => format!("You do not own the tag '{}'", &tag.name),
+        None => format!("The tag '{}' does not exist", &name),
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Show who owns a tag and how often it was used
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn info(
+    ctx: Context<'_>,
+    #[autocomplete = "comp_tag"]
+    #[description = "Name of the tag"]
+    name: String,
+) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+
+    if let Some(tag) = find(&ctx.data().db, guild_id, name.clone()).await? {
+        let embed = CreateEmbed::default()
+            .title(&tag.name)
+            .description(&tag.content)
+            .field("Owner", format!("<@{}>", tag.owner_id), true)
+            .field("Uses", tag.uses.to_string(), true);
+        ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    } else {
+        ctx.say(format!("The tag '{}' does not exist", &name))
+            .await?;
+    }
+    Ok(())
+}
+
+/// List all tags of this server
+#[poise::command(slash_command, prefix_command, ephemeral = true)]
+pub async fn list(ctx: Context<'_>) -> Result<(), AppError> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let tags = ctx
+        .data()
+        .db
+        .run(move |conn| Tag::list(conn, guild_id))
+        .await?;
+
+    if tags.is_empty() {
+        ctx.say("No tags created yet!").await?;
+        return Ok(());
+    }
+
+    let names: Vec<_> = tags.iter().map(|t| format!("`{}`", t.name)).collect();
+    let embed = CreateEmbed::default()
+        .title(format!("Tags ({})", tags.len()))
+        .description(names.join(", "));
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Posts a tag when a message consists of a bot prefix followed by a tag name, e.g. `$rules`
+pub async fn on_message(
+    ctx: &serenity::Context,
+    msg: &Message,
+    framework: poise::FrameworkContext<'_, Data, AppError>,
+    data: &Data,
+) -> Result<(), AppError> {
+    let guild_id = match msg.guild_id {
+        Some(id) if !msg.author.bot => id,
+        _ => return Ok(()),
+    };
+    let options = framework.options();
+    let prefixes = options.prefix_options.prefix.as_deref().into_iter().chain(
+        options
+            .prefix_options
+            .additional_prefixes
+            .iter()
+            .filter_map(|p| match p {
+                Prefix::Literal(p) => Some(*p),
+                _ => None,
+            }),
+    );
+    let rest = match prefixes.filter_map(|p| msg.content.strip_prefix(p)).next() {
+        Some(rest) => rest.trim_start(),
+        None => return Ok(()),
+    };
+    let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+    // Real commands take precedence and are handled by the framework
+    if name.is_empty()
+        || options
+            .commands
+            .iter()
+            .any(|c| c.name == name || c.aliases.iter().any(|a| a == name))
+    {
+        return Ok(());
+    }
+
+    if let Some(tag) = find_and_use(&data.db, guild_id.get() as i64, name.to_owned()).await? {
+        let content = render(&tag.content, msg.author.id, msg.channel_id, args.trim());
+        msg.channel_id
+            .send_message(
+                ctx,
+                CreateMessage::new()
+                    .content(content)
+                    .allowed_mentions(allowed_mentions()),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+async fn find(db: &Db, guild_id: i64, name: String) -> Result<Option<Tag>, AppError> {
+    db.run(move |conn| Tag::find(conn, guild_id, &name)).await
+}
+
+/// Looks up a tag that is about to be posted and counts the use
+async fn find_and_use(db: &Db, guild_id: i64, name: String) -> Result<Option<Tag>, AppError> {
+    db.run(move |conn| {
+        let tag = Tag::find(conn, guild_id, &name)?;
+        if let Some(tag) = &tag {
+            tag.used(conn)?;
+        }
+        Ok(tag)
+    })
+    .await
+}
+
+/// Tags may ping users, e.g. through {user}, but never @everyone, @here or roles
+fn allowed_mentions() -> CreateAllowedMentions {
+    CreateAllowedMentions::new().all_users(true)
+}
+
+fn render(content: &str, user: UserId, channel: ChannelId, args: &str) -> String {
+    content
+        .replace("{user}", &format!("<@{}>", user))
+        .replace("{channel}", &format!("<#{}>", channel))
+        .replace("{args}", args)
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().count() <= 32 && !name.contains(char::is_whitespace)
+}
+
+async fn can_manage(ctx: &Context<'_>, tag: &Tag) -> bool {
+    let author = ctx.author().id;
+    if tag.owner_id == author.get() as i64 || ctx.framework().options().owners.contains(&author) {
+        return true;
+    }
+    ctx.author_member()
+        .await
+        .and_then(|m| m.permissions)
+        .map_or(false, |p| p.manage_messages())
+}
+
+async fn comp_tag(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let partial = partial.to_owned();
+    ctx.data()
+        .db
+        .run(move |conn| Tag::comp_tag(conn, guild_id, &partial))
+        .await
+        .unwrap_or(Vec::new())
+}
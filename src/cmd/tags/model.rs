@@ -0,0 +1,77 @@
+use crate::db::schema;
+use crate::Conn;
+
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel::sql_types::Text;
+use schema::tag::dsl as t;
+use t::tag;
+
+sql_function!(fn lower(s: Text) -> Text);
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::tag)]
+pub struct Tag {
+    pub id: i32,
+    pub guild_id: i64,
+    pub name: String,
+    pub content: String,
+    pub owner_id: i64,
+    pub uses: i32,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::tag)]
+pub struct NewTag {
+    pub guild_id: i64,
+    pub name: String,
+    pub content: String,
+    pub owner_id: i64,
+}
+
+impl Tag {
+    pub fn find(conn: &mut Conn, guild_id: i64, name: &str) -> Result<Option<Tag>, Error> {
+        tag.filter(t::guild_id.eq(guild_id))
+            .filter(lower(t::name).eq(name.to_lowercase()))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn list(conn: &mut Conn, guild_id: i64) -> Result<Vec<Tag>, Error> {
+        tag.filter(t::guild_id.eq(guild_id))
+            .order(t::name)
+            .load(conn)
+    }
+
+    pub fn comp_tag(conn: &mut Conn, guild_id: i64, partial: &str) -> Result<Vec<String>, Error> {
+        let pattern = format!("{}%", partial.to_lowercase());
+        tag.select(t::name)
+            .filter(t::guild_id.eq(guild_id))
+            .filter(lower(t::name).like(pattern))
+            .order(t::uses.desc())
+            .limit(25)
+            .get_results(conn)
+    }
+
+    pub fn edit(&self, conn: &mut Conn, content: &str) -> Result<usize, Error> {
+        diesel::update(self)
+            .set(t::content.eq(content))
+            .execute(conn)
+    }
+
+    pub fn delete(&self, conn: &mut Conn) -> Result<usize, Error> {
+        diesel::delete(self).execute(conn)
+    }
+
+    pub fn used(&self, conn: &mut Conn) -> Result<usize, Error> {
+        diesel::update(self)
+            .set(t::uses.eq(t::uses + 1))
+            .execute(conn)
+    }
+}
+
+impl NewTag {
+    pub fn insert(&self, conn: &mut Conn) -> Result<usize, Error> {
+        self.insert_into(tag).execute(conn)
+    }
+}
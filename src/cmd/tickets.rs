@@ -0,0 +1,514 @@
+mod model;
+
+use crate::component::{Component, Route, TooLong};
+use crate::{AppError, Bot, ComponentAction, Context, Data};
+use diesel::result::DatabaseErrorKind;
+use log::info;
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::{builder::*, model::prelude::*};
+
+/// Button opening a new ticket for whoever clicks it
+pub struct OpenTicket;
+
+impl Component for OpenTicket {
+    const ACTION: ComponentAction = ComponentAction::OpenTicket;
+    const MODULE: &'static str = "tickets";
+
+    fn encode(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        match fields {
+            [] => Some(OpenTicket),
+            _ => None,
+        }
+    }
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ComponentInteraction,
+        data: &'a Data,
+    ) -> poise::BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(open(ctx, event, data, self))
+    }
+}
+
+inventory::submit!(Route::of::<OpenTicket>());
+
+/// Button for staff to claim a ticket
+pub struct ClaimTicket {
+    pub id: i32,
+}
+
+impl Component for ClaimTicket {
+    const ACTION: ComponentAction = ComponentAction::ClaimTicket;
+    const MODULE: &'static str = "tickets";
+
+    fn encode(&self) -> Vec<String> {
+        vec![self.id.to_string()]
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        match fields {
+            [id] => Some(ClaimTicket {
+                id: id.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ComponentInteraction,
+        data: &'a Data,
+    ) -> poise::BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(claim(ctx, event, data, self))
+    }
+}
+
+inventory::submit!(Route::of::<ClaimTicket>());
+
+/// Button closing a ticket
+pub struct CloseTicket {
+    pub id: i32,
+}
+
+impl Component for CloseTicket {
+    const ACTION: ComponentAction = ComponentAction::CloseTicket;
+    const MODULE: &'static str = "tickets";
+
+    fn encode(&self) -> Vec<String> {
+        vec![self.id.to_string()]
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        match fields {
+            [id] => Some(CloseTicket {
+                id: id.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ComponentInteraction,
+        data: &'a Data,
+    ) -> poise::BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(close(ctx, event, data, self))
+    }
+}
+
+inventory::submit!(Route::of::<CloseTicket>());
+
+/// Button for staff to reopen a closed ticket
+pub struct ReopenTicket {
+    pub id: i32,
+}
+
+impl Component for ReopenTicket {
+    const ACTION: ComponentAction = ComponentAction::ReopenTicket;
+    const MODULE: &'static str = "tickets";
+
+    fn encode(&self) -> Vec<String> {
+        vec![self.id.to_string()]
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        match fields {
+            [id] => Some(ReopenTicket {
+                id: id.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ComponentInteraction,
+        data: &'a Data,
+    ) -> poise::BoxFuture<'a, Result<(), AppError>> {
+        Box::pin(reopen(ctx, event, data, self))
+    }
+}
+
+inventory::submit!(Route::of::<ReopenTicket>());
+
+/// Transcripts stop after this many messages
+const TRANSCRIPT_LIMIT: usize = 5000;
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "MANAGE_GUILD",
+    required_bot_permissions = "CREATE_PRIVATE_THREADS | MANAGE_THREADS",
+    subcommands("setup")
+)]
+pub async fn tickets(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Post a button that lets members open a support ticket in this channel
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn setup(
+    ctx: Context<'_>,
+    #[description = "Role that handles tickets"] staff_role: Option<Role>,
+    #[description = "Channel for transcripts of closed tickets"] log_channel: Option<GuildChannel>,
+    #[description = "Text shown above the button"] message: Option<String>,
+) -> Result<(), AppError> {
+    let config = TicketConfig {
+        guild_id: ctx.guild_id().unwrap().get() as i64,
+        staff_role_id: staff_role.map(|r| r.id.get() as i64),
+        log_channel_id: log_channel.map(|c| c.id.get() as i64),
+    };
+    ctx.data().db.run(move |conn| config.upsert(conn)).await?;
+
+    let button = CreateButton::new(OpenTicket.custom_id()?)
+        .style(ButtonStyle::Primary)
+        .emoji('🎫')
+        .label("Open Ticket");
+    ctx.channel_id()
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .embed(CreateEmbed::default().title("Support").description(
+                    message.unwrap_or("Need help? Open a ticket to talk to the staff.".to_owned()),
+                ))
+                .components(vec![CreateActionRow::Buttons(vec![button])]),
+        )
+        .await?;
+    ctx.say("Posted the ticket button").await?;
+    Ok(())
+}
+
+pub async fn open(
+    ctx: &Bot,
+    event: &ComponentInteraction,
+    data: &Data,
+    _button: OpenTicket,
+) -> Result<(), AppError> {
+    let guild_id = event.guild_id.unwrap().get() as i64;
+    let user_id = event.user.id.get() as i64;
+    // The row is claimed before the thread exists, so clicking twice can't open two tickets
+    let new = NewTicket {
+        guild_id,
+        opener_id: user_id,
+    };
+    let ticket = data
+        .db
+        .run(move |conn| match new.insert(conn) {
+            Ok(ticket) => Ok(Ok(ticket)),
+            Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                Ok(Err(Ticket::open_for(conn, guild_id, user_id)?))
+            }
+            Err(e) => Err(e),
+        })
+        .await?;
+    let ticket = match ticket {
+        Ok(ticket) => ticket,
+        Err(open) => {
+            let msg = match open.and_then(|t| t.channel_id) {
+                Some(channel_id) => format!("You already have an open ticket: <#{}>", channel_id),
+                None => "Your ticket is being opened.".to_owned(),
+            };
+            return reply(ctx, event, msg).await;
+        }
+    };
+    event
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::Defer(
+                serenity::CreateInteractionResponseMessage::new().ephemeral(true),
+            ),
+        )
+        .await?;
+
+    let thread = event
+        .channel_id
+        .create_thread(
+            ctx,
+            CreateThread::new(format!("ticket-{}", event.user.name))
+                .kind(ChannelType::PrivateThread)
+                .invitable(false),
+        )
+        .await;
+    let thread = match thread {
+        Ok(thread) => thread,
+        Err(e) => {
+            // Let them try again
+            data.db.run(move |conn| ticket.delete(conn)).await?;
+            return Err(e.into());
+        }
+    };
+    thread.id.add_thread_member(ctx, event.user.id).await?;
+    let thread_id = thread.id.get() as i64;
+    let (ticket, config) = data
+        .db
+        .run(move |conn| {
+            let ticket = ticket.set_channel(conn, thread_id)?;
+            Ok((ticket, TicketConfig::find(conn, guild_id)?))
+        })
+        .await?;
+    info!("Opened ticket {} for {}", ticket.id, event.user.id);
+
+    // Mentioning the staff role adds its members to the private thread
+    let staff = config
+        .and_then(|c| c.staff_role_id)
+        .map_or(String::new(), |r| format!(" <@&{}>", r));
+    thread
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .content(format!("<@{}>{}", event.user.id, staff))
+                .embed(CreateEmbed::default().title(format!("Ticket #{}", ticket.id)).description(
+                    "Describe your issue and someone from the staff will be with you shortly.",
+                ))
+                .components(vec![controls(&ticket)?]),
+        )
+        .await?;
+
+    event
+        .create_followup(
+            ctx,
+            CreateInteractionResponseFollowup::new()
+                .ephemeral(true)
+                .content(format!("Opened your ticket: <#{}>", thread.id)),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn claim(
+    ctx: &Bot,
+    event: &ComponentInteraction,
+    data: &Data,
+    button: ClaimTicket,
+) -> Result<(), AppError> {
+    let (ticket, config) = match load(data, button.id).await? {
+        Some((ticket, config)) if !ticket.closed => (ticket, config),
+        _ => return reply(ctx, event, "This ticket is closed.".to_owned()).await,
+    };
+    if !is_staff(event, config.as_ref()) {
+        return reply(ctx, event, "Only staff can claim tickets.".to_owned()).await;
+    }
+
+    let user_id = event.user.id.get() as i64;
+    let ticket = data.db.run(move |conn| ticket.claim(conn, user_id)).await?;
+    event
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new()
+                    .components(vec![controls(&ticket)?]),
+            ),
+        )
+        .await?;
+    event
+        .channel_id
+        .say(ctx, format!("<@{}> claimed this ticket", event.user.id))
+        .await?;
+    Ok(())
+}
+
+pub async fn close(
+    ctx: &Bot,
+    event: &ComponentInteraction,
+    data: &Data,
+    button: CloseTicket,
+) -> Result<(), AppError> {
+    let (ticket, config) = match load(data, button.id).await? {
+        Some((ticket, config)) if !ticket.closed => (ticket, config),
+        _ => return reply(ctx, event, "This ticket is already closed.".to_owned()).await,
+    };
+    if ticket.opener_id != event.user.id.get() as i64 && !is_staff(event, config.as_ref()) {
+        let msg = "Only the ticket's opener and staff can close it.".to_owned();
+        return reply(ctx, event, msg).await;
+    }
+    let thread = match ticket.thread() {
+        Some(thread) => thread,
+        None => return reply(ctx, event, "This ticket is still being opened.".to_owned()).await,
+    };
+    event
+        .create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+        .await?;
+
+    let ticket = data.db.run(move |conn| ticket.close(conn)).await?;
+    info!("Closing ticket {}", ticket.id);
+    let transcript = transcript(ctx, thread).await?;
+    let file_name = format!("ticket-{}.txt", ticket.id);
+
+    let reopen = CreateButton::new(ReopenTicket { id: ticket.id }.custom_id()?)
+        .style(ButtonStyle::Secondary)
+        .emoji('🔓')
+        .label("Reopen");
+    thread
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .content(format!("Ticket closed by <@{}>", event.user.id))
+                .add_file(CreateAttachment::bytes(transcript.clone(), &file_name))
+                .components(vec![CreateActionRow::Buttons(vec![reopen])]),
+        )
+        .await?;
+    if let Some(log) = config.and_then(|c| c.log_channel_id) {
+        let embed = CreateEmbed::default()
+            .title(format!("Ticket #{} closed", ticket.id))
+            .field("Opened by", format!("<@{}>", ticket.opener_id), true)
+            .field("Closed by", format!("<@{}>", event.user.id), true)
+            .field(
+                "Claimed by",
+                ticket
+                    .claimed_by
+                    .map_or("Nobody".to_owned(), |u| format!("<@{}>", u)),
+                true,
+            )
+            .field("Thread", format!("<#{}>", thread), false);
+        ChannelId::new(log as u64)
+            .send_message(
+                ctx,
+                CreateMessage::new()
+                    .embed(embed)
+                    .add_file(CreateAttachment::bytes(transcript, &file_name)),
+            )
+            .await?;
+    }
+    thread
+        .edit_thread(ctx, EditThread::new().archived(true).locked(true))
+        .await?;
+    Ok(())
+}
+
+pub async fn reopen(
+    ctx: &Bot,
+    event: &ComponentInteraction,
+    data: &Data,
+    button: ReopenTicket,
+) -> Result<(), AppError> {
+    let (ticket, config) = match load(data, button.id).await? {
+        Some((ticket, config)) if ticket.closed => (ticket, config),
+        _ => return reply(ctx, event, "This ticket is already open.".to_owned()).await,
+    };
+    if !is_staff(event, config.as_ref()) {
+        return reply(ctx, event, "Only staff can reopen tickets.".to_owned()).await;
+    }
+    let (guild_id, opener_id) = (ticket.guild_id, ticket.opener_id);
+    let other = data
+        .db
+        .run(move |conn| Ticket::open_for(conn, guild_id, opener_id))
+        .await?;
+    if other.is_some() {
+        let msg = "The opener of this ticket has opened another one since.".to_owned();
+        return reply(ctx, event, msg).await;
+    }
+
+    let thread = match ticket.thread() {
+        Some(thread) => thread,
+        None => return reply(ctx, event, "This ticket is still being opened.".to_owned()).await,
+    };
+    thread
+        .edit_thread(ctx, EditThread::new().archived(false).locked(false))
+        .await?;
+    data.db.run(move |conn| ticket.reopen(conn)).await?;
+    event
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new().components(vec![]),
+            ),
+        )
+        .await?;
+    thread
+        .say(ctx, format!("Ticket reopened by <@{}>", event.user.id))
+        .await?;
+    Ok(())
+}
+
+/// The ticket a button is for, together with the config of its guild
+async fn load(data: &Data, id: i32) -> Result<Option<(Ticket, Option<TicketConfig>)>, AppError> {
+    data.db
+        .run(move |conn| match Ticket::find_id(conn, id)? {
+            Some(ticket) => {
+                let config = TicketConfig::find(conn, ticket.guild_id)?;
+                Ok(Some((ticket, config)))
+            }
+            None => Ok(None),
+        })
+        .await
+}
+
+/// Whether the user handling the interaction has the staff role or may manage threads
+fn is_staff(event: &ComponentInteraction, config: Option<&TicketConfig>) -> bool {
+    let member = match &event.member {
+        Some(member) => member,
+        None => return false,
+    };
+    let staff_role = config
+        .and_then(|c| c.staff_role_id)
+        .map(|r| RoleId::new(r as u64));
+    member.permissions.map_or(false, |p| p.manage_threads())
+        || staff_role.map_or(false, |r| member.roles.contains(&r))
+}
+
+/// All messages of a channel as plain text, oldest first
+async fn transcript(ctx: &Bot, channel: ChannelId) -> Result<String, AppError> {
+    let mut messages = Vec::new();
+    let mut before = None;
+    while messages.len() < TRANSCRIPT_LIMIT {
+        let mut request = GetMessages::new().limit(100);
+        if let Some(id) = before {
+            request = request.before(id);
+        }
+        let page = channel.messages(ctx, request).await?;
+        before = match page.last() {
+            Some(msg) => Some(msg.id),
+            None => break,
+        };
+        messages.extend(page);
+    }
+
+    let mut text = String::new();
+    for msg in messages.iter().rev() {
+        text += &format!("[{}] {}: {}\n", msg.timestamp, msg.author.name, msg.content);
+        for attachment in &msg.attachments {
+            text += &format!("    {}\n", attachment.url);
+        }
+    }
+    Ok(text)
+}
+
+fn controls(ticket: &Ticket) -> Result<CreateActionRow, TooLong> {
+    let claim = CreateButton::new(ClaimTicket { id: ticket.id }.custom_id()?)
+        .style(ButtonStyle::Secondary)
+        .emoji('🙋')
+        .label(match ticket.claimed_by {
+            Some(_) => "Claimed",
+            None => "Claim",
+        })
+        .disabled(ticket.claimed_by.is_some());
+    let close = CreateButton::new(CloseTicket { id: ticket.id }.custom_id()?)
+        .style(ButtonStyle::Danger)
+        .emoji('🔒')
+        .label("Close");
+    Ok(CreateActionRow::Buttons(vec![claim, close]))
+}
+
+async fn reply(ctx: &Bot, event: &ComponentInteraction, content: String) -> Result<(), AppError> {
+    event
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::Message(
+                serenity::CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(content),
+            ),
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use crate::db::schema;
+use crate::Conn;
+
+use chrono::{DateTime, Utc};
+use diesel::result::Error;
+use diesel::{insert_into, prelude::*};
+use poise::serenity_prelude::ChannelId;
+use schema::ticket::dsl as t;
+use schema::ticket_config::dsl as tc;
+use t::ticket;
+use tc::ticket_config;
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::ticket)]
+pub struct Ticket {
+    pub id: i32,
+    pub guild_id: i64,
+    /// The ticket's private thread, unset while it is being created
+    pub channel_id: Option<i64>,
+    pub opener_id: i64,
+    pub claimed_by: Option<i64>,
+    pub closed: bool,
+    pub created_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::ticket)]
+pub struct NewTicket {
+    pub guild_id: i64,
+    pub opener_id: i64,
+}
+
+#[derive(Queryable, Selectable, Insertable, Debug, PartialEq)]
+#[diesel(table_name = schema::ticket_config)]
+pub struct TicketConfig {
+    pub guild_id: i64,
+    pub staff_role_id: Option<i64>,
+    /// Where transcripts of closed tickets are posted
+    pub log_channel_id: Option<i64>,
+}
+
+impl Ticket {
+    pub fn find_id(conn: &mut Conn, id: i32) -> Result<Option<Ticket>, Error> {
+        ticket.find(id).first(conn).optional()
+    }
+
+    /// The user's ticket that is still open, if any
+    pub fn open_for(conn: &mut Conn, guild_id: i64, user_id: i64) -> Result<Option<Ticket>, Error> {
+        ticket
+            .filter(t::guild_id.eq(guild_id))
+            .filter(t::opener_id.eq(user_id))
+            .filter(t::closed.eq(false))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn thread(&self) -> Option<ChannelId> {
+        self.channel_id.map(|c| ChannelId::new(c as u64))
+    }
+
+    pub fn set_channel(&self, conn: &mut Conn, channel_id: i64) -> Result<Ticket, Error> {
+        diesel::update(self)
+            .set(t::channel_id.eq(channel_id))
+            .get_result(conn)
+    }
+
+    pub fn delete(&self, conn: &mut Conn) -> Result<usize, Error> {
+        diesel::delete(self).execute(conn)
+    }
+
+    pub fn claim(&self, conn: &mut Conn, user_id: i64) -> Result<Ticket, Error> {
+        diesel::update(self)
+            .set(t::claimed_by.eq(user_id))
+            .get_result(conn)
+    }
+
+    pub fn close(&self, conn: &mut Conn) -> Result<Ticket, Error> {
+        diesel::update(self)
+            .set((t::closed.eq(true), t::closed_at.eq(Utc::now())))
+            .get_result(conn)
+    }
+
+    pub fn reopen(&self, conn: &mut Conn) -> Result<Ticket, Error> {
+        diesel::update(self)
+            .set((t::closed.eq(false), t::closed_at.eq(None::<DateTime<Utc>>)))
+            .get_result(conn)
+    }
+}
+
+impl NewTicket {
+    /// Fails with a unique violation if the user already has an open ticket
+    pub fn insert(&self, conn: &mut Conn) -> Result<Ticket, Error> {
+        // In a transaction of its own, so the violation doesn't abort a surrounding one
+        conn.transaction(|conn| self.insert_into(ticket).get_result(conn))
+    }
+}
+
+impl TicketConfig {
+    pub fn find(conn: &mut Conn, guild_id: i64) -> Result<Option<TicketConfig>, Error> {
+        ticket_config.find(guild_id).first(conn).optional()
+    }
+
+    pub fn upsert(&self, conn: &mut Conn) -> Result<usize, Error> {
+        insert_into(ticket_config)
+            .values(self)
+            .on_conflict(tc::guild_id)
+            .do_update()
+            .set((
+                tc::staff_role_id.eq(self.staff_role_id),
+                tc::log_channel_id.eq(self.log_channel_id),
+            ))
+            .execute(conn)
+    }
+}
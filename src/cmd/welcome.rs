@@ -0,0 +1,279 @@
+mod model;
+
+use crate::cmd::roles::model::RoleMenuRepo;
+use crate::{AppError, Context, Data};
+use log::info;
+use model::*;
+use poise::serenity_prelude as serenity;
+use serenity::{builder::*, model::prelude::*};
+
+#[poise::command(
+    slash_command,
+    guild_only = true,
+    ephemeral = true,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("set", "preview", "disable")
+)]
+pub async fn welcome(_ctx: Context<'_>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Set the message posted when members join or leave.
+///
+/// The message can contain {user}, {name}, {server}, {member_count} and {roles}, \
+/// which links to the role menu picked with `role_menu`.
+#[poise::command(slash_command, ephemeral = true)]
+#[allow(clippy::too_many_arguments)]
+pub async fn set(
+    ctx: Context<'_>,
+    #[description = "Message to post, see /help welcome set"] message: String,
+    #[description = "Channel to post in"] channel: Option<GuildChannel>,
+    #[description = "Set the goodbye message instead"] goodbye: Option<bool>,
+    #[description = "Post the message as an embed"] embed: Option<bool>,
+    #[description = "Also send the welcome message to the new member"] dm: Option<bool>,
+    #[autocomplete = "comp_rolemenu"]
+    #[description = "Role menu {roles} points to"]
+    role_menu: Option<String>,
+) -> Result<(), AppError> {
+    let kind = kind(goodbye);
+    let dm = dm.unwrap_or(false);
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+
+    if channel.is_none() && !dm {
+        ctx.say("Pick a channel or enable sending the message directly")
+            .await?;
+        return Ok(());
+    }
+    if dm && kind == GreetingKind::Goodbye {
+        ctx.say("Members who left can't be sent a goodbye directly")
+            .await?;
+        return Ok(());
+    }
+    if let Some(name) = &role_menu {
+        let menu = name.clone();
+        let found = ctx
+            .data()
+            .db
+            .run(move |conn| conn.find_menu(guild_id, &menu))
+            .await?;
+        if found.is_none() {
+            ctx.say(format!("The role menu '{}' does not exist", name))
+                .await?;
+            return Ok(());
+        }
+    }
+
+    let new = NewGreeting {
+        guild_id,
+        kind: kind.to_string(),
+        channel_id: channel.as_ref().map(|c| c.id.get() as i64),
+        template: message,
+        embed: embed.unwrap_or(false),
+        dm,
+        role_menu,
+    };
+    ctx.data().db.run(move |conn| new.upsert(conn)).await?;
+    ctx.say(format!(
+        "Updated the {} message, use /welcome preview to see it",
+        kind.to_string().to_lowercase()
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Show the welcome or goodbye message as if you just joined or left
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn preview(
+    ctx: Context<'_>,
+    #[description = "Preview the goodbye message instead"] goodbye: Option<bool>,
+) -> Result<(), AppError> {
+    let kind = kind(goodbye);
+    let guild_id = ctx.guild_id().unwrap();
+    let guild = guild_id.get() as i64;
+    let greeting = ctx
+        .data()
+        .db
+        .run(move |conn| Greeting::find(conn, guild, kind))
+        .await?;
+
+    match greeting {
+        Some(greeting) => {
+            let msg = render(ctx.serenity_context(), &greeting, guild_id, ctx.author()).await;
+            let reply = if greeting.embed {
+                poise::CreateReply::default().embed(embed(&msg, ctx.author()))
+            } else {
+                poise::CreateReply::default().content(msg)
+            };
+            ctx.send(reply).await?;
+        }
+        None => {
+            ctx.say(format!(
+                "There is no {} message",
+                kind.to_string().to_lowercase()
+            ))
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Stop posting the welcome or goodbye message
+#[poise::command(slash_command, ephemeral = true)]
+pub async fn disable(
+    ctx: Context<'_>,
+    #[description = "Disable the goodbye message instead"] goodbye: Option<bool>,
+) -> Result<(), AppError> {
+    let kind = kind(goodbye);
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let deleted = ctx
+        .data()
+        .db
+        .run(move |conn| Greeting::delete(conn, guild_id, kind))
+        .await?;
+    let msg = if deleted > 0 {
+        format!("Disabled the {} message", kind.to_string().to_lowercase())
+    } else {
+        format!("There is no {} message", kind.to_string().to_lowercase())
+    };
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+pub async fn on_member_join(
+    ctx: &serenity::Context,
+    member: &Member,
+    data: &Data,
+) -> Result<(), AppError> {
+    greet(
+        ctx,
+        GreetingKind::Welcome,
+        member.guild_id,
+        &member.user,
+        data,
+    )
+    .await
+}
+
+pub async fn on_member_leave(
+    ctx: &serenity::Context,
+    guild_id: GuildId,
+    user: &User,
+    data: &Data,
+) -> Result<(), AppError> {
+    greet(ctx, GreetingKind::Goodbye, guild_id, user, data).await
+}
+
+async fn greet(
+    ctx: &serenity::Context,
+    kind: GreetingKind,
+    guild_id: GuildId,
+    user: &User,
+    data: &Data,
+) -> Result<(), AppError> {
+    if user.bot {
+        return Ok(());
+    }
+    let guild = guild_id.get() as i64;
+    let greeting = match data
+        .db
+        .run(move |conn| Greeting::find(conn, guild, kind))
+        .await?
+    {
+        Some(greeting) => greeting,
+        None => return Ok(()),
+    };
+
+    let text = render(ctx, &greeting, guild_id, user).await;
+    let msg = if greeting.embed {
+        CreateMessage::new().embed(embed(&text, user))
+    } else {
+        CreateMessage::new().content(&text)
+    };
+    if greeting.dm {
+        if let Err(e) = user.direct_message(ctx, msg.clone()).await {
+            info!("Could not send {} to {}: {:?}", kind, user.id, e);
+        }
+    }
+    if let Some(channel) = greeting.channel_id {
+        ChannelId::new(channel as u64)
+            .send_message(ctx, msg)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Fills in the placeholders of a greeting
+async fn render(
+    ctx: &serenity::Context,
+    greeting: &Greeting,
+    guild_id: GuildId,
+    user: &User,
+) -> String {
+    let cached = ctx
+        .cache
+        .guild(guild_id)
+        .map(|g| (g.name.clone(), g.member_count));
+    let (server, member_count) = match cached {
+        Some((name, count)) => (name, count.to_string()),
+        None => match guild_id.to_partial_guild_with_counts(ctx).await {
+            Ok(g) => (
+                g.name,
+                g.approximate_member_count
+                    .map_or("?".to_owned(), |c| c.to_string()),
+            ),
+            Err(_) => ("the server".to_owned(), "?".to_owned()),
+        },
+    };
+
+    let mut text = greeting
+        .template
+        .replace("{user}", &format!("<@{}>", user.id))
+        .replace("{name}", &user.name)
+        .replace("{server}", &server)
+        .replace("{member_count}", &member_count);
+    if text.contains("{roles}") {
+        text = text.replace("{roles}", &roles_link(ctx, greeting, guild_id).await);
+    }
+    text
+}
+
+/// Clickable mention of the /roles command, along with the role menu to pick.
+/// Module commands are registered per guild, so that is where its id is.
+async fn roles_link(ctx: &serenity::Context, greeting: &Greeting, guild_id: GuildId) -> String {
+    let command = match guild_id.get_commands(ctx).await {
+        Ok(commands) => commands
+            .iter()
+            .find(|c| c.name == "roles")
+            .map(|c| format!("</roles:{}>", c.id)),
+        Err(_) => None,
+    };
+    let command = command.unwrap_or("`/roles`".to_owned());
+    match &greeting.role_menu {
+        Some(menu) => format!("{} with the menu '{}'", command, menu),
+        None => command,
+    }
+}
+
+fn embed(text: &str, user: &User) -> CreateEmbed {
+    CreateEmbed::default()
+        .description(text)
+        .thumbnail(user.face())
+}
+
+fn kind(goodbye: Option<bool>) -> GreetingKind {
+    if goodbye.unwrap_or(false) {
+        GreetingKind::Goodbye
+    } else {
+        GreetingKind::Welcome
+    }
+}
+
+async fn comp_rolemenu(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    let partial = partial.to_owned();
+    ctx.data()
+        .db
+        .run(move |conn| conn.menu_names(guild_id, &partial))
+        .await
+        .unwrap_or_default()
+}
@@ -0,0 +1,82 @@
+use crate::db::schema;
+use crate::Conn;
+
+use diesel::result::Error;
+use diesel::{insert_into, prelude::*};
+use gr::greeting;
+use schema::greeting::dsl as gr;
+use strum_macros::{Display, EnumString};
+
+#[derive(EnumString, Display, Debug, PartialEq, Clone, Copy)]
+pub enum GreetingKind {
+    Welcome,
+    Goodbye,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = schema::greeting)]
+pub struct Greeting {
+    pub id: i32,
+    pub guild_id: i64,
+    pub kind: String,
+    pub channel_id: Option<i64>,
+    pub template: String,
+    pub embed: bool,
+    /// Also send the message to the member directly
+    pub dm: bool,
+    /// Name of the role menu `{roles}` points to
+    pub role_menu: Option<String>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = schema::greeting)]
+pub struct NewGreeting {
+    pub guild_id: i64,
+    pub kind: String,
+    pub channel_id: Option<i64>,
+    pub template: String,
+    pub embed: bool,
+    pub dm: bool,
+    pub role_menu: Option<String>,
+}
+
+impl Greeting {
+    pub fn find(
+        conn: &mut Conn,
+        guild_id: i64,
+        kind: GreetingKind,
+    ) -> Result<Option<Greeting>, Error> {
+        greeting
+            .filter(gr::guild_id.eq(guild_id))
+            .filter(gr::kind.eq(kind.to_string()))
+            .first(conn)
+            .optional()
+    }
+
+    pub fn delete(conn: &mut Conn, guild_id: i64, kind: GreetingKind) -> Result<usize, Error> {
+        diesel::delete(
+            greeting
+                .filter(gr::guild_id.eq(guild_id))
+                .filter(gr::kind.eq(kind.to_string())),
+        )
+        .execute(conn)
+    }
+}
+
+impl NewGreeting {
+    /// Inserts the greeting, replacing the guild's previous one of the same kind
+    pub fn upsert(&self, conn: &mut Conn) -> Result<usize, Error> {
+        insert_into(greeting)
+            .values(self)
+            .on_conflict((gr::guild_id, gr::kind))
+            .do_update()
+            .set((
+                gr::channel_id.eq(self.channel_id),
+                gr::template.eq(&self.template),
+                gr::embed.eq(self.embed),
+                gr::dm.eq(self.dm),
+                gr::role_menu.eq(&self.role_menu),
+            ))
+            .execute(conn)
+    }
+}
@@ -0,0 +1,303 @@
+//! Typed custom ids for message components.
+//!
+//! A custom id looks like `Action/v1/field/field|expiry|user|signature`. Every [`ComponentAction`]
+//! has a payload type implementing [`Component`] that turns it into such an id and back. The
+//! payload registers its handler with `inventory::submit!`, so `on_event` doesn't need to know
+//! about any of them.
+//!
+//! Ids are signed with an HMAC so nobody can craft buttons the bot didn't post. They can
+//! also stop working after a while or only work for a single user. Buttons posted before ids were
+//! signed keep working for the actions that opt in with [`Component::LEGACY`].
+
+use crate::{AppError, Bot, ComponentAction, Data};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use log::warn;
+use poise::serenity_prelude as serenity;
+use poise::BoxFuture;
+use serenity::{ComponentInteraction, UserId};
+use sha2::Sha256;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+#[cfg(test)]
+mod tests;
+
+/// Discord rejects longer custom ids
+pub const MAX_LEN: usize = 100;
+/// Bytes of the HMAC kept in an id, a truncated one is still far too long to guess
+const SIGNATURE_LEN: usize = 10;
+
+static KEY: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Sets the secret ids are signed with, changing it invalidates every posted component
+pub fn set_key(key: &str) {
+    KEY.set(key.as_bytes().to_vec())
+        .expect("Component key was already set");
+}
+
+pub trait Component: Sized {
+    const ACTION: ComponentAction;
+    /// The module the component belongs to, it does nothing in guilds that turned it off
+    const MODULE: &'static str;
+    /// Bump this when the fields change, so older buttons get a notice instead of being misread
+    const VERSION: u32 = 1;
+    /// Whether unsigned `Action/v1/field` ids, from buttons posted before ids were signed, are
+    /// still accepted. Only for actions whose handler checks who clicked by itself.
+    const LEGACY: bool = false;
+
+    fn encode(&self) -> Vec<String>;
+
+    /// Returns `None` if the fields don't make up a valid payload
+    fn decode(fields: &[&str]) -> Option<Self>;
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ComponentInteraction,
+        data: &'a Data,
+    ) -> BoxFuture<'a, Result<(), AppError>>;
+
+    fn custom_id(&self) -> Result<String, TooLong> {
+        self.custom_id_for(None, None)
+    }
+
+    /// An id that only `user` may use and that stops working at `expires`
+    fn custom_id_for(
+        &self,
+        user: Option<UserId>,
+        expires: Option<DateTime<Utc>>,
+    ) -> Result<String, TooLong> {
+        let mut parts = vec![Self::ACTION.to_string(), format!("v{}", Self::VERSION)];
+        parts.extend(self.encode());
+        sign(&parts, user, expires)
+    }
+}
+
+/// A custom id that came out longer than Discord allows
+#[derive(Debug)]
+pub struct TooLong(String);
+
+impl fmt::Display for TooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Custom id '{}' is too long", self.0)
+    }
+}
+
+impl std::error::Error for TooLong {}
+
+#[derive(Debug)]
+pub(crate) enum Rejection {
+    Forged,
+    Outdated,
+    Expired,
+    WrongUser,
+}
+
+impl Rejection {
+    /// What to tell the user, `what` is the kind of thing they used
+    pub(crate) fn message(&self, what: &str) -> String {
+        match self {
+            Rejection::Forged | Rejection::Outdated => {
+                format!("This {} is outdated, try running the command again.", what)
+            }
+            Rejection::Expired => {
+                format!("This {} has expired, try running the command again.", what)
+            }
+            Rejection::WrongUser => format!("This {} isn't meant for you.", what),
+        }
+    }
+}
+
+/// Joins the parts of an id and signs it together with its expiry and user
+pub(crate) fn sign(
+    parts: &[String],
+    user: Option<UserId>,
+    expires: Option<DateTime<Utc>>,
+) -> Result<String, TooLong> {
+    let body = format!(
+        "{}|{}|{}",
+        parts.join("/"),
+        expires.map_or(String::new(), |e| e.timestamp().to_string()),
+        user.map_or(String::new(), |u| u.to_string())
+    );
+    let signature = &mac(&body).finalize().into_bytes()[..SIGNATURE_LEN];
+    let id = format!("{}|{}", body, URL_SAFE_NO_PAD.encode(signature));
+    if id.len() > MAX_LEN {
+        return Err(TooLong(id));
+    }
+    Ok(id)
+}
+
+fn mac(body: &str) -> Hmac<Sha256> {
+    let key = KEY.get().expect("Component key was not set");
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(body.as_bytes());
+    mac
+}
+
+/// The action an id is meant for, if it is one of ours at all
+pub(crate) fn action<T: FromStr>(custom_id: &str) -> Option<T> {
+    let action = custom_id.split(['/', '|']).next().unwrap_or_default();
+    T::from_str(action).ok()
+}
+
+/// Checks the signature, expiry and user of an id and returns its version and fields
+pub(crate) fn unseal(custom_id: &str, user: UserId) -> Result<(u32, Vec<&str>), Rejection> {
+    let (body, signature) = custom_id.rsplit_once('|').ok_or(Rejection::Forged)?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| Rejection::Forged)?;
+    // A shorter signature would be easy to guess
+    if signature.len() != SIGNATURE_LEN {
+        return Err(Rejection::Forged);
+    }
+    mac(body)
+        .verify_truncated_left(&signature)
+        .map_err(|_| Rejection::Forged)?;
+
+    let mut parts = body.rsplitn(3, '|');
+    let (bound, expires, payload) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(bound), Some(expires), Some(payload)) => (bound, expires, payload),
+        _ => return Err(Rejection::Forged),
+    };
+    if let Ok(expires) = expires.parse::<i64>() {
+        if Utc::now().timestamp() >= expires {
+            return Err(Rejection::Expired);
+        }
+    }
+    if !bound.is_empty() && bound != user.to_string() {
+        return Err(Rejection::WrongUser);
+    }
+
+    let mut fields = payload.split('/').skip(1);
+    let version = fields
+        .next()
+        .and_then(|tag| tag.strip_prefix('v'))
+        .and_then(|v| v.parse().ok())
+        .ok_or(Rejection::Outdated)?;
+    Ok((version, fields.collect()))
+}
+
+/// Reads an unsigned id as it was posted before ids were signed, `Action/v1/field` or, older
+/// still, `Action/field`. Returns `None` if it is a signed one.
+fn unsigned(custom_id: &str) -> Option<(u32, Vec<&str>)> {
+    if custom_id.contains('|') {
+        return None;
+    }
+    let rest: Vec<&str> = custom_id.split('/').skip(1).collect();
+    match rest
+        .first()
+        .and_then(|tag| tag.strip_prefix('v'))
+        .and_then(|v| v.parse().ok())
+    {
+        Some(version) => Some((version, rest[1..].to_vec())),
+        None => Some((1, rest)),
+    }
+}
+
+type Handler = for<'a> fn(
+    &'a Bot,
+    &'a ComponentInteraction,
+    &'a Data,
+    u32,
+    &'a [&'a str],
+) -> Option<BoxFuture<'a, Result<(), AppError>>>;
+
+pub struct Route {
+    action: ComponentAction,
+    module: &'static str,
+    legacy: bool,
+    handler: Handler,
+}
+
+impl Route {
+    pub const fn of<C: Component>() -> Route {
+        Route {
+            action: C::ACTION,
+            module: C::MODULE,
+            legacy: C::LEGACY,
+            handler: decode_and_handle::<C>,
+        }
+    }
+}
+
+inventory::collect!(Route);
+
+fn decode_and_handle<'a, C: Component>(
+    ctx: &'a Bot,
+    event: &'a ComponentInteraction,
+    data: &'a Data,
+    version: u32,
+    fields: &'a [&'a str],
+) -> Option<BoxFuture<'a, Result<(), AppError>>> {
+    if version != C::VERSION {
+        return None;
+    }
+    Some(C::decode(fields)?.handle(ctx, event, data))
+}
+
+/// Hands a component interaction to the handler registered for its action.
+/// Ids that aren't actions, like the ones collectors wait for, are left alone.
+pub async fn dispatch(
+    ctx: &Bot,
+    event: &ComponentInteraction,
+    data: &Data,
+) -> Result<(), AppError> {
+    let custom_id = &event.data.custom_id;
+    let action: ComponentAction = match action(custom_id) {
+        Some(action) => action,
+        None => return Ok(()),
+    };
+    let route = inventory::iter::<Route>
+        .into_iter()
+        .find(|r| r.action == action);
+    if let Some(route) = route {
+        if !data.enabled(event.guild_id, route.module).await? {
+            return reply(ctx, event, &turned_off(route.module)).await;
+        }
+    }
+    let legacy = route.filter(|r| r.legacy).and_then(|_| unsigned(custom_id));
+    let (version, fields) = match (unseal(custom_id, event.user.id), legacy) {
+        (Ok(unsealed), _) => unsealed,
+        (Err(Rejection::Forged), Some(unsigned)) => unsigned,
+        (Err(rejection), _) => {
+            if let Rejection::Forged = rejection {
+                warn!(
+                    "Rejected forged custom id {} of {}",
+                    custom_id, event.user.id
+                );
+            }
+            return reply(ctx, event, &rejection.message("button")).await;
+        }
+    };
+
+    let handler = route.and_then(|r| (r.handler)(ctx, event, data, version, &fields));
+    match handler {
+        Some(handler) => handler.await,
+        None => {
+            warn!("Could not decode custom id {}", custom_id);
+            reply(ctx, event, &Rejection::Outdated.message("button")).await
+        }
+    }
+}
+
+pub(crate) fn turned_off(module: &str) -> String {
+    format!("The {} module is turned off in this server", module)
+}
+
+async fn reply(ctx: &Bot, event: &ComponentInteraction, content: &str) -> Result<(), AppError> {
+    event
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::Message(
+                serenity::CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(content),
+            ),
+        )
+        .await?;
+    Ok(())
+}
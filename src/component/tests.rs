@@ -0,0 +1,148 @@
+use super::*;
+use crate::testing::{self, FakeDiscord};
+use chrono::Duration;
+use serde_json::{json, Value};
+
+const USER: u64 = 10;
+
+fn parts(fields: &[&str]) -> Vec<String> {
+    fields.iter().map(|f| f.to_string()).collect()
+}
+
+fn vote_id(user: Option<UserId>, expires: Option<DateTime<Utc>>) -> String {
+    testing::init();
+    sign(&parts(&["VoteInPoll", "v1", "7", "2"]), user, expires).unwrap()
+}
+
+/// The ephemeral reply `dispatch` gave to a button press with `custom_id`
+async fn dispatch_reply(custom_id: &str) -> Value {
+    let data = testing::data();
+    let fake = FakeDiscord::start().await;
+    let event = testing::button_press(custom_id, 1, 2, USER);
+    dispatch(&fake.bot(), &event, &data).await.unwrap();
+    let requests = fake.requests_to("POST");
+    assert_eq!(requests.len(), 1, "{:#?}", requests);
+    assert_eq!(requests[0].body["data"]["flags"], json!(64));
+    requests[0].body["data"]["content"].clone()
+}
+
+#[test]
+fn signed_ids_unseal_to_their_fields() {
+    let id = vote_id(None, None);
+    assert!(id.starts_with("VoteInPoll/v1/7/2||"), "{}", id);
+    let (version, fields) = unseal(&id, UserId::new(USER)).unwrap();
+    assert_eq!(version, 1);
+    assert_eq!(fields, vec!["7", "2"]);
+    assert_eq!(
+        action::<ComponentAction>(&id),
+        Some(ComponentAction::VoteInPoll)
+    );
+}
+
+#[test]
+fn tampered_fields_are_forged() {
+    let id = vote_id(None, None).replacen("/7/", "/8/", 1);
+    assert!(matches!(
+        unseal(&id, UserId::new(USER)),
+        Err(Rejection::Forged)
+    ));
+
+    // Moving the signature to another user or expiry breaks it too
+    let id = vote_id(None, None).replacen("||", &format!("||{}", USER + 1), 1);
+    assert!(matches!(
+        unseal(&id, UserId::new(USER)),
+        Err(Rejection::Forged)
+    ));
+}
+
+#[test]
+fn short_signatures_are_forged() {
+    let id = vote_id(None, None);
+    let (body, signature) = id.rsplit_once('|').unwrap();
+
+    let truncated = format!("{}|{}", body, &signature[..signature.len() - 3]);
+    assert!(matches!(
+        unseal(&truncated, UserId::new(USER)),
+        Err(Rejection::Forged)
+    ));
+    // A prefix of the real signature that still decodes
+    let bytes = URL_SAFE_NO_PAD.decode(signature).unwrap();
+    let short = format!("{}|{}", body, URL_SAFE_NO_PAD.encode(&bytes[..4]));
+    assert!(matches!(
+        unseal(&short, UserId::new(USER)),
+        Err(Rejection::Forged)
+    ));
+    let empty = format!("{}|", body);
+    assert!(matches!(
+        unseal(&empty, UserId::new(USER)),
+        Err(Rejection::Forged)
+    ));
+}
+
+#[test]
+fn expired_ids_are_rejected() {
+    let id = vote_id(None, Some(Utc::now() - Duration::seconds(1)));
+    assert!(matches!(
+        unseal(&id, UserId::new(USER)),
+        Err(Rejection::Expired)
+    ));
+    let id = vote_id(None, Some(Utc::now() + Duration::minutes(5)));
+    assert!(unseal(&id, UserId::new(USER)).is_ok());
+}
+
+#[test]
+fn ids_for_a_user_reject_everyone_else() {
+    let id = vote_id(Some(UserId::new(USER)), None);
+    assert!(unseal(&id, UserId::new(USER)).is_ok());
+    assert!(matches!(
+        unseal(&id, UserId::new(USER + 1)),
+        Err(Rejection::WrongUser)
+    ));
+}
+
+#[test]
+fn ids_without_a_version_are_outdated() {
+    testing::init();
+    let id = sign(&parts(&["VoteInPoll", "7", "2"]), None, None).unwrap();
+    assert!(matches!(
+        unseal(&id, UserId::new(USER)),
+        Err(Rejection::Outdated)
+    ));
+}
+
+#[test]
+fn legacy_ids_are_parsed() {
+    assert_eq!(
+        unsigned("DeleteFromFavorites/v1/2/100"),
+        Some((1, vec!["2", "100"]))
+    );
+    assert_eq!(
+        unsigned("DeleteFromFavorites/2/100"),
+        Some((1, vec!["2", "100"]))
+    );
+    assert_eq!(unsigned("DeleteFromFavorites/v3/2"), Some((3, vec!["2"])));
+    assert_eq!(unsigned(&vote_id(None, None)), None);
+}
+
+#[test]
+fn long_ids_are_refused() {
+    testing::init();
+    let long = "x".repeat(MAX_LEN);
+    let err = sign(&parts(&["VoteInPoll", "v1", &long]), None, None).unwrap_err();
+    assert!(err.to_string().contains(&long));
+    assert!(sign(&parts(&["VoteInPoll", "v1", "7"]), None, None).is_ok());
+}
+
+#[tokio::test]
+async fn unsigned_ids_are_refused_for_actions_without_legacy_support() {
+    let reply = dispatch_reply("VoteInPoll/v1/7/2").await;
+    assert_eq!(reply, json!(Rejection::Forged.message("button")));
+}
+
+#[tokio::test]
+async fn other_versions_are_outdated() {
+    testing::init();
+    let id = sign(&parts(&["VoteInPoll", "v2", "7", "2"]), None, None).unwrap();
+    let reply = dispatch_reply(&id).await;
+    assert_eq!(reply, json!(Rejection::Outdated.message("button")));
+}
@@ -0,0 +1,324 @@
+//! Settings from an optional TOML file, every one of which can be overridden by an environment
+//! variable. Problems are collected so a bad config reports all of them at once.
+
+use crate::cmd;
+use crate::db::PoolConfig;
+use poise::serenity_prelude::{GatewayIntents, GuildId, UserId};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests;
+
+pub const USAGE: &str = "Usage: bean-bot [--config <path>] [--check-config]";
+
+/// What the command line asked for
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    /// `--config <path>`, falls back to `BEAN_BOT_CONFIG`
+    pub config: Option<PathBuf>,
+    /// `--check-config`, validate the config and exit without connecting to anything
+    pub check: bool,
+}
+
+impl Args {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" | "-c" => {
+                    let path = args.next().ok_or("--config needs a path")?;
+                    parsed.config = Some(path.into());
+                }
+                "--check-config" => parsed.check = true,
+                _ => return Err(format!("Unknown argument '{}'", arg)),
+            }
+        }
+        if parsed.config.is_none() {
+            parsed.config = std::env::var_os("BEAN_BOT_CONFIG").map(PathBuf::from);
+        }
+        Ok(parsed)
+    }
+}
+
+/// The config file as written, everything is optional so the environment can fill it in
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct File {
+    token: Option<String>,
+    owners: Option<Vec<u64>>,
+    component_key: Option<String>,
+    log_level: Option<String>,
+    prefixes: Option<Vec<String>>,
+    intents: Option<Vec<String>>,
+    modules: Option<Vec<String>>,
+    dev_guilds: Option<Vec<u64>>,
+    database: DatabaseFile,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct DatabaseFile {
+    url: Option<String>,
+    pool_size: Option<u32>,
+    min_idle: Option<u32>,
+    idle_timeout: Option<u64>,
+    timeout: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    /// `token` or `DISCORD_TOKEN`
+    pub token: String,
+    /// `owners` or `BEAN_BOT_OWNERS`, comma separated
+    pub owners: HashSet<UserId>,
+    /// `component_key` or `BEAN_BOT_COMPONENT_KEY`, signs the custom ids of buttons
+    pub component_key: String,
+    /// `log_level` or `BEAN_BOT_LOG_LEVEL`, `RUST_LOG` still wins if set
+    pub log_level: log::LevelFilter,
+    /// `prefixes` or `BEAN_BOT_PREFIXES`, comma separated, the first is the main one
+    pub prefixes: Vec<String>,
+    /// `intents` or `BEAN_BOT_INTENTS`, comma separated names like `guild_members`
+    pub intents: GatewayIntents,
+    /// `modules` or `BEAN_BOT_MODULES`, comma separated, all of them if not set
+    pub modules: HashSet<&'static str>,
+    /// `dev_guilds` or `BEAN_BOT_DEV_GUILDS`, comma separated. If set, every command is
+    /// registered in just these guilds, which unlike global registration takes effect right away.
+    pub dev_guilds: HashSet<GuildId>,
+    /// `database.url` or `DATABASE_URL`
+    pub database_url: String,
+    /// The rest of `[database]`, or `BEAN_BOT_DB_POOL_SIZE`, `BEAN_BOT_DB_MIN_IDLE`,
+    /// `BEAN_BOT_DB_IDLE_TIMEOUT` and `BEAN_BOT_DB_TIMEOUT`
+    pub pool: PoolConfig,
+}
+
+#[derive(Debug)]
+pub struct ConfigError(Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid config:")?;
+        for error in &self.0 {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads the file at `path` if given and applies the environment on top of it
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        let text = match path {
+            Some(path) => Some(std::fs::read_to_string(path).map_err(|e| {
+                ConfigError(vec![format!("Could not read {}: {}", path.display(), e)])
+            })?),
+            None => None,
+        };
+        Config::parse(text.as_deref(), |key| std::env::var(key).ok())
+    }
+
+    /// Builds the config from the contents of a file and a way to look up environment variables
+    pub fn parse(
+        text: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config, ConfigError> {
+        let file: File = match text {
+            Some(text) => toml::from_str(text).map_err(|e| ConfigError(vec![e.to_string()]))?,
+            None => File::default(),
+        };
+        let mut sources = Sources {
+            env,
+            errors: Vec::new(),
+        };
+        let defaults = PoolConfig::default();
+
+        let token = sources.required("token", "DISCORD_TOKEN", file.token);
+        let owners = sources
+            .list("BEAN_BOT_OWNERS", file.owners)
+            .unwrap_or_default();
+        let component_key = sources.required(
+            "component_key",
+            "BEAN_BOT_COMPONENT_KEY",
+            file.component_key,
+        );
+        let log_level = sources
+            .value("BEAN_BOT_LOG_LEVEL", file.log_level)
+            .unwrap_or_else(|| "info".to_owned());
+        let prefixes = sources
+            .list("BEAN_BOT_PREFIXES", file.prefixes)
+            .unwrap_or_else(|| vec!["🫘".to_owned(), "$".to_owned(), "beans".to_owned()]);
+        let intents = sources
+            .list("BEAN_BOT_INTENTS", file.intents)
+            .unwrap_or_else(|| {
+                vec![
+                    "non_privileged".to_owned(),
+                    "message_content".to_owned(),
+                    "guild_members".to_owned(),
+                ]
+            });
+        let modules = sources.list("BEAN_BOT_MODULES", file.modules);
+        let dev_guilds = sources
+            .list("BEAN_BOT_DEV_GUILDS", file.dev_guilds)
+            .unwrap_or_default();
+        let database_url = sources.required("database.url", "DATABASE_URL", file.database.url);
+        let pool = PoolConfig {
+            max_size: sources
+                .parsed("BEAN_BOT_DB_POOL_SIZE", file.database.pool_size)
+                .unwrap_or(defaults.max_size),
+            min_idle: sources
+                .parsed("BEAN_BOT_DB_MIN_IDLE", file.database.min_idle)
+                .or(defaults.min_idle),
+            idle_timeout: sources
+                .parsed("BEAN_BOT_DB_IDLE_TIMEOUT", file.database.idle_timeout)
+                .map(Duration::from_secs)
+                .or(defaults.idle_timeout),
+            timeout: sources
+                .parsed("BEAN_BOT_DB_TIMEOUT", file.database.timeout)
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.timeout),
+        };
+
+        let mut errors = sources.errors;
+        let log_level = log::LevelFilter::from_str(&log_level).unwrap_or_else(|_| {
+            errors.push(format!(
+                "'{}' is not a log level, use one of off, error, warn, info, debug or trace",
+                log_level
+            ));
+            log::LevelFilter::Info
+        });
+        if prefixes.iter().any(String::is_empty) {
+            errors.push("Prefixes can't be empty".to_owned());
+        }
+        let intents = intents
+            .iter()
+            .filter_map(|name| {
+                let intent = intent(name);
+                if intent.is_none() {
+                    errors.push(format!("Unknown intent '{}'", name));
+                }
+                intent
+            })
+            .fold(GatewayIntents::empty(), |all, intent| all | intent);
+        if owners.contains(&0) {
+            errors.push("owners can't contain 0, it is not a user id".to_owned());
+        }
+        if dev_guilds.contains(&0) {
+            errors.push("dev_guilds can't contain 0, it is not a guild id".to_owned());
+        }
+        if pool.max_size == 0 {
+            errors.push("database.pool_size must be at least 1".to_owned());
+        }
+        if pool.min_idle.map_or(false, |idle| idle > pool.max_size) {
+            errors.push("database.min_idle can't be more than database.pool_size".to_owned());
+        }
+        if pool.timeout.is_zero() {
+            errors.push("database.timeout must be at least 1 second".to_owned());
+        }
+
+        let known: Vec<_> = cmd::modules().into_iter().map(|m| m.name).collect();
+        let modules = match modules {
+            Some(modules) => modules
+                .iter()
+                .filter_map(|name| {
+                    let module = known.iter().find(|m| *m == name).copied();
+                    if module.is_none() {
+                        errors.push(format!(
+                            "Unknown module '{}', the modules are {}",
+                            name,
+                            known.join(", ")
+                        ));
+                    }
+                    module
+                })
+                .collect(),
+            None => known.iter().copied().collect(),
+        };
+
+        if !errors.is_empty() {
+            return Err(ConfigError(errors));
+        }
+        Ok(Config {
+            token: token.unwrap_or_default(),
+            owners: owners.into_iter().map(UserId::new).collect(),
+            component_key: component_key.unwrap_or_default(),
+            log_level,
+            prefixes,
+            intents,
+            modules,
+            dev_guilds: dev_guilds.into_iter().map(GuildId::new).collect(),
+            database_url: database_url.unwrap_or_default(),
+            pool,
+        })
+    }
+}
+
+/// An intent by its name in the Discord docs, in any case.
+/// `non_privileged` stands for all intents that don't need to be enabled in the developer portal.
+fn intent(name: &str) -> Option<GatewayIntents> {
+    let name = name.trim().to_uppercase();
+    if name == "NON_PRIVILEGED" {
+        return Some(GatewayIntents::non_privileged());
+    }
+    GatewayIntents::from_name(&name)
+}
+
+/// Looks up overrides and remembers what could not be parsed
+struct Sources<E> {
+    env: E,
+    errors: Vec<String>,
+}
+
+impl<E: Fn(&str) -> Option<String>> Sources<E> {
+    fn value(&self, key: &str, file: Option<String>) -> Option<String> {
+        (self.env)(key).or(file)
+    }
+
+    fn required(&mut self, name: &str, key: &str, file: Option<String>) -> Option<String> {
+        let value = self.value(key, file).filter(|v| !v.is_empty());
+        if value.is_none() {
+            self.errors.push(format!(
+                "Missing {}, set it in the file or with {}",
+                name, key
+            ));
+        }
+        value
+    }
+
+    fn parsed<T: FromStr>(&mut self, key: &str, file: Option<T>) -> Option<T> {
+        match (self.env)(key) {
+            Some(value) => match value.trim().parse() {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    self.errors
+                        .push(format!("{} is not a valid number: '{}'", key, value));
+                    file
+                }
+            },
+            None => file,
+        }
+    }
+
+    /// A comma separated list from the environment, or the list from the file
+    fn list<T: FromStr>(&mut self, key: &str, file: Option<Vec<T>>) -> Option<Vec<T>> {
+        let value = match (self.env)(key) {
+            Some(value) => value,
+            None => return file,
+        };
+        let mut items = Vec::new();
+        for item in value.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            match item.parse() {
+                Ok(item) => items.push(item),
+                Err(_) => self
+                    .errors
+                    .push(format!("{} contains an invalid entry '{}'", key, item)),
+            }
+        }
+        Some(items)
+    }
+}
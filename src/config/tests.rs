@@ -0,0 +1,135 @@
+use super::*;
+use std::collections::HashMap;
+
+const MINIMAL: &str = r#"
+token = "file-token"
+component_key = "file-key"
+
+[database]
+url = "postgres://localhost/beans"
+"#;
+
+fn parse(text: Option<&str>, env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+    let env: HashMap<_, _> = env
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    Config::parse(text, |key| env.get(key).cloned())
+}
+
+fn errors(res: Result<Config, ConfigError>) -> Vec<String> {
+    match res {
+        Ok(config) => panic!("Expected errors, got {:?}", config),
+        Err(ConfigError(errors)) => errors,
+    }
+}
+
+#[test]
+fn defaults_fill_in_a_minimal_file() {
+    let config = parse(Some(MINIMAL), &[]).unwrap();
+    assert_eq!(config.token, "file-token");
+    assert!(config.owners.is_empty());
+    assert_eq!(config.prefixes, vec!["🫘", "$", "beans"]);
+    assert_eq!(
+        config.intents,
+        GatewayIntents::non_privileged()
+            | GatewayIntents::MESSAGE_CONTENT
+            | GatewayIntents::GUILD_MEMBERS
+    );
+    assert_eq!(config.modules.len(), cmd::modules().len());
+    assert_eq!(config.pool.max_size, PoolConfig::default().max_size);
+    assert_eq!(config.log_level, log::LevelFilter::Info);
+    assert!(config.dev_guilds.is_empty());
+}
+
+#[test]
+fn environment_overrides_file() {
+    let config = parse(
+        Some(MINIMAL),
+        &[
+            ("DISCORD_TOKEN", "env-token"),
+            ("BEAN_BOT_OWNERS", "1, 2"),
+            ("BEAN_BOT_MODULES", "levels,tags"),
+            ("BEAN_BOT_DB_TIMEOUT", "3"),
+            ("BEAN_BOT_DEV_GUILDS", "5"),
+        ],
+    )
+    .unwrap();
+    assert_eq!(config.token, "env-token");
+    assert_eq!(config.component_key, "file-key");
+    assert_eq!(
+        config.owners,
+        HashSet::from([UserId::new(1), UserId::new(2)])
+    );
+    assert_eq!(config.modules, HashSet::from(["levels", "tags"]));
+    assert_eq!(config.pool.timeout, Duration::from_secs(3));
+    assert_eq!(config.dev_guilds, HashSet::from([GuildId::new(5)]));
+}
+
+#[test]
+fn environment_alone_is_enough() {
+    let config = parse(
+        None,
+        &[
+            ("DISCORD_TOKEN", "env-token"),
+            ("BEAN_BOT_COMPONENT_KEY", "env-key"),
+            ("DATABASE_URL", "postgres://localhost/beans"),
+            ("BEAN_BOT_INTENTS", "guilds,Guild_Messages"),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        config.intents,
+        GatewayIntents::GUILDS | GatewayIntents::GUILD_MESSAGES
+    );
+}
+
+#[test]
+fn all_problems_are_reported() {
+    let text = r#"
+log_level = "loud"
+intents = ["guilds", "telepathy"]
+modules = ["levels", "karaoke"]
+
+[database]
+pool_size = 2
+min_idle = 5
+"#;
+    let errors = errors(parse(Some(text), &[("BEAN_BOT_DB_TIMEOUT", "soon")]));
+    assert_eq!(errors.len(), 8, "{:#?}", errors);
+    assert!(errors.iter().any(|e| e.contains("DISCORD_TOKEN")));
+    assert!(errors.iter().any(|e| e.contains("BEAN_BOT_COMPONENT_KEY")));
+    assert!(errors.iter().any(|e| e.contains("DATABASE_URL")));
+    assert!(errors.iter().any(|e| e.contains("'soon'")));
+    assert!(errors.iter().any(|e| e.contains("'loud'")));
+    assert!(errors.iter().any(|e| e.contains("'telepathy'")));
+    assert!(errors.iter().any(|e| e.contains("'karaoke'")));
+    assert!(errors.iter().any(|e| e.contains("min_idle")));
+}
+
+#[test]
+fn zero_ids_are_rejected() {
+    let text = format!("owners = [1, 0]\n{}", MINIMAL);
+    let errors = errors(parse(Some(&text), &[("BEAN_BOT_DEV_GUILDS", "0")]));
+    assert_eq!(errors.len(), 2, "{:#?}", errors);
+    assert!(errors[0].starts_with("owners"), "{}", errors[0]);
+    assert!(errors[1].starts_with("dev_guilds"), "{}", errors[1]);
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+    let text = format!("prefix = \"!\"\n{}", MINIMAL);
+    let errors = errors(parse(Some(&text), &[]));
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("prefix"), "{}", errors[0]);
+}
+
+#[test]
+fn arguments() {
+    let args = |args: &[&str]| Args::parse(args.iter().map(|a| a.to_string()));
+    let parsed = args(&["--check-config", "--config", "bot.toml"]).unwrap();
+    assert!(parsed.check);
+    assert_eq!(parsed.config, Some(PathBuf::from("bot.toml")));
+    assert!(args(&["--config"]).is_err());
+    assert!(args(&["--verbose"]).is_err());
+}
@@ -1,23 +1,286 @@
 pub mod schema;
 
-use diesel::{
-    backend::Backend,
-    r2d2::{ConnectionManager, Pool, R2D2Connection},
-};
+use crate::{AppError, Conn, ConnType};
+use diesel::connection::SimpleConnection;
+use diesel::r2d2::{Builder, ConnectionManager, Pool};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use log::warn;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+// Every schema change needs a migration in both sets
+#[cfg(feature = "postgres")]
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/postgres");
+#[cfg(feature = "sqlite")]
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/sqlite");
 
-pub fn connect<C: R2D2Connection + 'static>() -> Pool<ConnectionManager<C>> {
-    let url = std::env::var("DATABASE_URL").expect("Missing DATABASE_URL");
-    let manager = ConnectionManager::<C>::new(&url);
+/// Column types the schema uses that are named differently depending on the backend
+pub mod sql_types {
+    #[cfg(feature = "sqlite")]
+    pub use diesel::sql_types::TimestamptzSqlite as Timestamptz;
+}
+
+/// Waiting longer than this for a connection is worth a warning
+const SLOW_WAIT: Duration = Duration::from_millis(500);
+
+/// Pool settings, the `[database]` table of the config
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// `pool_size`, most connections open at once
+    pub max_size: u32,
+    /// `min_idle`, connections kept open even when unused
+    pub min_idle: Option<u32>,
+    /// `idle_timeout`, seconds until unused connections are closed
+    pub idle_timeout: Option<Duration>,
+    /// `timeout`, seconds to wait for a connection and that each statement may take
+    pub timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 10,
+            min_idle: None,
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Makes Postgres cancel statements that run longer than the timeout, so a query that timed out
+/// is really stopped instead of finishing after its caller gave up
+#[cfg(feature = "postgres")]
+#[derive(Debug)]
+struct StatementTimeout(Duration);
+
+#[cfg(feature = "postgres")]
+impl diesel::r2d2::CustomizeConnection<ConnType, diesel::r2d2::Error> for StatementTimeout {
+    fn on_acquire(&self, conn: &mut ConnType) -> Result<(), diesel::r2d2::Error> {
+        conn.batch_execute(&format!("set statement_timeout = {}", self.0.as_millis()))
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+/// Whether the database gave up on a query because of the timeout
+fn timed_out(err: &diesel::result::Error) -> bool {
+    match err {
+        diesel::result::Error::DatabaseError(_, info) => {
+            #[cfg(feature = "postgres")]
+            let timeout = "canceling statement due to statement timeout";
+            #[cfg(feature = "sqlite")]
+            let timeout = "database is locked";
+            info.message().contains(timeout)
+        }
+        _ => false,
+    }
+}
+
+/// Whether the pool gave up because every connection stayed busy, r2d2 only
+/// adds a reason when it failed to open a connection instead
+fn pool_exhausted(err: &diesel::r2d2::PoolError) -> bool {
+    err.to_string() == "timed out waiting for connection"
+}
+
+/// Settings SQLite only keeps per connection
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+struct SqlitePragmas {
+    busy_timeout: Duration,
+}
+
+#[cfg(feature = "sqlite")]
+impl diesel::r2d2::CustomizeConnection<ConnType, diesel::r2d2::Error> for SqlitePragmas {
+    fn on_acquire(&self, conn: &mut ConnType) -> Result<(), diesel::r2d2::Error> {
+        // Cascading deletes need foreign keys, which SQLite ignores by default.
+        // Writers wait for each other instead of failing right away.
+        conn.batch_execute(&format!(
+            "pragma foreign_keys = on; pragma busy_timeout = {}; pragma journal_mode = wal;",
+            self.busy_timeout.as_millis()
+        ))
+        .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+/// Keeps everything a test does inside a transaction that is never committed
+#[cfg(test)]
+#[derive(Debug)]
+struct TestTransaction;
 
-    // C::establish(&url).unwrap_or_else(|_| panic!("Error connecting to {}", url))
-    Pool::builder()
-        .build(manager)
-        .unwrap_or_else(|_| panic!("Could not build connection pool to {}", url))
+#[cfg(test)]
+impl diesel::r2d2::CustomizeConnection<ConnType, diesel::r2d2::Error> for TestTransaction {
+    fn on_acquire(&self, conn: &mut ConnType) -> Result<(), diesel::r2d2::Error> {
+        use diesel::Connection;
+
+        #[cfg(feature = "sqlite")]
+        diesel::r2d2::CustomizeConnection::on_acquire(
+            &SqlitePragmas {
+                busy_timeout: Duration::from_secs(5),
+            },
+            conn,
+        )?;
+        conn.begin_test_transaction()
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+/// Returned when a query didn't finish within the configured timeout
+#[derive(Debug)]
+pub struct Timeout(Duration);
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Database query timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+/// How long queries waited for a connection
+#[derive(Debug, Default)]
+struct Metrics {
+    checkouts: AtomicU64,
+    total_wait_us: AtomicU64,
+    max_wait_us: AtomicU64,
+    timeouts: AtomicU64,
+}
+
+impl Metrics {
+    fn record_wait(&self, wait: Duration) {
+        let us = wait.as_micros() as u64;
+        self.checkouts.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_us.fetch_add(us, Ordering::Relaxed);
+        self.max_wait_us.fetch_max(us, Ordering::Relaxed);
+        if wait >= SLOW_WAIT {
+            warn!("Waited {:?} for a database connection", wait);
+        }
+    }
 }
 
-pub fn run_pending_migrations<DB: Backend, M: MigrationHarness<DB>>(conn: &mut M) {
-    conn.run_pending_migrations(MIGRATIONS).unwrap();
+/// A snapshot of the pool and its metrics
+#[derive(Debug)]
+pub struct Stats {
+    pub connections: u32,
+    pub idle: u32,
+    pub checkouts: u64,
+    pub average_wait: Duration,
+    pub max_wait: Duration,
+    pub timeouts: u64,
+}
+
+/// The connection pool, queries run on tokio's blocking threads so they never stall the gateway
+#[derive(Clone)]
+pub struct Database {
+    pool: Pool<ConnectionManager<ConnType>>,
+    timeout: Duration,
+    metrics: Arc<Metrics>,
+}
+
+impl Database {
+    pub fn connect(url: &str, config: &PoolConfig) -> Database {
+        let builder = Pool::builder()
+            .max_size(config.max_size)
+            .min_idle(config.min_idle)
+            .idle_timeout(config.idle_timeout)
+            .connection_timeout(config.timeout);
+        #[cfg(feature = "postgres")]
+        let builder = builder.connection_customizer(Box::new(StatementTimeout(config.timeout)));
+        #[cfg(feature = "sqlite")]
+        let builder = builder.connection_customizer(Box::new(SqlitePragmas {
+            busy_timeout: config.timeout,
+        }));
+        Database::build(builder, url, config.timeout)
+    }
+
+    /// A single connection to `TEST_DATABASE_URL` whose changes are rolled back once it's dropped.
+    /// Panics if the variable isn't set, SQLite falls back to an in-memory database instead.
+    #[cfg(test)]
+    pub fn for_tests() -> Database {
+        let url = std::env::var("TEST_DATABASE_URL").ok();
+        #[cfg(feature = "sqlite")]
+        let url = url.or(Some(":memory:".to_owned()));
+        let url = url.expect("Set TEST_DATABASE_URL to a database the tests can use");
+        let timeout = Duration::from_secs(30);
+        let builder = Pool::builder()
+            .max_size(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connection_timeout(timeout)
+            .connection_customizer(Box::new(TestTransaction));
+        let db = Database::build(builder, &url, timeout);
+        db.run_pending_migrations()
+            .expect("Could not migrate the test database");
+        db
+    }
+
+    fn build(
+        builder: Builder<ConnectionManager<ConnType>>,
+        url: &str,
+        timeout: Duration,
+    ) -> Database {
+        let pool = builder
+            .build(ConnectionManager::new(url))
+            .unwrap_or_else(|_| panic!("Could not build connection pool to {}", url));
+        Database {
+            pool,
+            timeout,
+            metrics: Default::default(),
+        }
+    }
+
+    /// Runs `query` with a connection from the pool on a blocking thread.
+    /// The pool and the database enforce the timeout, by giving up on the connection or cancelling
+    /// the statement, so a [`Timeout`] means the query did not go through.
+    pub async fn run<T, F>(&self, query: F) -> Result<T, AppError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Conn) -> Result<T, diesel::result::Error> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+        let metrics = self.metrics.clone();
+        let timeout = self.timeout;
+        tokio::task::spawn_blocking(move || -> Result<T, AppError> {
+            let start = Instant::now();
+            let mut conn = match pool.get() {
+                Ok(conn) => conn,
+                Err(err) if pool_exhausted(&err) => {
+                    metrics.timeouts.fetch_add(1, Ordering::Relaxed);
+                    warn!("Could not get a database connection: {}", err);
+                    return Err(Timeout(timeout).into());
+                }
+                Err(err) => return Err(err.into()),
+            };
+            metrics.record_wait(start.elapsed());
+            query(&mut conn).map_err(|err| {
+                if timed_out(&err) {
+                    metrics.timeouts.fetch_add(1, Ordering::Relaxed);
+                    Timeout(timeout).into()
+                } else {
+                    err.into()
+                }
+            })
+        })
+        .await?
+    }
+
+    pub fn stats(&self) -> Stats {
+        let state = self.pool.state();
+        let checkouts = self.metrics.checkouts.load(Ordering::Relaxed);
+        let total_wait = self.metrics.total_wait_us.load(Ordering::Relaxed);
+        Stats {
+            connections: state.connections,
+            idle: state.idle_connections,
+            checkouts,
+            average_wait: Duration::from_micros(total_wait.checked_div(checkouts).unwrap_or(0)),
+            max_wait: Duration::from_micros(self.metrics.max_wait_us.load(Ordering::Relaxed)),
+            timeouts: self.metrics.timeouts.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Brings the schema up to date, this blocks and is only meant for startup
+    pub fn run_pending_migrations(&self) -> Result<(), AppError> {
+        self.pool.get()?.run_pending_migrations(MIGRATIONS)?;
+        Ok(())
+    }
 }
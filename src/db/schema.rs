@@ -1,16 +1,304 @@
 // @generated automatically by Diesel CLI.
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    autoresponse (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        trigger -> Text,
+        kind -> Text,
+        actions -> Text,
+        response -> Nullable<Text>,
+        timeout -> Nullable<Int8>,
+        channel_id -> Nullable<Int8>,
+        cooldown -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    bean_account (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        user_id -> Int8,
+        balance -> Int8,
+        last_daily -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    bean_ledger (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        user_id -> Int8,
+        amount -> Int8,
+        kind -> Text,
+        counterparty_id -> Nullable<Int8>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    birthday (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        user_id -> Int8,
+        day -> Int4,
+        month -> Int4,
+        year -> Nullable<Int4>,
+        timezone -> Text,
+        celebrated_year -> Nullable<Int4>,
+        role_until -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    birthday_config (guild_id) {
+        guild_id -> Int8,
+        channel_id -> Nullable<Int8>,
+        role_id -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    command_registration (scope) {
+        scope -> Int8,
+        hash -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    disabled_module (guild_id, module) {
+        guild_id -> Int8,
+        module -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
     fav_msgs (id) {
         id -> Int4,
         user_id -> Int8,
         guild_id -> Int8,
         channel_id -> Int8,
         message_id -> Int8,
+        note -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    giveaway (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        channel_id -> Int8,
+        message_id -> Nullable<Int8>,
+        host_id -> Int8,
+        prize -> Text,
+        winners -> Int4,
+        required_role_id -> Nullable<Int8>,
+        min_account_age -> Nullable<Int8>,
+        ends_at -> Timestamptz,
+        ended -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    giveaway_entry (id) {
+        id -> Int4,
+        giveaway_id -> Int4,
+        user_id -> Int8,
+        won -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    greeting (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        kind -> Text,
+        channel_id -> Nullable<Int8>,
+        template -> Text,
+        embed -> Bool,
+        dm -> Bool,
+        role_menu -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    level_reward (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        level -> Int4,
+        role_id -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    message_log_config (guild_id) {
+        guild_id -> Int8,
+        channel_id -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    message_log_ignore (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        target_id -> Int8,
+        kind -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    mod_cases (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        case_number -> Int4,
+        action -> Text,
+        user_id -> Int8,
+        moderator_id -> Int8,
+        reason -> Nullable<Text>,
+        duration -> Nullable<Int8>,
+        expires_at -> Nullable<Timestamptz>,
+        resolved -> Bool,
+        log_message_id -> Nullable<Int8>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    mod_config (guild_id) {
+        guild_id -> Int8,
+        log_channel_id -> Nullable<Int8>,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    poll (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        channel_id -> Int8,
+        message_id -> Nullable<Int8>,
+        author_id -> Int8,
+        question -> Text,
+        multiple -> Bool,
+        anonymous -> Bool,
+        closes_at -> Nullable<Timestamptz>,
+        closed -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    poll_option (id) {
+        id -> Int4,
+        poll_id -> Int4,
+        position -> Int4,
+        label -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    poll_vote (id) {
+        id -> Int4,
+        poll_id -> Int4,
+        user_id -> Int8,
+        position -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    quote (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        number -> Int4,
+        channel_id -> Int8,
+        message_id -> Int8,
+        author_id -> Int8,
+        author_name -> Text,
+        author_avatar -> Nullable<Text>,
+        content -> Text,
+        image_url -> Nullable<Text>,
+        saved_by -> Int8,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    reminder (id) {
+        id -> Int4,
+        user_id -> Int8,
+        guild_id -> Nullable<Int8>,
+        channel_id -> Int8,
+        message_id -> Nullable<Int8>,
+        content -> Text,
+        remind_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
     role_menu (id) {
         id -> Int4,
         guild_id -> Int8,
@@ -20,6 +308,9 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
     role_option (id) {
         id -> Int4,
         role_id -> Int8,
@@ -29,6 +320,179 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    shop_item (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        role_id -> Int8,
+        price -> Int8,
+        duration -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    shop_purchase (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        user_id -> Int8,
+        role_id -> Int8,
+        price -> Int8,
+        expires_at -> Nullable<Timestamptz>,
+        active -> Bool,
+        refunded -> Bool,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    suggestion (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        channel_id -> Int8,
+        message_id -> Nullable<Int8>,
+        author_id -> Int8,
+        content -> Text,
+        status -> Text,
+        reason -> Nullable<Text>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    suggestion_config (guild_id) {
+        guild_id -> Int8,
+        channel_id -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    suggestion_vote (id) {
+        id -> Int4,
+        suggestion_id -> Int4,
+        user_id -> Int8,
+        upvote -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    tag (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        name -> Text,
+        content -> Text,
+        owner_id -> Int8,
+        uses -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    ticket (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        channel_id -> Nullable<Int8>,
+        opener_id -> Int8,
+        claimed_by -> Nullable<Int8>,
+        closed -> Bool,
+        created_at -> Timestamptz,
+        closed_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    ticket_config (guild_id) {
+        guild_id -> Int8,
+        staff_role_id -> Nullable<Int8>,
+        log_channel_id -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    xp_channel (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        channel_id -> Int8,
+        multiplier -> Float8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::db::sql_types::*;
+
+    xp_member (id) {
+        id -> Int4,
+        guild_id -> Int8,
+        user_id -> Int8,
+        xp -> Int8,
+        last_award -> Timestamptz,
+    }
+}
+
+diesel::joinable!(giveaway_entry -> giveaway (giveaway_id));
+diesel::joinable!(poll_option -> poll (poll_id));
+diesel::joinable!(poll_vote -> poll (poll_id));
 diesel::joinable!(role_option -> role_menu (role_menu_id));
+diesel::joinable!(suggestion_vote -> suggestion (suggestion_id));
 
-diesel::allow_tables_to_appear_in_same_query!(fav_msgs, role_menu, role_option,);
+diesel::allow_tables_to_appear_in_same_query!(
+    autoresponse,
+    bean_account,
+    bean_ledger,
+    birthday,
+    birthday_config,
+    command_registration,
+    disabled_module,
+    fav_msgs,
+    giveaway,
+    giveaway_entry,
+    greeting,
+    level_reward,
+    message_log_config,
+    message_log_ignore,
+    mod_cases,
+    mod_config,
+    poll,
+    poll_option,
+    poll_vote,
+    quote,
+    reminder,
+    role_menu,
+    role_option,
+    shop_item,
+    shop_purchase,
+    suggestion,
+    suggestion_config,
+    suggestion_vote,
+    tag,
+    ticket,
+    ticket_config,
+    xp_channel,
+    xp_member,
+);
@@ -1,30 +1,119 @@
 mod cmd;
 use cmd::*;
+mod component;
+mod config;
 mod db;
+mod modal;
+mod scheduler;
+#[cfg(test)]
+mod testing;
+mod util;
 
-use diesel::{
-    prelude::PgConnection,
-    r2d2::{ConnectionManager, Pool, PooledConnection},
-};
+#[cfg(all(feature = "postgres", feature = "sqlite"))]
+compile_error!("The postgres and sqlite features can't be enabled together");
+#[cfg(not(any(feature = "postgres", feature = "sqlite")))]
+compile_error!("Enable either the postgres or the sqlite feature");
+
+use config::Config;
+#[cfg(feature = "postgres")]
+use diesel::pg::PgConnection;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+#[cfg(feature = "sqlite")]
+use diesel::sqlite::SqliteConnection;
 use log::info;
 use poise::{serenity_prelude as serenity, Prefix};
-use serenity::{model::prelude::*, FullEvent, GatewayIntents};
-use std::str::FromStr;
-use std::{collections::HashSet, env::var};
+use serenity::{model::prelude::*, FullEvent};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 use strum_macros::{Display, EnumString, IntoStaticStr};
 
 type Context<'a> = poise::Context<'a, Data, AppError>;
 type AppError = Box<dyn std::error::Error + Send + Sync>;
+#[cfg(feature = "postgres")]
 type ConnType = PgConnection;
+#[cfg(feature = "sqlite")]
+type ConnType = SqliteConnection;
 type Conn = PooledConnection<ConnectionManager<ConnType>>;
+type Db = db::Database;
+
+/// What component and modal handlers use to talk to Discord.
+/// Unlike a `serenity::Context` it needs no gateway connection, so tests can build one.
+#[derive(Clone)]
+pub struct Bot {
+    pub http: Arc<serenity::Http>,
+    pub cache: Arc<serenity::Cache>,
+}
+
+impl From<&serenity::Context> for Bot {
+    fn from(ctx: &serenity::Context) -> Self {
+        Bot {
+            http: ctx.http.clone(),
+            cache: ctx.cache.clone(),
+        }
+    }
+}
+
+impl serenity::CacheHttp for Bot {
+    fn http(&self) -> &serenity::Http {
+        &self.http
+    }
+
+    fn cache(&self) -> Option<&Arc<serenity::Cache>> {
+        Some(&self.cache)
+    }
+}
+
+impl AsRef<serenity::Http> for Bot {
+    fn as_ref(&self) -> &serenity::Http {
+        &self.http
+    }
+}
+
+impl AsRef<serenity::Cache> for Bot {
+    fn as_ref(&self) -> &serenity::Cache {
+        &self.cache
+    }
+}
 
 pub struct Data {
-    db: Pool<ConnectionManager<ConnType>>,
+    db: Db,
+    msg_cache: message_log::MessageCache,
+    responders: autoresponder::MatcherCache,
+    modules: module_settings::Modules,
+    /// The module of every command that belongs to one, by name
+    command_modules: HashMap<String, &'static str>,
+    /// Guilds that get every command while none are registered globally, empty outside dev mode
+    dev_guilds: HashSet<GuildId>,
+}
+
+impl Data {
+    /// Whether `module` is on, both in the config and in the guild if there is one.
+    /// Commands and events of modules that are off are ignored.
+    async fn enabled(&self, guild_id: Option<GuildId>, module: &str) -> Result<bool, AppError> {
+        self.modules.enabled(guild_id, module).await
+    }
 }
 
-#[derive(EnumString, IntoStaticStr, Display)]
+#[derive(EnumString, IntoStaticStr, Display, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ComponentAction {
     DeleteFromFavorites,
+    CancelReminder,
+    VoteInPoll,
+    EnterGiveaway,
+    VoteOnSuggestion,
+    OpenTicket,
+    ClaimTicket,
+    CloseTicket,
+    ReopenTicket,
+}
+
+#[derive(EnumString, IntoStaticStr, Display, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ModalAction {
+    AddFavorite,
+    EditRoleOption,
+    ComposeMessage,
 }
 
 async fn on_error(error: poise::FrameworkError<'_, Data, AppError>) {
@@ -33,6 +122,8 @@ async fn on_error(error: poise::FrameworkError<'_, Data, AppError>) {
         poise::FrameworkError::Command { error, ctx } => {
             println!("Error in command `{}`: {:?}", ctx.command().name, error,)
         }
+        // The check already told the user the module is off
+        poise::FrameworkError::CommandCheckFailed { error: None, .. } => {}
         error => {
             if let Err(e) = poise::builtins::on_error(error).await {
                 println!("Error while handling error: {}", e)
@@ -43,26 +134,90 @@ async fn on_error(error: poise::FrameworkError<'_, Data, AppError>) {
 
 async fn on_event(
     event: &FullEvent,
-    _framework: poise::FrameworkContext<'_, Data, AppError>,
+    framework: poise::FrameworkContext<'_, Data, AppError>,
     data: &Data,
 ) -> Result<(), AppError> {
     match event {
         FullEvent::InteractionCreate { ctx, interaction } => match interaction {
-            Interaction::Component(i) => {
-                let params: Vec<&str> = i.data.custom_id.split('/').collect();
-                if let Some((action, args)) = params.split_first() {
-                    match ComponentAction::from_str(&action) {
-                        Ok(ComponentAction::DeleteFromFavorites) => {
-                            fav_msgs::delete(ctx, i, data, &args).await
-                        }
-                        _ => Ok(()),
-                    }
-                } else {
-                    Ok(())
-                }
-            }
+            Interaction::Component(i) => component::dispatch(&ctx.into(), i, data).await,
+            Interaction::Modal(i) => modal::dispatch(&ctx.into(), i, data).await,
             _ => Ok(()),
         },
+        FullEvent::GuildCreate { ctx, guild, .. } => {
+            let commands = &framework.options().commands;
+            registration::register_in_guild(ctx, commands, data, guild.id, false).await?;
+            Ok(())
+        }
+        FullEvent::Message { ctx, new_message } => {
+            let guild_id = new_message.guild_id;
+            if data.enabled(guild_id, "message_log").await? {
+                message_log::on_message(new_message, data);
+            }
+            if data.enabled(guild_id, "autoresponder").await?
+                && autoresponder::on_message(ctx, new_message, data).await?
+            {
+                return Ok(());
+            }
+            if data.enabled(guild_id, "levels").await? {
+                levels::on_message(ctx, new_message, data).await?;
+            }
+            if data.enabled(guild_id, "tags").await? {
+                tags::on_message(ctx, new_message, framework, data).await?;
+            }
+            Ok(())
+        }
+        FullEvent::GuildMemberAddition { ctx, new_member } => {
+            if !data.enabled(Some(new_member.guild_id), "welcome").await? {
+                return Ok(());
+            }
+            welcome::on_member_join(ctx, new_member, data).await
+        }
+        FullEvent::GuildMemberRemoval {
+            ctx,
+            guild_id,
+            user,
+            ..
+        } => {
+            if !data.enabled(Some(*guild_id), "welcome").await? {
+                return Ok(());
+            }
+            welcome::on_member_leave(ctx, *guild_id, user, data).await
+        }
+        FullEvent::MessageUpdate { ctx, event, .. } => {
+            if !data.enabled(event.guild_id, "message_log").await? {
+                return Ok(());
+            }
+            message_log::on_update(ctx, event, data).await
+        }
+        FullEvent::MessageDelete {
+            ctx,
+            channel_id,
+            deleted_message_id,
+            guild_id,
+        } => {
+            if !data.enabled(*guild_id, "message_log").await? {
+                return Ok(());
+            }
+            message_log::on_delete(ctx, *guild_id, *channel_id, *deleted_message_id, data).await
+        }
+        FullEvent::MessageDeleteBulk {
+            ctx,
+            channel_id,
+            multiple_deleted_messages_ids,
+            guild_id,
+        } => {
+            if !data.enabled(*guild_id, "message_log").await? {
+                return Ok(());
+            }
+            message_log::on_delete_bulk(
+                ctx,
+                *guild_id,
+                *channel_id,
+                multiple_deleted_messages_ids,
+                data,
+            )
+            .await
+        }
         _ => Ok(()),
     }
 }
@@ -71,54 +226,89 @@ async fn pre_command(ctx: Context<'_>) {
     info!("Executing command {}...", ctx.command().qualified_name);
 }
 
-fn owners() -> Result<HashSet<UserId>, AppError> {
-    var("BEAN_BOT_OWNERS").map_or(Ok(HashSet::new()), |arg| {
-        arg.split(',')
-            .map(|owner| Ok(owner.parse::<u64>()?.into()))
-            .collect()
-    })
+/// Stops commands of modules that are off in the guild. Unregistering them only hides the slash
+/// commands, this also catches prefix commands and stale command lists.
+async fn command_check(ctx: Context<'_>) -> Result<bool, AppError> {
+    let root = ctx
+        .parent_commands()
+        .first()
+        .copied()
+        .unwrap_or(ctx.command());
+    let module = match ctx.data().command_modules.get(&root.name) {
+        Some(module) => *module,
+        None => return Ok(true),
+    };
+    if ctx.data().enabled(ctx.guild_id(), module).await? {
+        return Ok(true);
+    }
+    ctx.send(
+        poise::CreateReply::new()
+            .content(component::turned_off(module))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(false)
 }
 
-async fn app() -> Result<(), AppError> {
-    let db = db::connect::<ConnType>();
-    db::run_pending_migrations(&mut db.get()?);
+async fn app(config: Config) -> Result<(), AppError> {
+    let db = db::Database::connect(&config.database_url, &config.pool);
+    db.run_pending_migrations()?;
 
+    let mut commands = cmd::core();
+    let mut command_modules = HashMap::new();
+    for module in cmd::modules() {
+        if config.modules.contains(module.name) {
+            for command in &module.commands {
+                command_modules.insert(command.name.clone(), module.name);
+            }
+            commands.extend(module.commands);
+        }
+    }
+    // Prefixes live as long as the bot
+    let mut prefixes = config
+        .prefixes
+        .into_iter()
+        .map(|p| -> &'static str { Box::leak(p.into_boxed_str()) });
     let options = poise::FrameworkOptions {
-        commands: vec![
-            general::help(),
-            general::shutdown(),
-            general::say(),
-            general::ask_matthias(),
-            general::eight_ball(),
-            fav_msgs::mystery(),
-            fav_msgs::add(),
-            roles::rolemenu(),
-            roles::roles(),
-        ],
+        commands,
         event_handler: |event, framework, user_data| {
             Box::pin(on_event(event, framework, user_data))
         },
         on_error: |err| Box::pin(on_error(err)),
         pre_command: |ctx| Box::pin(pre_command(ctx)),
-        owners: owners()?,
+        command_check: Some(|ctx| Box::pin(command_check(ctx))),
+        owners: config.owners,
         prefix_options: poise::PrefixFrameworkOptions {
-            prefix: Some("🫘".to_owned()),
-            additional_prefixes: vec![Prefix::Literal("$"), Prefix::Literal("beans")],
+            prefix: prefixes.next().map(str::to_owned),
+            additional_prefixes: prefixes.map(Prefix::Literal).collect(),
             ..Default::default()
         },
         ..Default::default()
     };
-    let token = var("DISCORD_TOKEN").expect("Missing DISCORD_TOKEN");
-    let intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
+    component::set_key(&config.component_key);
 
+    let modules = module_settings::Modules::new(db.clone(), config.modules);
+    let dev_guilds = config.dev_guilds;
     let framework = poise::Framework::new(options, move |ctx, ready, framework| {
         Box::pin(async move {
-            poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-            info!("Registered commands and logged in as {}", ready.user.name);
-            Ok(Data { db })
+            scheduler::start(ctx.clone(), db.clone(), modules.clone());
+            scheduler::start_daily(ctx.clone(), db.clone(), modules.clone());
+            let data = Data {
+                db,
+                msg_cache: Default::default(),
+                responders: Default::default(),
+                modules,
+                command_modules,
+                dev_guilds,
+            };
+            // Guilds register their commands as they become available
+            let commands = &framework.options().commands;
+            registration::register_globally(ctx, commands, &data, false).await?;
+            info!("Logged in as {}", ready.user.name);
+            Ok(data)
         })
     });
-    let mut client = serenity::Client::builder(token, intents)
+    let mut client = serenity::Client::builder(config.token, config.intents)
         .framework(framework)
         .await?;
     client.start().await?;
@@ -127,9 +317,28 @@ async fn app() -> Result<(), AppError> {
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    let args = config::Args::parse(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, config::USAGE);
+        std::process::exit(2);
+    });
+    let config = Config::load(args.config.as_deref()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    if args.check {
+        println!(
+            "Config is valid, {} of {} modules enabled",
+            config.modules.len(),
+            cmd::modules().len()
+        );
+        return;
+    }
 
-    if let Err(e) = app().await {
+    env_logger::Builder::new()
+        .filter_level(config.log_level)
+        .parse_default_env()
+        .init();
+    if let Err(e) = app(config).await {
         log::error!("{}", e);
         std::process::exit(1);
     }
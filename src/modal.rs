@@ -0,0 +1,168 @@
+//! Modals, forms that collect several inputs at once.
+//!
+//! Their custom ids are built and signed like those of components, keyed by a [`ModalAction`]
+//! instead. A [`Form`] carries what the modal is about in its id, while its inputs are a
+//! `poise::Modal` struct that is parsed once the user submits.
+
+use crate::component::{self, Rejection, TooLong};
+use crate::{AppError, Bot, Context, Data, ModalAction};
+use chrono::{Duration, Utc};
+use log::warn;
+use poise::serenity_prelude as serenity;
+use poise::{BoxFuture, Modal};
+use serenity::{CreateInteractionResponse, ModalInteraction, UserId};
+use std::sync::atomic::Ordering;
+
+/// How long a user has to fill out a form
+const FORM_HOURS: i64 = 1;
+
+pub trait Form: Sized {
+    const ACTION: ModalAction;
+    /// The module the form belongs to, it does nothing in guilds that turned it off
+    const MODULE: &'static str;
+    /// Bump this when the fields change, so older forms get a notice instead of being misread
+    const VERSION: u32 = 1;
+
+    /// The inputs shown to the user
+    type Inputs: Modal + Send;
+
+    fn encode(&self) -> Vec<String>;
+
+    /// Returns `None` if the fields don't make up a valid payload
+    fn decode(fields: &[&str]) -> Option<Self>;
+
+    fn handle<'a>(
+        self,
+        ctx: &'a Bot,
+        event: &'a ModalInteraction,
+        data: &'a Data,
+        inputs: Self::Inputs,
+    ) -> BoxFuture<'a, Result<(), AppError>>;
+
+    /// The response showing the modal to `user`, prefilled with `defaults`
+    fn open(
+        &self,
+        user: UserId,
+        defaults: Option<Self::Inputs>,
+    ) -> Result<CreateInteractionResponse, TooLong> {
+        let mut parts = vec![Self::ACTION.to_string(), format!("v{}", Self::VERSION)];
+        parts.extend(self.encode());
+        let expires = Utc::now() + Duration::hours(FORM_HOURS);
+        let custom_id = component::sign(&parts, Some(user), Some(expires))?;
+        Ok(Self::Inputs::create(defaults, custom_id))
+    }
+}
+
+type Handler = for<'a> fn(
+    &'a Bot,
+    &'a ModalInteraction,
+    &'a Data,
+    u32,
+    &'a [&'a str],
+) -> Option<Result<BoxFuture<'a, Result<(), AppError>>, &'static str>>;
+
+pub struct Route {
+    action: ModalAction,
+    module: &'static str,
+    handler: Handler,
+}
+
+impl Route {
+    pub const fn of<F: Form>() -> Route {
+        Route {
+            action: F::ACTION,
+            module: F::MODULE,
+            handler: decode_and_handle::<F>,
+        }
+    }
+}
+
+inventory::collect!(Route);
+
+fn decode_and_handle<'a, F: Form>(
+    ctx: &'a Bot,
+    event: &'a ModalInteraction,
+    data: &'a Data,
+    version: u32,
+    fields: &'a [&'a str],
+) -> Option<Result<BoxFuture<'a, Result<(), AppError>>, &'static str>> {
+    if version != F::VERSION {
+        return None;
+    }
+    let form = F::decode(fields)?;
+    Some(F::Inputs::parse(event.data.clone()).map(|inputs| form.handle(ctx, event, data, inputs)))
+}
+
+/// Answers a slash command by showing the form
+pub async fn show<F: Form>(
+    ctx: Context<'_>,
+    form: &F,
+    defaults: Option<F::Inputs>,
+) -> Result<(), AppError> {
+    match ctx {
+        poise::Context::Application(app) => {
+            app.interaction
+                .create_response(ctx, form.open(ctx.author().id, defaults)?)
+                .await?;
+            app.has_sent_initial_response.store(true, Ordering::SeqCst);
+        }
+        poise::Context::Prefix(_) => {
+            ctx.say("This only works as a slash command").await?;
+        }
+    }
+    Ok(())
+}
+
+/// Hands a submitted modal to the handler registered for its action.
+/// Modals that aren't ours, like the ones collectors wait for, are left alone.
+pub async fn dispatch(ctx: &Bot, event: &ModalInteraction, data: &Data) -> Result<(), AppError> {
+    let custom_id = &event.data.custom_id;
+    let action: ModalAction = match component::action(custom_id) {
+        Some(action) => action,
+        None => return Ok(()),
+    };
+    let route = inventory::iter::<Route>
+        .into_iter()
+        .find(|r| r.action == action);
+    if let Some(route) = route {
+        if !data.enabled(event.guild_id, route.module).await? {
+            return reply(ctx, event, &component::turned_off(route.module)).await;
+        }
+    }
+    let (version, fields) = match component::unseal(custom_id, event.user.id) {
+        Ok(unsealed) => unsealed,
+        Err(rejection) => {
+            if let Rejection::Forged = rejection {
+                warn!(
+                    "Rejected forged modal id {} of {}",
+                    custom_id, event.user.id
+                );
+            }
+            return reply(ctx, event, &rejection.message("form")).await;
+        }
+    };
+
+    let handler = route.and_then(|r| (r.handler)(ctx, event, data, version, &fields));
+    match handler {
+        Some(Ok(handler)) => handler.await,
+        Some(Err(msg)) => reply(ctx, event, msg).await,
+        None => {
+            warn!("Could not decode modal id {}", custom_id);
+            reply(ctx, event, &Rejection::Outdated.message("form")).await
+        }
+    }
+}
+
+pub async fn reply(ctx: &Bot, event: &ModalInteraction, content: &str) -> Result<(), AppError> {
+    event
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::Message(
+                serenity::CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(content),
+            ),
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use crate::cmd::module_settings::Modules;
+use crate::cmd::*;
+use crate::Db;
+use log::error;
+use poise::serenity_prelude as serenity;
+use std::time::Duration;
+
+const TICK: Duration = Duration::from_secs(30);
+/// Daily jobs check every hour so they can follow each member's timezone
+const DAILY_TICK: Duration = Duration::from_secs(60 * 60);
+
+/// Spawns the background task running all time based jobs.
+/// Jobs pick up everything that is due, so work missed during downtime is caught up on start.
+/// They leave alone what belongs to guilds that turned their module off, until it is on again.
+pub fn start(ctx: serenity::Context, db: Db, modules: Modules) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK);
+        loop {
+            interval.tick().await;
+            if let Err(e) = reminders::deliver_due(&ctx, &db, &modules).await {
+                error!("Failed to deliver reminders: {:?}", e);
+            }
+            if let Err(e) = polls::close_due(&ctx, &db, &modules).await {
+                error!("Failed to close polls: {:?}", e);
+            }
+            if let Err(e) = giveaways::end_due(&ctx, &db, &modules).await {
+                error!("Failed to end giveaways: {:?}", e);
+            }
+            if let Err(e) = moderation::lift_expired(&ctx, &db, &modules).await {
+                error!("Failed to lift expired punishments: {:?}", e);
+            }
+            if let Err(e) = shop::expire_due(&ctx, &db, &modules).await {
+                error!("Failed to expire bought roles: {:?}", e);
+            }
+            if let Err(e) = birthdays::take_roles(&ctx, &db, &modules).await {
+                error!("Failed to take birthday roles: {:?}", e);
+            }
+        }
+    });
+}
+
+/// Spawns the background task for jobs that happen once a day per member, like birthdays.
+pub fn start_daily(ctx: serenity::Context, db: Db, modules: Modules) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(DAILY_TICK);
+        loop {
+            interval.tick().await;
+            if let Err(e) = birthdays::celebrate_due(&ctx, &db, &modules).await {
+                error!("Failed to celebrate birthdays: {:?}", e);
+            }
+        }
+    });
+}
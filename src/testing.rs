@@ -0,0 +1,252 @@
+//! Helpers for driving handlers without a gateway connection.
+//!
+//! [`FakeDiscord`] is a local HTTP server standing in for the Discord REST API. It records every
+//! request and answers with whatever a test registered for the route, `204 No Content` otherwise.
+//! Handlers get a [`Bot`] pointed at it, and a [`Data`] on the test database.
+
+use crate::cmd::module_settings::Modules;
+use crate::{component, db, Bot, Data};
+use poise::serenity_prelude as serenity;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Once};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+pub const APPLICATION_ID: u64 = 1000;
+pub const BOT_ID: u64 = 1001;
+
+/// A request the fake API received, its path without `/api/v10`
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: Value,
+}
+
+#[derive(Default)]
+struct State {
+    requests: Vec<Request>,
+    responses: HashMap<(String, String), (u16, Value)>,
+}
+
+pub struct FakeDiscord {
+    url: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeDiscord {
+    pub async fn start() -> FakeDiscord {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::<Mutex<State>>::default();
+        let server = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, server.clone()));
+            }
+        });
+        FakeDiscord { url, state }
+    }
+
+    /// Answers `method` requests to `path` with `body` from now on
+    pub fn respond(&self, method: &str, path: &str, status: u16, body: Value) {
+        self.state
+            .lock()
+            .unwrap()
+            .responses
+            .insert((method.to_owned(), path.to_owned()), (status, body));
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// The requests made with `method`, in order
+    pub fn requests_to(&self, method: &str) -> Vec<Request> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.method == method)
+            .collect()
+    }
+
+    pub fn bot(&self) -> Bot {
+        let http = serenity::HttpBuilder::new("fake-token")
+            .proxy(&self.url)
+            .ratelimiter_disabled(true)
+            .application_id(serenity::ApplicationId::new(APPLICATION_ID))
+            .build();
+        Bot {
+            http: Arc::new(http),
+            cache: Arc::new(serenity::Cache::new()),
+        }
+    }
+}
+
+async fn serve(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let (read, mut write) = stream.into_split();
+    let mut read = BufReader::new(read);
+    // Connections are kept alive, so read requests until the client hangs up
+    loop {
+        let mut line = String::new();
+        if read.read_line(&mut line).await.unwrap_or(0) == 0 {
+            return;
+        }
+        let mut parts = line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_owned();
+        let target = parts.next().unwrap_or_default();
+        let path = target.split('?').next().unwrap_or_default();
+        let path = path.strip_prefix("/api/v10").unwrap_or(path).to_owned();
+
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            read.read_line(&mut header).await.unwrap();
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        read.read_exact(&mut body).await.unwrap();
+        let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+        let (status, response) = {
+            let mut state = state.lock().unwrap();
+            let response = state
+                .responses
+                .get(&(method.clone(), path.clone()))
+                .cloned()
+                .unwrap_or((204, Value::Null));
+            state.requests.push(Request { method, path, body });
+            response
+        };
+        let response = if response.is_null() {
+            String::new()
+        } else {
+            response.to_string()
+        };
+        let head = format!(
+            "HTTP/1.1 {} Fake\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n",
+            status,
+            response.len()
+        );
+        if write
+            .write_all((head + &response).as_bytes())
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Sets up what every test needs once per process
+pub fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| component::set_key("test key"));
+}
+
+/// Handler data on the test database
+pub fn data() -> Data {
+    init();
+    let db = db::Database::for_tests();
+    let modules = crate::cmd::modules();
+    Data {
+        db: db.clone(),
+        msg_cache: Default::default(),
+        responders: Default::default(),
+        modules: Modules::new(db, modules.iter().map(|m| m.name).collect()),
+        command_modules: modules
+            .iter()
+            .flat_map(|m| m.commands.iter().map(|c| (c.name.clone(), m.name)))
+            .collect(),
+        dev_guilds: Default::default(),
+    }
+}
+
+pub fn user(id: u64) -> Value {
+    json!({
+        "id": id.to_string(),
+        "username": format!("user{}", id),
+        "discriminator": "0",
+        "global_name": null,
+        "avatar": null,
+    })
+}
+
+pub fn member(guild_id: u64, user_id: u64, roles: &[u64]) -> Value {
+    json!({
+        "guild_id": guild_id.to_string(),
+        "user": user(user_id),
+        "nick": null,
+        "roles": roles.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+        "joined_at": "2023-01-01T00:00:00+00:00",
+        "deaf": false,
+        "mute": false,
+        "flags": 0,
+        "pending": false,
+    })
+}
+
+pub fn message(guild_id: u64, channel_id: u64, message_id: u64, author_id: u64) -> Value {
+    json!({
+        "id": message_id.to_string(),
+        "channel_id": channel_id.to_string(),
+        "guild_id": guild_id.to_string(),
+        "author": user(author_id),
+        "content": format!("Message {}", message_id),
+        "timestamp": "2023-01-01T00:00:00+00:00",
+        "edited_timestamp": null,
+        "tts": false,
+        "mention_everyone": false,
+        "mentions": [],
+        "mention_roles": [],
+        "attachments": [],
+        "embeds": [],
+        "pinned": false,
+        "type": 0,
+        "flags": 0,
+    })
+}
+
+/// A button press by `user_id` on a message the bot posted, `custom_id` as the bot built it
+pub fn button_press(
+    custom_id: &str,
+    guild_id: u64,
+    channel_id: u64,
+    user_id: u64,
+) -> serenity::ComponentInteraction {
+    serde_json::from_value(json!({
+        "id": "2000",
+        "application_id": APPLICATION_ID.to_string(),
+        "type": 3,
+        "data": {
+            "custom_id": custom_id,
+            "component_type": 2,
+        },
+        "guild_id": guild_id.to_string(),
+        "channel_id": channel_id.to_string(),
+        "member": member(guild_id, user_id, &[]),
+        "token": "interaction-token",
+        "version": 1,
+        "message": message(guild_id, channel_id, 3000, BOT_ID),
+        "app_permissions": "0",
+        "locale": "en-US",
+        "guild_locale": "en-US",
+        "entitlements": [],
+        "authorizing_integration_owners": {},
+        "attachment_size_limit": 10485760,
+    }))
+    .expect("Invalid component interaction")
+}
+
+/// The path interaction responses are posted to
+pub fn callback_path(event: &serenity::ComponentInteraction) -> String {
+    format!("/interactions/{}/{}/callback", event.id, event.token)
+}
@@ -0,0 +1,75 @@
+use crate::AppError;
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude as serenity;
+use serenity::{CacheHttp, GuildId, Member, PartialGuild, UserId};
+use std::time::Duration;
+
+/// Parses durations like `90m`, `1h 30min`, `2 days and 3 hours` or `a week`
+pub fn parse_duration(input: &str) -> Result<Duration, humantime::DurationError> {
+    let normalized = input
+        .trim()
+        .trim_start_matches("in ")
+        .replace(',', " ")
+        .replace(" and ", " ");
+    let normalized = match normalized.split_once(' ') {
+        Some(("a" | "an", rest)) => format!("1 {}", rest),
+        _ => normalized,
+    };
+    humantime::parse_duration(&normalized)
+}
+
+/// The point in time `duration` from now, `None` if it is too far out to be represented
+pub fn from_now(duration: Duration) -> Option<DateTime<Utc>> {
+    Utc::now().checked_add_signed(chrono::Duration::from_std(duration).ok()?)
+}
+
+/// Formats a point in time as a Discord timestamp, rendered relative to the reader (e.g. "in 2 hours")
+pub fn relative_time(time: DateTime<Utc>) -> String {
+    format!("<t:{}:R>", time.timestamp())
+}
+
+/// Whether a request failed because the thing it was about is gone, e.g. a deleted message
+pub fn is_not_found(err: &serenity::Error) -> bool {
+    match err {
+        serenity::Error::Http(err) => {
+            err.status_code() == Some(serenity::http::StatusCode::NOT_FOUND)
+        }
+        _ => false,
+    }
+}
+
+/// Position of the highest role of a member, 0 if they only have @everyone
+pub fn top_role_position(guild: &PartialGuild, member: &Member) -> u16 {
+    member
+        .roles
+        .iter()
+        .filter_map(|r| guild.roles.get(r))
+        .map(|r| r.position)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Whether `actor` ranks above `target`, like Discord decides who may moderate whom:
+/// the owner ranks above everyone, everyone else by their highest role.
+/// Users that are not in the guild rank below everyone.
+pub async fn outranks(
+    ctx: impl CacheHttp,
+    guild_id: GuildId,
+    actor: UserId,
+    target: UserId,
+) -> Result<bool, AppError> {
+    let guild = guild_id.to_partial_guild(&ctx).await?;
+    if actor == guild.owner_id {
+        return Ok(true);
+    }
+    if target == guild.owner_id {
+        return Ok(false);
+    }
+    let target = match guild_id.member(&ctx, target).await {
+        Ok(member) => member,
+        Err(e) if is_not_found(&e) => return Ok(true),
+        Err(e) => return Err(e.into()),
+    };
+    let actor = guild_id.member(&ctx, actor).await?;
+    Ok(top_role_position(&guild, &actor) > top_role_position(&guild, &target))
+}